@@ -0,0 +1,195 @@
+//! Running one turn's approved tool calls: consecutive read-only calls run concurrently,
+//! results come back in the model's order.
+
+use tauri::{AppHandle, Runtime};
+use crate::agent_system::tools;
+use crate::core_traits::rag::RagService;
+use crate::events::{
+    self, AgentEventKind, DirectoryFinding, ExploreFindings, ExploreProgress, ExploreStage, ScanProgress,
+};
+use serde_json::Value;
+use futures::future::join_all;
+
+/// Tools that only read the project; they need no approval and consecutive calls to them
+/// run concurrently
+const READ_ONLY_TOOLS: [&str; 6] = [
+    "agent_read_file", "agent_list_dir", "agent_batch_read", "agent_scan_directory", "agent_find_symbol", "agent_find_references",
+];
+
+/// Whether `tool_name` only reads the project
+pub fn is_read_only(tool_name: &str) -> bool {
+    READ_ONLY_TOOLS.contains(&tool_name)
+}
+
+/// What to do with one tool call once approvals are in
+pub enum CallPlan {
+    Run(Value),
+    /// Not executed (rejected, unparsable arguments); the text is the tool result
+    Skip(String),
+}
+
+impl CallPlan {
+    /// Whether the call can run alongside its read-only neighbours
+    fn is_parallel_safe(&self, tool_name: &str) -> bool {
+        match self {
+            CallPlan::Run(_) => is_read_only(tool_name),
+            CallPlan::Skip(_) => true,
+        }
+    }
+}
+
+/// Run the planned calls and return their results in the original order.
+///
+/// Runs of read-only calls execute concurrently; any other call runs on its own, so reads
+/// after a write see the written file.
+pub async fn execute_in_order<R: Runtime>(app: &AppHandle<R>, rag: &dyn RagService, event_id: &str, project_root: &str, planned: &[(&str, CallPlan)]) -> Vec<String> {
+    let mut results = Vec::with_capacity(planned.len());
+    let mut start = 0;
+
+    while start < planned.len() {
+        let end = planned[start..].iter()
+            .position(|(name, plan)| !plan.is_parallel_safe(name))
+            .map_or(planned.len(), |offset| start + offset);
+
+        if end > start {
+            if end - start > 1 {
+                println!("[AgentRunner] Running {} read-only tool calls in parallel", end - start);
+            }
+            let batch = planned[start..end].iter().map(|(name, plan)| run_planned(app, rag, event_id, project_root, name, plan));
+            results.extend(join_all(batch).await);
+            start = end;
+        } else {
+            let (name, plan) = &planned[start];
+            results.push(run_planned(app, rag, event_id, project_root, name, plan).await);
+            start += 1;
+        }
+    }
+
+    results
+}
+
+async fn run_planned<R: Runtime>(app: &AppHandle<R>, rag: &dyn RagService, event_id: &str, project_root: &str, tool_name: &str, plan: &CallPlan) -> String {
+    let args = match plan {
+        CallPlan::Run(args) => args,
+        CallPlan::Skip(result) => return result.clone(),
+    };
+
+    // Use recursive scan for agent_scan_directory to enable progress callbacks
+    if tool_name == "agent_scan_directory" {
+        let rel_path = args["rel_path"].as_str().or_else(|| args["path"].as_str()).unwrap_or(".").to_string();
+        let pattern = args["pattern"].as_str().map(|s| s.to_string());
+        let max_depth = args["max_depth"].as_u64().map(|v| v as usize);
+        let max_files = args["max_files"].as_u64().map(|v| v as usize);
+
+        let tool_result = match crate::commands::core_wrappers::agent_scan_directory_with_progress(
+            app, event_id, project_root.to_string(), rel_path, pattern, max_depth, max_files
+        ).await {
+            Ok(res) => res,
+            Err(e) => format!("Error: {}", e)
+        };
+        emit_explore_findings(app, event_id, &tool_result);
+        return tool_result;
+    }
+
+    match tools::execute_tool_internal(tool_name, args, project_root, rag).await {
+        Ok(res) => res,
+        Err(e) => format!("Error: {}", e)
+    }
+}
+
+/// Send explore_findings event for agent_scan_directory
+fn emit_explore_findings<R: Runtime>(app: &AppHandle<R>, event_id: &str, tool_result: &str) {
+    let Ok(scan_result) = serde_json::from_str::<Value>(tool_result) else { return };
+    let total_files = scan_result["stats"]["totalFiles"].as_u64().unwrap_or(0);
+    let total_dirs = scan_result["stats"]["totalDirectories"].as_u64().unwrap_or(0);
+
+    // Send analyzing progress event (scanning done, now analyzing findings)
+    events::emit_agent(app, event_id, AgentEventKind::ExploreProgress {
+        explore_progress: ExploreProgress {
+            phase: ExploreStage::Analyzing,
+            current_path: None,
+            current_file: None,
+            progress: ScanProgress { total: 1, scanned: 1, ..Default::default() },
+        },
+    });
+
+    // Build directories array from scan result with sample files
+    let directories = if let (Some(dirs_arr), Some(files_arr)) = (
+        scan_result["directories"].as_array(),
+        scan_result["files"].as_array()
+    ) {
+        dirs_arr.iter().filter_map(|dir_value| {
+            let dir_path = dir_value.as_str()?;
+            let dir_prefix = if dir_path == "." {
+                String::new()
+            } else {
+                format!("{}/", dir_path)
+            };
+
+            // Find files in this directory
+            let dir_files: Vec<String> = files_arr.iter()
+                .filter_map(|f| f.as_str())
+                .filter(|f| f.starts_with(&dir_prefix) || dir_path == ".")
+                .filter(|f| {
+                    // Only direct children (no more slashes after the directory prefix)
+                    let rest = if dir_path == "." { *f } else { f.strip_prefix(&dir_prefix).unwrap_or(f) };
+                    !rest.contains('/')
+                })
+                .take(5) // Take up to 5 sample files
+                .map(|f| f.split('/').last().unwrap_or(f).to_string())
+                .collect();
+
+            Some(DirectoryFinding {
+                path: dir_path.to_string(),
+                file_count: dir_files.len(),
+                key_files: dir_files,
+            })
+        }).collect::<Vec<DirectoryFinding>>()
+    } else {
+        Vec::new()
+    };
+
+    let summary = format!(
+        "探索完成：发现 {} 个文件和 {} 个目录",
+        total_files,
+        total_dirs
+    );
+
+    events::emit_agent(app, event_id, AgentEventKind::ExploreFindings {
+        explore_findings: ExploreFindings { summary, directories },
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::community::CommunityRagService;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_parallel_reads_keep_call_order() {
+        let temp = tempfile::tempdir().unwrap();
+        let project = temp.path();
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            std::fs::write(project.join(name), format!("contents of {}", name)).unwrap();
+        }
+
+        let planned = vec![
+            ("agent_read_file", CallPlan::Run(json!({ "rel_path": "a.txt" }))),
+            ("agent_read_file", CallPlan::Skip("User rejected the operation.".to_string())),
+            ("agent_read_file", CallPlan::Run(json!({ "rel_path": "b.txt" }))),
+            ("agent_write_file", CallPlan::Run(json!({ "rel_path": "c.txt", "content": "rewritten" }))),
+            ("agent_read_file", CallPlan::Run(json!({ "rel_path": "c.txt" }))),
+        ];
+
+        let app = tauri::test::mock_app();
+        let results = execute_in_order(app.handle(), &CommunityRagService::new(), "agent_test", &project.to_string_lossy(), &planned).await;
+
+        assert_eq!(results.len(), 5);
+        assert!(results[0].contains("contents of a.txt"));
+        assert_eq!(results[1], "User rejected the operation.");
+        assert!(results[2].contains("contents of b.txt"));
+        // The read after the write sees the new content
+        assert!(results[4].contains("rewritten"));
+    }
+}
@@ -1,20 +1,12 @@
-#[cfg(feature = "commercial")]
+// The agent loop compiles (and is tested) in both editions; only the commercial
+// build starts it from `launch_agent`.
+#![cfg_attr(not(feature = "commercial"), allow(dead_code))]
+
 pub mod base;
-#[cfg(feature = "commercial")]
 pub mod supervisor;
-#[cfg(feature = "commercial")]
 pub mod runner;
-#[cfg(feature = "commercial")]
+pub mod calls;
 pub mod tools;
 
-#[cfg(feature = "commercial")]
 pub use base::{AgentStatus, AgentContext};
-#[cfg(feature = "commercial")]
 pub use supervisor::Supervisor;
-
-#[cfg(not(feature = "commercial"))]
-pub struct Supervisor;
-#[cfg(not(feature = "commercial"))]
-impl Supervisor {
-    pub fn new() -> Self { Self }
-}
\ No newline at end of file
@@ -1,15 +1,20 @@
-use tauri::{AppHandle, Emitter};
+use tauri::{AppHandle, Runtime};
 use crate::agent_system::base::{AgentStatus, AgentContext};
 use crate::agent_system::supervisor::Supervisor;
-use crate::agent_system::tools;
+use crate::agent_system::calls::{self, CallPlan, execute_in_order};
 use crate::prompt_manager;
 use crate::ai_utils;
+use crate::usage_ledger::{self, UsageContext};
+use crate::events::{self, AgentEventKind, AgentRunStatus, AgentToolCall};
 use crate::core_traits::ai::{Message, Content};
+use crate::core_traits::rag::RagService;
 use serde_json::{json, Value};
+use std::sync::Arc;
 
-pub async fn run_agent_task(
-    app: AppHandle,
+pub async fn run_agent_task<R: Runtime>(
+    app: AppHandle<R>,
     supervisor: Supervisor,
+    rag: Arc<dyn RagService>,
     id: String,
     agent_type: String,
     context: AgentContext,
@@ -28,6 +33,7 @@ pub async fn run_agent_task(
         content: Content::Text(system_content_with_tools(&system_prompt)),
         tool_calls: None,
         tool_call_id: None,
+        thinking_blocks: None,
     });
 
     history.push(Message {
@@ -35,9 +41,14 @@ pub async fn run_agent_task(
         content: Content::Text(context.task_description.clone()),
         tool_calls: None,
         tool_call_id: None,
+        thinking_blocks: None,
     });
 
     let _ = supervisor.update_status(&id, AgentStatus::Running).await;
+
+    // Lets `cancel_ai_request` stop this run by agent id
+    let cancel_registration = ai_utils::cancel::register(&id);
+    let cancel_token = cancel_registration.token();
     
     let tools = vec![
         json!({
@@ -114,6 +125,36 @@ pub async fn run_agent_task(
                 }
             }
         }),
+        json!({
+            "type": "function",
+            "function": {
+                "name": "agent_find_symbol",
+                "description": "Find where functions, methods, types and classes are defined, using the project's syntax index. Accepts a name ('wait_for_approval') or a qualified name ('Supervisor::wait_for_approval', 'Store.get'). Returns file paths with line ranges.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "query": { "type": "string", "description": "Symbol name, qualified name or part of a name" },
+                        "limit": { "type": "number", "description": "Maximum number of definitions to return (default: 20)" }
+                    },
+                    "required": ["query"]
+                }
+            }
+        }),
+        json!({
+            "type": "function",
+            "function": {
+                "name": "agent_find_references",
+                "description": "Find the lines that mention a symbol by name (calls, uses, imports), excluding its definition. Matching is by name, so same-named symbols are not told apart.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "name": { "type": "string", "description": "Symbol name; for a qualified name only the last part is used" },
+                        "limit": { "type": "number", "description": "Maximum number of lines to return (default: 50)" }
+                    },
+                    "required": ["name"]
+                }
+            }
+        }),
         json!({
             "type": "function",
             "function": {
@@ -136,11 +177,28 @@ pub async fn run_agent_task(
 
     while loop_count < MAX_LOOPS {
         loop_count += 1;
-        let _ = app.emit("agent:status", json!({ "id": id, "status": "running", "progress": 0.15 + (loop_count as f32 * 0.05) }));
-        let _ = app.emit(&event_id, json!({ "type": "status", "status": "running", "progress": 0.15 + (loop_count as f32 * 0.05) }));
-        let _ = app.emit(&event_id, json!({ "type": "log", "message": "Thinking..." }));
+        let progress = 0.15 + (loop_count as f32 * 0.05);
+        events::emit_agent_status(&app, &event_id, &id, AgentRunStatus::Running, Some(progress), None);
+        events::emit_agent(&app, &event_id, AgentEventKind::Log { message: "Thinking...".to_string() });
+
+        let usage_ctx = UsageContext {
+            project_root: context.project_root.clone(),
+            conversation_id: None,
+            agent_id: Some(id.clone()),
+        };
+        let stream = usage_ledger::scope(
+            usage_ctx,
+            ai_utils::agent_stream_chat(&app, &context.provider_config, history.clone(), &id, Some(tools.clone())),
+        );
+        let stream_result = match ai_utils::cancel::with_cancellation(&cancel_token, stream).await {
+            Ok(result) => result,
+            Err(_) => {
+                report_cancelled(&app, &supervisor, &id, &event_id).await;
+                return;
+            }
+        };
 
-        match ai_utils::agent_stream_chat(&app, &context.provider_config, history.clone(), &id, Some(tools.clone())).await {
+        match stream_result {
             Ok(ai_message) => {
                 if let Content::Text(ref text) = ai_message.content {
                     if !text.is_empty() {
@@ -152,167 +210,93 @@ pub async fn run_agent_task(
                     if tool_calls.is_empty() { break; }
                     history.push(ai_message.clone());
 
+                    // Approvals are requested in the model's order (read-only calls need none); the
+                    // approved calls then run with consecutive read-only calls in parallel
+                    let mut planned = Vec::with_capacity(tool_calls.len());
                     for tool_call in tool_calls {
                         let tool_name = &tool_call.function.name;
                         let args_res: Result<Value, _> = serde_json::from_str(&tool_call.function.arguments);
-                        
-                        let _ = app.emit(&event_id, json!({ "type": "log", "message": format!("Processing tool: {}", tool_name) }));
 
-                        let (tool_result, _success) = match args_res {
+                        events::emit_agent(&app, &event_id, AgentEventKind::Log { message: format!("Processing tool: {}", tool_name) });
+
+                        let plan = match args_res {
+                            Ok(args) if calls::is_read_only(tool_name) => {
+                                events::emit_agent(&app, &event_id, AgentEventKind::ToolCall {
+                                    tool_call: AgentToolCall {
+                                        id: tool_call.id.clone(),
+                                        tool: tool_name.clone(),
+                                        args: args.clone(),
+                                        is_partial: false,
+                                        auto_approved: true,
+                                    },
+                                });
+                                events::emit_agent(&app, &event_id, AgentEventKind::Log { message: format!("🚀 Executing {}...", tool_name) });
+                                CallPlan::Run(args)
+                            },
                             Ok(args) => {
                                 // Send final tool_call event with complete arguments (isPartial: false)
                                 // This marks the end of streaming and requests user approval
                                 println!("[AgentRunner] Requesting authorization for: {}, event_id={}", tool_name, event_id);
-                                let emit_result = app.emit(&event_id, json!({
-                                    "type": "tool_call",
-                                    "toolCall": {
-                                        "id": tool_call.id.clone(),
-                                        "tool": tool_name,
-                                        "args": args,
-                                        "isPartial": false
-                                    }
-                                }));
-                                if let Err(e) = emit_result {
-                                    eprintln!("[AgentRunner] ERROR emitting event: {}", e);
-                                } else {
-                                    eprintln!("[AgentRunner] Event emitted successfully");
-                                }
+                                events::emit_agent(&app, &event_id, AgentEventKind::ToolCall {
+                                    tool_call: AgentToolCall {
+                                        id: tool_call.id.clone(),
+                                        tool: tool_name.clone(),
+                                        args: args.clone(),
+                                        is_partial: false,
+                                        auto_approved: false,
+                                    },
+                                });
 
                                 let _ = supervisor.update_status(&id, AgentStatus::WaitingForTool).await;
                                 // Send waitingfortool status event to frontend
-                                let _ = app.emit("agent:status", json!({ "id": id.clone(), "status": "waitingfortool" }));
-                                let _ = app.emit(&event_id, json!({ "type": "status", "status": "waitingfortool" }));
+                                events::emit_agent_status(&app, &event_id, &id, AgentRunStatus::WaitingForTool, None, None);
+
+                                let approved = match ai_utils::cancel::with_cancellation(&cancel_token, supervisor.wait_for_approval(id.clone())).await {
+                                    Ok(approved) => approved,
+                                    Err(_) => {
+                                        report_cancelled(&app, &supervisor, &id, &event_id).await;
+                                        return;
+                                    }
+                                };
 
-                                let approved = supervisor.wait_for_approval(id.clone()).await;
-                                
                                 if approved {
-                                    let _ = app.emit("agent:status", json!({ "id": id, "status": "running" }));
-                                    let _ = app.emit(&event_id, json!({ "type": "status", "status": "running" }));
-                                    let _ = app.emit(&event_id, json!({ "type": "log", "message": format!("🚀 Executing {}...", tool_name) }));
+                                    events::emit_agent_status(&app, &event_id, &id, AgentRunStatus::Running, None, None);
+                                    events::emit_agent(&app, &event_id, AgentEventKind::Log { message: format!("🚀 Executing {}...", tool_name) });
                                 }
 
                                 let _ = supervisor.update_status(&id, if approved { AgentStatus::Running } else { AgentStatus::Stopped }).await;
 
                                 if !approved {
-                                    ("User rejected the operation.".to_string(), false)
+                                    CallPlan::Skip("User rejected the operation.".to_string())
                                 } else {
                                     if tool_name == "agent_write_file" {
                                         if let Some(path) = args["rel_path"].as_str() {
                                             created_files.push(path.to_string());
                                         }
                                     }
-
-                                    // Use recursive scan for agent_scan_directory to enable progress callbacks
-                                    let tool_result = if tool_name == "agent_scan_directory" {
-                                        let rel_path = args["rel_path"].as_str().or_else(|| args["path"].as_str()).unwrap_or(".").to_string();
-                                        let pattern = args["pattern"].as_str().map(|s| s.to_string());
-                                        let max_depth = args["max_depth"].as_u64().map(|v| v as usize);
-                                        let max_files = args["max_files"].as_u64().map(|v| v as usize);
-
-                                        match crate::commands::core_wrappers::agent_scan_directory_with_progress(
-                                            &app, &event_id, context.project_root.clone(), rel_path, pattern, max_depth, max_files
-                                        ).await {
-                                            Ok(res) => res,
-                                            Err(e) => format!("Error: {}", e)
-                                        }
-                                    } else {
-                                        match tools::execute_tool_internal(tool_name, &args, &context.project_root).await {
-                                            Ok(res) => res,
-                                            Err(e) => format!("Error: {}", e)
-                                        }
-                                    };
-
-                                    // Send explore_findings event for agent_scan_directory
-                                    if tool_name == "agent_scan_directory" {
-                                        if let Ok(scan_result) = serde_json::from_str::<Value>(&tool_result) {
-                                            let total_files = scan_result["stats"]["totalFiles"].as_u64().unwrap_or(0);
-                                            let total_dirs = scan_result["stats"]["totalDirectories"].as_u64().unwrap_or(0);
-
-                                            // Send analyzing progress event (scanning done, now analyzing findings)
-                                            let _ = app.emit(&event_id, json!({
-                                                "type": "explore_progress",
-                                                "exploreProgress": {
-                                                    "phase": "analyzing",
-                                                    "progress": {
-                                                        "total": 1,
-                                                        "scanned": 1,
-                                                        "byDirectory": {}
-                                                    }
-                                                }
-                                            }));
-
-                                            // Build directories array from scan result with sample files
-                                            let directories = if let (Some(dirs_arr), Some(files_arr)) = (
-                                                scan_result["directories"].as_array(),
-                                                scan_result["files"].as_array()
-                                            ) {
-                                                dirs_arr.iter().filter_map(|dir_value| {
-                                                    let dir_path = dir_value.as_str()?;
-                                                    let dir_prefix = if dir_path == "." {
-                                                        String::new()
-                                                    } else {
-                                                        format!("{}/", dir_path)
-                                                    };
-
-                                                    // Find files in this directory
-                                                    let dir_files: Vec<String> = files_arr.iter()
-                                                        .filter_map(|f| f.as_str())
-                                                        .filter(|f| f.starts_with(&dir_prefix) || dir_path == ".")
-                                                        .filter(|f| {
-                                                            // Only direct children (no more slashes after the directory prefix)
-                                                            let rest = if dir_path == "." { *f } else { f.strip_prefix(&dir_prefix).unwrap_or(f) };
-                                                            !rest.contains('/')
-                                                        })
-                                                        .take(5) // Take up to 5 sample files
-                                                        .map(|f| f.split('/').last().unwrap_or(f).to_string())
-                                                        .collect();
-
-                                                    let file_count = dir_files.len();
-
-                                                    Some(json!({
-                                                        "path": dir_path,
-                                                        "fileCount": file_count,
-                                                        "keyFiles": dir_files
-                                                    }))
-                                                }).collect::<Vec<serde_json::Value>>()
-                                            } else {
-                                                Vec::new()
-                                            };
-
-                                            let summary = format!(
-                                                "探索完成：发现 {} 个文件和 {} 个目录",
-                                                total_files,
-                                                total_dirs
-                                            );
-
-                                            let _ = app.emit(&event_id, json!({
-                                                "type": "explore_findings",
-                                                "exploreFindings": {
-                                                    "summary": summary,
-                                                    "directories": directories
-                                                }
-                                            }));
-                                        }
-                                    }
-
-                                    (tool_result, true)
+                                    CallPlan::Run(args)
                                 }
                             },
-                            Err(e) => (format!("Failed to parse arguments: {}", e), false)
+                            Err(e) => CallPlan::Skip(format!("Failed to parse arguments: {}", e))
                         };
+                        planned.push((tool_name.as_str(), plan));
+                    }
 
+                    let results = execute_in_order(&app, rag.as_ref(), &event_id, &context.project_root, &planned).await;
+                    for (tool_call, tool_result) in tool_calls.iter().zip(results) {
                         history.push(Message {
                             role: "tool".to_string(),
                             content: Content::Text(tool_result),
                             tool_calls: None,
                             tool_call_id: Some(tool_call.id.clone()),
+                            thinking_blocks: None,
                         });
                     }
                 } else { break; }
             },
             Err(e) => {
-                let _ = app.emit(&event_id, json!({ "type": "error", "error": e }));
-                let _ = app.emit("agent:status", json!({ "id": id, "status": "failed", "error": e }));
+                events::emit_agent(&app, &event_id, AgentEventKind::Error { error: e.clone() });
+                events::emit_agent_status(&app, &event_id, &id, AgentRunStatus::Failed, None, Some(e));
                 return;
             }
         }
@@ -332,19 +316,82 @@ pub async fn run_agent_task(
     }
 
     let _ = supervisor.update_status(&id, AgentStatus::Completed).await;
-    let _ = app.emit("agent:status", json!({ "id": id, "status": "completed", "progress": 1.0 }));
-    let _ = app.emit(&event_id, json!({ "type": "status", "status": "completed", "progress": 1.0 }));
+    events::emit_agent_status(&app, &event_id, &id, AgentRunStatus::Completed, Some(1.0), None);
 
-    // Send final result through unified stream
-    let _ = app.emit(&event_id, json!({
-        "type": "result",
-        "result": final_output
-    }));
-    
-    // Also keep agent:result for backward compatibility and global listeners
-    let _ = app.emit("agent:result", json!({ "id": id, "output": final_output }));
+    // Send final result through unified stream, and on agent:result for global listeners
+    events::emit_agent_result(&app, &event_id, &id, final_output);
+}
+
+/// Cancellation is its own outcome: the agent is stopped, not failed
+async fn report_cancelled<R: Runtime>(app: &AppHandle<R>, supervisor: &Supervisor, id: &str, event_id: &str) {
+    println!("[AgentRunner] Agent {} cancelled", id);
+    let _ = supervisor.update_status(id, AgentStatus::Stopped).await;
+    events::emit_agent_status(app, event_id, id, AgentRunStatus::Stopped, None, None);
+    events::emit_agent(app, event_id, AgentEventKind::Cancelled);
 }
 
 fn system_content_with_tools(base: &str) -> String {
     format!("{}\n\nAlways use tools. Show the code you intend to write clearly. Wait for approval before writing files.", base)
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cassette::{Cassette, Interaction, MockServer};
+    use crate::community::CommunityRagService;
+    use crate::core_traits::ai::AIProviderConfig;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+    use std::time::Duration;
+    use tauri::Listener;
+
+    #[tokio::test]
+    async fn test_agent_reads_file_then_reports() {
+        let server = MockServer::replay(Cassette::default()
+            .with(Interaction::stream_tool_call("call_1", "agent_read_file", json!({ "rel_path": "notes.txt" })))
+            .with(Interaction::stream_text(&["The notes ", "say hello."])))
+            .await
+            .unwrap();
+
+        let temp = tempfile::tempdir().unwrap();
+        let project = temp.path();
+        std::fs::write(project.join("notes.txt"), "hello from the notes").unwrap();
+
+        let app = tauri::test::mock_app();
+        let result = Arc::new(Mutex::new(None));
+        let sink = result.clone();
+        app.listen("agent:result", move |event| {
+            let payload: Value = serde_json::from_str(event.payload()).unwrap();
+            *sink.lock().unwrap() = payload["output"].as_str().map(|s| s.to_string());
+        });
+
+        let supervisor = Supervisor::new();
+        supervisor.register_agent("agent-1".to_string(), "explore".to_string()).await;
+
+        let context = AgentContext {
+            project_root: project.to_string_lossy().to_string(),
+            task_description: "Summarize notes.txt".to_string(),
+            initial_prompt: String::new(),
+            variables: HashMap::new(),
+            provider_config: AIProviderConfig {
+                id: "cassette".to_string(),
+                base_url: server.chat_url(),
+                models: vec!["gpt-4o-mini".to_string()],
+                ..Default::default()
+            },
+        };
+        // Nobody approves anything here, so a read that waited for approval would time out
+        let run = run_agent_task(app.handle().clone(), supervisor, Arc::new(CommunityRagService::new()), "agent-1".to_string(), "explore".to_string(), context);
+        tokio::time::timeout(Duration::from_secs(10), run).await.unwrap();
+
+        assert_eq!(result.lock().unwrap().as_deref(), Some("The notes say hello."));
+
+        // The second request carries the tool result back to the model
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        let tool_message = requests[1]["messages"].as_array().unwrap().iter()
+            .find(|m| m["role"] == "tool")
+            .unwrap();
+        assert_eq!(tool_message["tool_call_id"], "call_1");
+        assert!(tool_message["content"].as_str().unwrap().contains("hello from the notes"));
+    }
+}
@@ -1,4 +1,5 @@
-use ifainew_core::agent;
+use crate::core_traits::rag::RagService;
+use crate::commands::core_wrappers as agent;
 use serde_json::Value;
 
 /// Unescape escape sequences in a string (e.g., "\\n" -> "\n", "\\t" -> "\t")
@@ -44,6 +45,7 @@ pub async fn execute_tool_internal(
     tool_name: &str,
     args: &Value,
     project_root: &str,
+    rag: &dyn RagService,
 ) -> Result<String, String> {
     println!("[AgentTools] Executing tool: {} with args: {}", tool_name, args);
 
@@ -111,6 +113,30 @@ pub async fn execute_tool_internal(
                 max_files
             ).await
         },
+        "agent_find_symbol" => {
+            let query = args["query"].as_str().or_else(|| args["name"].as_str()).unwrap_or("");
+            let limit = args["limit"].as_u64().map_or(20, |v| v as usize);
+            let definitions = rag.find_symbol(project_root, query, limit).await?;
+            if definitions.is_empty() {
+                return Ok(format!("No definitions found for '{}'", query));
+            }
+            Ok(definitions.iter()
+                .map(|d| format!("{}:{}-{} {} {}", d.file_path, d.line_start, d.line_end, d.kind, d.qualified_name))
+                .collect::<Vec<_>>()
+                .join("\n"))
+        },
+        "agent_find_references" => {
+            let name = args["name"].as_str().or_else(|| args["query"].as_str()).unwrap_or("");
+            let limit = args["limit"].as_u64().map_or(50, |v| v as usize);
+            let references = rag.find_references(project_root, name, limit).await?;
+            if references.is_empty() {
+                return Ok(format!("No references found for '{}'", name));
+            }
+            Ok(references.iter()
+                .map(|r| format!("{}:{}: {}", r.file_path, r.line, r.content))
+                .collect::<Vec<_>>()
+                .join("\n"))
+        },
         _ => Err(format!("Tool {} not implemented or allowed in Agent System", tool_name))
     }
 }
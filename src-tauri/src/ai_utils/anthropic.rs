@@ -0,0 +1,516 @@
+//! Anthropic Messages API adapter.
+//!
+//! Converts the OpenAI-shaped `Message`/`ContentPart`/`ToolCall` values used throughout the
+//! app into the Messages format (and back), so `AIProtocol::Anthropic` providers can be
+//! called directly instead of through an OpenAI-compatible proxy.
+
+use crate::core_traits::ai::{Message, Content, ContentPart, ToolCall, FunctionCall};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use super::StreamChunk;
+
+pub const API_VERSION: &str = "2023-06-01";
+
+/// The Messages API requires `max_tokens`; this matches what the chat panel expects from other providers
+const DEFAULT_MAX_TOKENS: u32 = 8192;
+
+/// Smallest thinking budget the API accepts
+const MIN_THINKING_BUDGET: u32 = 1024;
+
+/// Resolve the endpoint from a configured base URL.
+///
+/// Accepts either the full endpoint (`.../v1/messages`) or just the host (`https://api.anthropic.com`).
+pub fn messages_url(base_url: &str) -> String {
+    let trimmed = base_url.trim_end_matches('/');
+    if trimmed.ends_with("/messages") {
+        trimmed.to_string()
+    } else if trimmed.ends_with("/v1") {
+        format!("{}/messages", trimmed)
+    } else {
+        format!("{}/v1/messages", trimmed)
+    }
+}
+
+/// Model listing endpoint for the same base URL forms as [`messages_url`]
+pub fn models_url(base_url: &str) -> String {
+    let messages = messages_url(base_url);
+    format!("{}/models", messages.trim_end_matches("/messages"))
+}
+
+pub fn build_request_body(model: &str, messages: &[Message], tools: Option<&[Value]>, stream: bool) -> Value {
+    let (system, converted) = convert_messages(messages);
+
+    let mut body = json!({
+        "model": model,
+        "max_tokens": DEFAULT_MAX_TOKENS,
+        "messages": converted,
+        "stream": stream
+    });
+
+    if let Some(system) = system {
+        body["system"] = json!(system);
+    }
+
+    if let Some(tools) = tools {
+        if !tools.is_empty() {
+            body["tools"] = Value::Array(tools.iter().map(convert_tool).collect());
+        }
+    }
+
+    body
+}
+
+/// Enable extended thinking with `budget` tokens and re-attach each tool-calling turn's
+/// thinking blocks (carried on its `Message`) to the assistant turn built from it.
+///
+/// While thinking is on, the API rejects a tool-result turn whose assistant turn has no
+/// signed thinking. When the latest tool-calling turn has none (it was answered with thinking
+/// off, or by another provider), thinking is left off for this request instead.
+pub fn apply_thinking(body: &mut Value, messages: &[Message], budget: u32) {
+    let thinking_by_tool_id: Vec<(&str, &Vec<Value>)> = messages.iter()
+        .filter_map(|m| Some((m.tool_calls.as_ref()?.first()?.id.as_str(), m.thinking_blocks.as_ref()?)))
+        .filter(|(_, blocks)| !blocks.is_empty())
+        .collect();
+    let first_tool_id = |turn: &Value| turn["content"].as_array()
+        .and_then(|blocks| blocks.iter().find(|b| b["type"] == "tool_use"))
+        .and_then(|b| b["id"].as_str())
+        .map(|id| id.to_string());
+    let find_thinking = |id: &str| thinking_by_tool_id.iter().find(|(key, _)| *key == id).map(|(_, blocks)| *blocks);
+
+    let turns = body["messages"].as_array().cloned().unwrap_or_default();
+    let latest_assistant = turns.iter().rev().find(|t| t["role"] == "assistant");
+    if let Some(id) = latest_assistant.and_then(first_tool_id) {
+        if find_thinking(&id).is_none() {
+            println!("[Anthropic] No thinking blocks for tool turn {}; sending without thinking", id);
+            return;
+        }
+    }
+
+    let budget = budget.max(MIN_THINKING_BUDGET);
+    body["thinking"] = json!({ "type": "enabled", "budget_tokens": budget });
+    // max_tokens covers thinking and the answer
+    body["max_tokens"] = json!(budget + DEFAULT_MAX_TOKENS);
+
+    for turn in body["messages"].as_array_mut().into_iter().flatten() {
+        if turn["role"] != "assistant" {
+            continue;
+        }
+        let Some(thinking) = first_tool_id(turn).and_then(|id| find_thinking(&id)) else { continue };
+        let Some(blocks) = turn["content"].as_array_mut() else { continue };
+        let mut with_thinking = thinking.clone();
+        with_thinking.append(blocks);
+        *blocks = with_thinking;
+    }
+}
+
+/// Hoist system messages into the top-level `system` field and convert the rest into
+/// strictly alternating user/assistant turns made of content blocks.
+fn convert_messages(messages: &[Message]) -> (Option<String>, Vec<Value>) {
+    let mut system_parts: Vec<String> = Vec::new();
+    let mut converted: Vec<Value> = Vec::new();
+
+    for msg in messages {
+        match msg.role.as_str() {
+            "system" => {
+                let text = content_text(&msg.content);
+                if !text.is_empty() {
+                    system_parts.push(text);
+                }
+            }
+            "assistant" => {
+                let mut blocks = content_blocks(&msg.content);
+                if let Some(tool_calls) = &msg.tool_calls {
+                    for tc in tool_calls {
+                        // Arguments arrive as a JSON string; the Messages API wants an object
+                        let input = serde_json::from_str::<Value>(&tc.function.arguments)
+                            .ok()
+                            .filter(|v| v.is_object())
+                            .unwrap_or_else(|| json!({}));
+                        blocks.push(json!({
+                            "type": "tool_use",
+                            "id": tc.id,
+                            "name": tc.function.name,
+                            "input": input
+                        }));
+                    }
+                }
+                push_blocks(&mut converted, "assistant", blocks);
+            }
+            "tool" => {
+                let block = json!({
+                    "type": "tool_result",
+                    "tool_use_id": msg.tool_call_id.clone().unwrap_or_default(),
+                    "content": content_text(&msg.content)
+                });
+                push_blocks(&mut converted, "user", vec![block]);
+            }
+            _ => {
+                push_blocks(&mut converted, "user", content_blocks(&msg.content));
+            }
+        }
+    }
+
+    let system = if system_parts.is_empty() { None } else { Some(system_parts.join("\n\n")) };
+    (system, converted)
+}
+
+/// Append blocks, merging into the previous turn when it has the same role
+/// (e.g. several tool results, or a tool result followed by a user question).
+fn push_blocks(converted: &mut Vec<Value>, role: &str, blocks: Vec<Value>) {
+    if blocks.is_empty() {
+        return;
+    }
+
+    if let Some(last) = converted.last_mut() {
+        if last["role"] == role {
+            if let Some(existing) = last["content"].as_array_mut() {
+                existing.extend(blocks);
+                return;
+            }
+        }
+    }
+
+    converted.push(json!({ "role": role, "content": blocks }));
+}
+
+fn content_text(content: &Content) -> String {
+    match content {
+        Content::Text(text) => text.clone(),
+        Content::Parts(parts) => parts.iter()
+            .filter_map(|p| match p {
+                ContentPart::Text { text, .. } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+    }
+}
+
+fn content_blocks(content: &Content) -> Vec<Value> {
+    match content {
+        Content::Text(text) => {
+            if text.is_empty() {
+                vec![]
+            } else {
+                vec![json!({ "type": "text", "text": text })]
+            }
+        }
+        Content::Parts(parts) => parts.iter()
+            .filter_map(|p| match p {
+                ContentPart::Text { text, .. } => {
+                    if text.is_empty() { None } else { Some(json!({ "type": "text", "text": text })) }
+                }
+                ContentPart::ImageUrl { image_url } => Some(image_block(&image_url.url)),
+            })
+            .collect(),
+    }
+}
+
+fn image_block(url: &str) -> Value {
+    // data:<media_type>;base64,<data>
+    if let Some(rest) = url.strip_prefix("data:") {
+        if let Some((meta, data)) = rest.split_once(',') {
+            let media_type = meta.trim_end_matches(";base64");
+            return json!({
+                "type": "image",
+                "source": { "type": "base64", "media_type": media_type, "data": data }
+            });
+        }
+    }
+
+    json!({
+        "type": "image",
+        "source": { "type": "url", "url": url }
+    })
+}
+
+/// OpenAI `{"type":"function","function":{...}}` tool definitions to Anthropic `input_schema` tools
+fn convert_tool(tool: &Value) -> Value {
+    let function = if tool["function"].is_object() { &tool["function"] } else { tool };
+    let schema = if function["parameters"].is_object() {
+        function["parameters"].clone()
+    } else {
+        json!({ "type": "object", "properties": {} })
+    };
+
+    json!({
+        "name": function["name"],
+        "description": function["description"].as_str().unwrap_or(""),
+        "input_schema": schema
+    })
+}
+
+/// Convert a non-streaming Messages API response into an assistant `Message`
+pub fn parse_response(res_json: &Value) -> Result<Message, String> {
+    let blocks = res_json["content"].as_array().ok_or_else(|| {
+        eprintln!("[AIUtils] Error: 'content' is missing in Anthropic response: {}", res_json);
+        "Malformed AI response: content field missing".to_string()
+    })?;
+
+    let mut text = String::new();
+    let mut tool_calls = Vec::new();
+    let mut thinking = Vec::new();
+
+    for block in blocks {
+        match block["type"].as_str() {
+            Some("text") => text.push_str(block["text"].as_str().unwrap_or("")),
+            // Reasoning stays out of the text; the signed blocks go back with tool results
+            Some("thinking") | Some("redacted_thinking") => thinking.push(block.clone()),
+            Some("tool_use") => tool_calls.push(ToolCall {
+                id: block["id"].as_str().unwrap_or("").to_string(),
+                r#type: "function".to_string(),
+                function: FunctionCall {
+                    name: block["name"].as_str().unwrap_or("").to_string(),
+                    arguments: block["input"].to_string(),
+                },
+            }),
+            _ => {}
+        }
+    }
+
+    let (tool_calls, thinking_blocks) = if tool_calls.is_empty() {
+        (None, None)
+    } else {
+        (Some(tool_calls), if thinking.is_empty() { None } else { Some(thinking) })
+    };
+
+    Ok(Message {
+        role: "assistant".to_string(),
+        content: Content::Text(text),
+        tool_calls,
+        tool_call_id: None,
+        thinking_blocks,
+    })
+}
+
+/// Decoder for streaming Messages API responses.
+///
+/// Besides turning events into chunks it rebuilds the turn's thinking blocks (text plus
+/// signature) so they can be replayed with tool results.
+#[derive(Default)]
+pub(crate) struct StreamDecoder {
+    thinking: BTreeMap<i32, Value>,
+}
+
+impl StreamDecoder {
+    /// Decode one SSE `data:` payload
+    pub(crate) fn decode(&mut self, data: &str) -> Result<Vec<StreamChunk>, serde_json::Error> {
+        let event: Value = serde_json::from_str(data)?;
+        let index = event["index"].as_i64().unwrap_or(0) as i32;
+
+        match (event["type"].as_str(), event["content_block"]["type"].as_str(), event["delta"]["type"].as_str()) {
+            (Some("content_block_start"), Some("thinking" | "redacted_thinking"), _) => {
+                self.thinking.insert(index, event["content_block"].clone());
+            }
+            (Some("content_block_delta"), _, Some("thinking_delta")) => {
+                if let Some(block) = self.thinking.get_mut(&index) {
+                    let text = format!("{}{}",
+                        block["thinking"].as_str().unwrap_or(""), event["delta"]["thinking"].as_str().unwrap_or(""));
+                    block["thinking"] = json!(text);
+                }
+            }
+            (Some("content_block_delta"), _, Some("signature_delta")) => {
+                if let Some(block) = self.thinking.get_mut(&index) {
+                    block["signature"] = event["delta"]["signature"].clone();
+                }
+            }
+            _ => {}
+        }
+
+        Ok(decode_event(&event, index))
+    }
+
+    /// The turn's thinking blocks, in order
+    pub(crate) fn thinking_blocks(&self) -> Vec<Value> {
+        self.thinking.values().cloned().collect()
+    }
+}
+
+fn decode_event(event: &Value, index: i32) -> Vec<StreamChunk> {
+    match event["type"].as_str() {
+        Some("message_start") => vec![StreamChunk::Usage(event["message"]["usage"].clone())],
+        Some("content_block_start") => {
+            let block = &event["content_block"];
+            match block["type"].as_str() {
+                Some("tool_use") => vec![StreamChunk::ToolCall {
+                    index,
+                    id: block["id"].as_str().map(|s| s.to_string()),
+                    name: block["name"].as_str().map(|s| s.to_string()),
+                    arguments: None,
+                }],
+                Some("text") => match block["text"].as_str() {
+                    Some(t) if !t.is_empty() => vec![StreamChunk::Text(t.to_string())],
+                    _ => vec![],
+                },
+                Some("thinking") => match block["thinking"].as_str() {
+                    Some(t) if !t.is_empty() => vec![StreamChunk::Reasoning(t.to_string())],
+                    _ => vec![],
+                },
+                _ => vec![],
+            }
+        }
+        Some("content_block_delta") => {
+            let delta = &event["delta"];
+            match delta["type"].as_str() {
+                Some("text_delta") => vec![StreamChunk::Text(delta["text"].as_str().unwrap_or("").to_string())],
+                Some("thinking_delta") => vec![StreamChunk::Reasoning(delta["thinking"].as_str().unwrap_or("").to_string())],
+                Some("input_json_delta") => vec![StreamChunk::ToolCall {
+                    index,
+                    id: None,
+                    name: None,
+                    arguments: delta["partial_json"].as_str().map(|s| s.to_string()),
+                }],
+                _ => vec![],
+            }
+        }
+        Some("message_delta") => vec![StreamChunk::Usage(event["usage"].clone())],
+        Some("message_stop") => vec![StreamChunk::Stop],
+        Some("error") => vec![StreamChunk::Error(
+            event["error"]["message"].as_str().unwrap_or("Unknown Anthropic stream error").to_string()
+        )],
+        // ping, content_block_stop
+        _ => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text_message(role: &str, text: &str) -> Message {
+        Message {
+            role: role.to_string(),
+            content: Content::Text(text.to_string()),
+            tool_calls: None,
+            tool_call_id: None,
+            thinking_blocks: None,
+        }
+    }
+
+    #[test]
+    fn test_system_prompt_is_hoisted() {
+        let messages = vec![
+            text_message("system", "You are helpful."),
+            text_message("user", "Hi"),
+        ];
+
+        let body = build_request_body("claude-sonnet-4-5", &messages, None, false);
+        assert_eq!(body["system"], "You are helpful.");
+        assert_eq!(body["messages"].as_array().unwrap().len(), 1);
+        assert_eq!(body["messages"][0]["role"], "user");
+        assert_eq!(body["messages"][0]["content"][0]["text"], "Hi");
+    }
+
+    #[test]
+    fn test_tool_round_trip_blocks() {
+        let mut assistant = text_message("assistant", "");
+        assistant.tool_calls = Some(vec![ToolCall {
+            id: "toolu_1".to_string(),
+            r#type: "function".to_string(),
+            function: FunctionCall {
+                name: "agent_read_file".to_string(),
+                arguments: r#"{"rel_path":"src/main.rs"}"#.to_string(),
+            },
+        }]);
+        let mut result = text_message("tool", "fn main() {}");
+        result.tool_call_id = Some("toolu_1".to_string());
+
+        let messages = vec![text_message("user", "Read main"), assistant, result, text_message("user", "Thanks")];
+        let body = build_request_body("claude-sonnet-4-5", &messages, None, true);
+        let converted = body["messages"].as_array().unwrap();
+
+        assert_eq!(converted.len(), 3);
+        assert_eq!(converted[1]["content"][0]["type"], "tool_use");
+        assert_eq!(converted[1]["content"][0]["input"]["rel_path"], "src/main.rs");
+        // Tool result and the follow-up question share one user turn
+        assert_eq!(converted[2]["role"], "user");
+        assert_eq!(converted[2]["content"][0]["type"], "tool_result");
+        assert_eq!(converted[2]["content"][0]["tool_use_id"], "toolu_1");
+        assert_eq!(converted[2]["content"][1]["text"], "Thanks");
+    }
+
+    #[test]
+    fn test_parse_response_with_tool_use() {
+        let res = json!({
+            "content": [
+                { "type": "text", "text": "Let me look." },
+                { "type": "tool_use", "id": "toolu_2", "name": "agent_list_dir", "input": { "rel_path": "." } }
+            ]
+        });
+
+        let msg = parse_response(&res).unwrap();
+        assert!(matches!(msg.content, Content::Text(ref t) if t == "Let me look."));
+        let calls = msg.tool_calls.unwrap();
+        assert_eq!(calls[0].function.name, "agent_list_dir");
+        assert_eq!(calls[0].function.arguments, r#"{"rel_path":"."}"#);
+    }
+
+    #[test]
+    fn test_thinking_is_streamed_and_replayed_with_tool_results() {
+        let events = [
+            json!({ "type": "content_block_start", "index": 0, "content_block": { "type": "thinking", "thinking": "", "signature": "" } }),
+            json!({ "type": "content_block_delta", "index": 0, "delta": { "type": "thinking_delta", "thinking": "Need the file." } }),
+            json!({ "type": "content_block_delta", "index": 0, "delta": { "type": "signature_delta", "signature": "sig-1" } }),
+            json!({ "type": "content_block_start", "index": 1, "content_block": { "type": "tool_use", "id": "toolu_think", "name": "agent_read_file" } }),
+        ];
+
+        let mut decoder = StreamDecoder::default();
+        let reasoning: String = events.iter()
+            .flat_map(|e| decoder.decode(&e.to_string()).unwrap())
+            .filter_map(|c| match c { StreamChunk::Reasoning(t) => Some(t), _ => None })
+            .collect();
+        assert_eq!(reasoning, "Need the file.");
+
+        let call = ToolCall {
+            id: "toolu_think".to_string(),
+            r#type: "function".to_string(),
+            function: FunctionCall { name: "agent_read_file".to_string(), arguments: "{}".to_string() },
+        };
+        let mut assistant = text_message("assistant", "");
+        assistant.tool_calls = Some(vec![call]);
+        assistant.thinking_blocks = Some(decoder.thinking_blocks());
+        let mut result = text_message("tool", "contents");
+        result.tool_call_id = Some("toolu_think".to_string());
+
+        // The blocks travel with the message, e.g. through the frontend and back
+        let messages: Vec<Message> = serde_json::from_value(
+            serde_json::to_value([text_message("user", "Read it"), assistant, result]).unwrap()
+        ).unwrap();
+        let mut body = build_request_body("claude-sonnet-4-5", &messages, None, true);
+        apply_thinking(&mut body, &messages, 2048);
+
+        assert_eq!(body["thinking"]["budget_tokens"], 2048);
+        let replayed = &body["messages"][1]["content"];
+        assert_eq!(replayed[0]["type"], "thinking");
+        assert_eq!(replayed[0]["thinking"], "Need the file.");
+        assert_eq!(replayed[0]["signature"], "sig-1");
+        assert_eq!(replayed[1]["type"], "tool_use");
+    }
+
+    #[test]
+    fn test_thinking_is_dropped_when_tool_turn_has_no_blocks() {
+        let mut assistant = text_message("assistant", "");
+        assistant.tool_calls = Some(vec![ToolCall {
+            id: "toolu_plain".to_string(),
+            r#type: "function".to_string(),
+            function: FunctionCall { name: "agent_read_file".to_string(), arguments: "{}".to_string() },
+        }]);
+        let mut result = text_message("tool", "contents");
+        result.tool_call_id = Some("toolu_plain".to_string());
+
+        let messages = [text_message("user", "Read it"), assistant, result];
+        let mut body = build_request_body("claude-sonnet-4-5", &messages, None, true);
+        apply_thinking(&mut body, &messages, 2048);
+
+        assert!(body.get("thinking").is_none());
+        assert_eq!(body["max_tokens"], DEFAULT_MAX_TOKENS);
+        assert_eq!(body["messages"][1]["content"][0]["type"], "tool_use");
+    }
+
+    #[test]
+    fn test_messages_url() {
+        assert_eq!(messages_url("https://api.anthropic.com"), "https://api.anthropic.com/v1/messages");
+        assert_eq!(messages_url("https://api.anthropic.com/v1/"), "https://api.anthropic.com/v1/messages");
+        assert_eq!(messages_url("https://proxy.local/v1/messages"), "https://proxy.local/v1/messages");
+    }
+}
@@ -0,0 +1,127 @@
+//! Cancellation registry for in-flight AI requests.
+//!
+//! Chat requests register under their `event_id`, agents under their agent id.
+//! `cancel_ai_request` flips the token; the request future is then dropped at its next
+//! await point, which also drops the underlying reqwest stream.
+
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
+
+/// Message used where a cancelled request has to be surfaced through a `String` error
+pub const CANCELLED_MESSAGE: &str = "Request cancelled";
+
+static REGISTRY: Lazy<Mutex<HashMap<String, CancelToken>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Clone)]
+pub struct CancelToken {
+    tx: Arc<watch::Sender<bool>>,
+}
+
+impl CancelToken {
+    fn new() -> Self {
+        let (tx, _) = watch::channel(false);
+        Self { tx: Arc::new(tx) }
+    }
+
+    pub fn cancel(&self) {
+        self.tx.send_replace(true);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        *self.tx.borrow()
+    }
+
+    /// Resolves once `cancel` has been called (immediately if it already was)
+    pub async fn cancelled(&self) {
+        let mut rx = self.tx.subscribe();
+        // The sender lives as long as `self`, so this only returns once the flag is set
+        let _ = rx.wait_for(|cancelled| *cancelled).await;
+    }
+}
+
+/// Keeps a request registered until dropped
+pub struct CancelRegistration {
+    key: String,
+    token: CancelToken,
+}
+
+impl CancelRegistration {
+    pub fn token(&self) -> CancelToken {
+        self.token.clone()
+    }
+}
+
+impl Drop for CancelRegistration {
+    fn drop(&mut self) {
+        let mut registry = REGISTRY.lock().unwrap();
+        // A newer request may have re-registered the same key; leave that one alone
+        if registry.get(&self.key).map_or(false, |t| Arc::ptr_eq(&t.tx, &self.token.tx)) {
+            registry.remove(&self.key);
+        }
+    }
+}
+
+/// The request was cancelled before it finished
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+pub fn register(key: &str) -> CancelRegistration {
+    let token = CancelToken::new();
+    REGISTRY.lock().unwrap().insert(key.to_string(), token.clone());
+    CancelRegistration { key: key.to_string(), token }
+}
+
+/// Cancel the request registered under `key`. Returns false if nothing was running.
+pub fn cancel(key: &str) -> bool {
+    match REGISTRY.lock().unwrap().get(key) {
+        Some(token) => {
+            token.cancel();
+            true
+        }
+        None => false,
+    }
+}
+
+/// Drive `fut` to completion unless `token` is cancelled first, in which case `fut` is dropped
+pub async fn with_cancellation<F, T>(token: &CancelToken, fut: F) -> Result<T, Cancelled>
+where
+    F: Future<Output = T>,
+{
+    tokio::select! {
+        biased;
+        _ = token.cancelled() => Err(Cancelled),
+        out = fut => Ok(out),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_cancel_drops_pending_future() {
+        let registration = register("test-event-cancel");
+        let token = registration.token();
+
+        let handle = tokio::spawn(async move {
+            with_cancellation(&token, tokio::time::sleep(Duration::from_secs(60))).await
+        });
+
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(cancel("test-event-cancel"));
+        assert_eq!(handle.await.unwrap(), Err(Cancelled));
+    }
+
+    #[tokio::test]
+    async fn test_registration_is_removed_on_drop() {
+        {
+            let _registration = register("test-event-drop");
+        }
+        assert!(!cancel("test-event-drop"));
+    }
+}
@@ -0,0 +1,564 @@
+//! Model discovery and connection checks for configured providers.
+//!
+//! Models are listed from the provider's own endpoint (OpenAI-compatible `/models`,
+//! Ollama `/api/tags`, Anthropic and Gemini `models`). Capabilities come from the listing
+//! when the provider reports them and from the model name otherwise.
+
+use crate::core_traits::ai::{AIProviderConfig, AIProtocol};
+use futures::future::join_all;
+use reqwest::{Client, RequestBuilder, StatusCode};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::time::{Duration, Instant};
+use super::{anthropic, gemini, http, routing};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Default Ollama port, used to recognise Ollama behind its OpenAI-compatible endpoint
+const OLLAMA_PORT: u16 = 11434;
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ModelCapability {
+    Chat,
+    Tools,
+    Vision,
+    Reasoning,
+    Embedding,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelInfo {
+    pub id: String,
+    pub display_name: Option<String>,
+    pub owned_by: Option<String>,
+    pub context_window: Option<u64>,
+    pub capabilities: Vec<ModelCapability>,
+    /// False when capabilities were guessed from the model name
+    pub capabilities_reported: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionTestResult {
+    pub ok: bool,
+    pub latency_ms: u64,
+    /// Which request was used for the check
+    pub endpoint: String,
+    pub status: Option<u16>,
+    pub model_count: Option<usize>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ListingApi {
+    OpenAI,
+    Ollama,
+    Anthropic,
+    Gemini,
+}
+
+fn listing_api(config: &AIProviderConfig) -> ListingApi {
+    match config.protocol {
+        AIProtocol::Anthropic => ListingApi::Anthropic,
+        AIProtocol::Gemini => ListingApi::Gemini,
+        _ if is_ollama(config) => ListingApi::Ollama,
+        _ => ListingApi::OpenAI,
+    }
+}
+
+fn is_ollama(config: &AIProviderConfig) -> bool {
+    if config.id.to_lowercase().contains("ollama") {
+        return true;
+    }
+    reqwest::Url::parse(&config.base_url)
+        .map(|url| url.port() == Some(OLLAMA_PORT) || url.path().starts_with("/api/"))
+        .unwrap_or(false)
+}
+
+/// `https://api.openai.com/v1/chat/completions` -> `https://api.openai.com/v1/models`
+fn openai_models_url(base_url: &str) -> String {
+    let trimmed = base_url.trim_end_matches('/');
+    let root = trimmed
+        .strip_suffix("/chat/completions")
+        .or_else(|| trimmed.strip_suffix("/completions"))
+        .unwrap_or(trimmed);
+    format!("{}/models", root)
+}
+
+fn ollama_url(base_url: &str, path: &str) -> String {
+    match reqwest::Url::parse(base_url) {
+        Ok(url) => format!("{}{}", url.origin().ascii_serialization(), path),
+        Err(_) => format!("http://localhost:{}{}", OLLAMA_PORT, path),
+    }
+}
+
+fn models_url(config: &AIProviderConfig, api: ListingApi) -> String {
+    match api {
+        ListingApi::OpenAI => openai_models_url(&config.base_url),
+        ListingApi::Ollama => ollama_url(&config.base_url, "/api/tags"),
+        ListingApi::Anthropic => format!("{}?limit=1000", anthropic::models_url(&config.base_url)),
+        ListingApi::Gemini => format!("{}?pageSize=1000", gemini::models_url(&config.base_url)),
+    }
+}
+
+/// Auth headers plus the provider's extra headers and query parameters, with the short discovery timeout
+fn with_auth(request: RequestBuilder, config: &AIProviderConfig, api: ListingApi) -> RequestBuilder {
+    let request = match api {
+        ListingApi::Anthropic => request
+            .header("x-api-key", &config.api_key)
+            .header("anthropic-version", anthropic::API_VERSION),
+        ListingApi::Gemini => request.header("x-goog-api-key", &config.api_key),
+        _ if config.api_key.is_empty() => request,
+        _ => request.header("Authorization", format!("Bearer {}", config.api_key)),
+    };
+    http::apply_provider_options(request, config, false).timeout(REQUEST_TIMEOUT)
+}
+
+/// List the models a provider serves
+pub async fn list_models(config: &AIProviderConfig) -> Result<Vec<ModelInfo>, String> {
+    let client = http::client_for(config)?;
+    let api = listing_api(config);
+    let url = models_url(config, api);
+
+    let body = fetch_json(with_auth(client.get(&url), config, api), &url).await?;
+    let mut models = parse_model_list(api, &body);
+
+    if api == ListingApi::Ollama {
+        // `/api/tags` has no capabilities; `/api/show` reports them per model on newer Ollama versions
+        let details = join_all(models.iter().map(|m| ollama_show(&client, config, &m.id))).await;
+        for (model, detail) in models.iter_mut().zip(details) {
+            if let Some(detail) = detail {
+                apply_ollama_show(model, &detail);
+            }
+        }
+    }
+
+    models.sort_by(|a, b| a.id.cmp(&b.id));
+    Ok(models)
+}
+
+/// Cheap connectivity and auth check.
+///
+/// Uses the model listing, which costs no tokens. OpenAI-compatible providers without a
+/// listing endpoint are checked with a one-token completion instead.
+pub async fn test_connection(config: &AIProviderConfig) -> ConnectionTestResult {
+    let client = match http::client_for(config) {
+        Ok(c) => c,
+        Err(e) => return failed_result(String::new(), Duration::ZERO, None, e),
+    };
+    let api = listing_api(config);
+    let url = models_url(config, api);
+
+    let start = Instant::now();
+    let response = with_auth(client.get(&url), config, api).send().await;
+    let elapsed = start.elapsed();
+
+    match response {
+        Ok(res) if res.status().is_success() => {
+            let status = res.status().as_u16();
+            let count = res.json::<Value>().await.ok().map(|body| parse_model_list(api, &body).len());
+            ConnectionTestResult {
+                ok: true,
+                latency_ms: elapsed.as_millis() as u64,
+                endpoint: url,
+                status: Some(status),
+                model_count: count,
+                error: None,
+            }
+        }
+        Ok(res) if res.status() == StatusCode::NOT_FOUND && api == ListingApi::OpenAI => {
+            println!("[Discovery] {} has no model listing, checking with a completion", url);
+            test_with_completion(&client, config).await
+        }
+        Ok(res) => {
+            let status = res.status();
+            let body = res.text().await.unwrap_or_default();
+            failed_result(url.clone(), elapsed, Some(status.as_u16()), describe_status(status, &body, &url))
+        }
+        Err(e) => failed_result(url.clone(), elapsed, None, describe_transport_error(&e, &url)),
+    }
+}
+
+async fn test_with_completion(client: &Client, config: &AIProviderConfig) -> ConnectionTestResult {
+    let Some(model) = routing::primary_model(config) else {
+        return failed_result(config.base_url.clone(), Duration::ZERO, None,
+            "The provider has no model listing and no model is configured to test with".to_string());
+    };
+
+    let body = json!({
+        "model": model,
+        "messages": [{ "role": "user", "content": "ping" }],
+        "max_tokens": 1,
+        "stream": false
+    });
+
+    let start = Instant::now();
+    let response = with_auth(client.post(&config.base_url), config, ListingApi::OpenAI)
+        .json(&body)
+        .send()
+        .await;
+    let elapsed = start.elapsed();
+    let url = config.base_url.clone();
+
+    match response {
+        Ok(res) if res.status().is_success() => ConnectionTestResult {
+            ok: true,
+            latency_ms: elapsed.as_millis() as u64,
+            endpoint: url,
+            status: Some(res.status().as_u16()),
+            model_count: None,
+            error: None,
+        },
+        Ok(res) => {
+            let status = res.status();
+            let body = res.text().await.unwrap_or_default();
+            failed_result(url.clone(), elapsed, Some(status.as_u16()), describe_status(status, &body, &url))
+        }
+        Err(e) => failed_result(url.clone(), elapsed, None, describe_transport_error(&e, &url)),
+    }
+}
+
+fn failed_result(endpoint: String, elapsed: Duration, status: Option<u16>, error: String) -> ConnectionTestResult {
+    eprintln!("[Discovery] Connection test failed: {}", error);
+    ConnectionTestResult {
+        ok: false,
+        latency_ms: elapsed.as_millis() as u64,
+        endpoint,
+        status,
+        model_count: None,
+        error: Some(error),
+    }
+}
+
+async fn fetch_json(request: RequestBuilder, url: &str) -> Result<Value, String> {
+    let response = request.send().await.map_err(|e| describe_transport_error(&e, url))?;
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(describe_status(status, &body, url));
+    }
+    response.json::<Value>().await
+        .map_err(|e| format!("Unexpected response from {}: {}", url, e))
+}
+
+/// Turn an HTTP failure into something a user can act on
+fn describe_status(status: StatusCode, body: &str, url: &str) -> String {
+    // Most providers wrap the reason in `{"error": {"message": ...}}` or `{"error": "..."}`
+    let detail = serde_json::from_str::<Value>(body).ok()
+        .and_then(|v| {
+            v["error"]["message"].as_str()
+                .or_else(|| v["error"].as_str())
+                .or_else(|| v["message"].as_str())
+                .map(|s| s.to_string())
+        })
+        .unwrap_or_else(|| body.chars().take(200).collect());
+
+    let hint = match status.as_u16() {
+        401 | 403 => "Authentication failed, check the API key",
+        404 => "Endpoint not found, check the base URL",
+        429 => "Rate limited by the provider, the key is valid but requests are being throttled",
+        500..=599 => "The provider reported a server error",
+        _ => "Request rejected",
+    };
+
+    if detail.trim().is_empty() {
+        format!("{} ({} from {})", hint, status, url)
+    } else {
+        format!("{} ({} from {}): {}", hint, status, url, detail.trim())
+    }
+}
+
+fn describe_transport_error(e: &reqwest::Error, url: &str) -> String {
+    if e.is_timeout() {
+        format!("Timed out after {}s waiting for {}", REQUEST_TIMEOUT.as_secs(), url)
+    } else if e.is_connect() {
+        format!("Could not connect to {}, check the base URL and that the server is running: {}", url, e)
+    } else {
+        format!("Network error talking to {}: {}", url, e)
+    }
+}
+
+fn parse_model_list(api: ListingApi, body: &Value) -> Vec<ModelInfo> {
+    let empty = vec![];
+    match api {
+        ListingApi::OpenAI | ListingApi::Anthropic => body["data"].as_array().unwrap_or(&empty)
+            .iter()
+            .filter_map(parse_openai_style_model)
+            .collect(),
+        ListingApi::Ollama => body["models"].as_array().unwrap_or(&empty)
+            .iter()
+            .filter_map(|m| {
+                let id = m["name"].as_str().or_else(|| m["model"].as_str())?;
+                Some(guessed(id, None, m["details"]["family"].as_str().map(|s| s.to_string())))
+            })
+            .collect(),
+        ListingApi::Gemini => body["models"].as_array().unwrap_or(&empty)
+            .iter()
+            .filter_map(parse_gemini_model)
+            .collect(),
+    }
+}
+
+fn guessed(id: &str, display_name: Option<String>, owned_by: Option<String>) -> ModelInfo {
+    ModelInfo {
+        id: id.to_string(),
+        display_name,
+        owned_by,
+        context_window: None,
+        capabilities: infer_capabilities(id),
+        capabilities_reported: false,
+    }
+}
+
+/// OpenAI `/models` entries, plus the extra fields OpenRouter/Groq-style gateways add
+/// and Anthropic's `display_name`
+fn parse_openai_style_model(m: &Value) -> Option<ModelInfo> {
+    let id = m["id"].as_str()?;
+    let mut info = guessed(
+        id,
+        m["display_name"].as_str().or_else(|| m["name"].as_str()).map(|s| s.to_string()),
+        m["owned_by"].as_str().map(|s| s.to_string()),
+    );
+
+    info.context_window = m["context_length"].as_u64()
+        .or_else(|| m["context_window"].as_u64());
+
+    let params = m["supported_parameters"].as_array();
+    let modalities = m["architecture"]["input_modalities"].as_array();
+    if params.is_some() || modalities.is_some() {
+        let has = |list: Option<&Vec<Value>>, name: &str| list.is_some_and(|l| l.iter().any(|v| v.as_str() == Some(name)));
+        let mut caps = vec![ModelCapability::Chat];
+        if has(params, "tools") {
+            caps.push(ModelCapability::Tools);
+        }
+        if has(modalities, "image") {
+            caps.push(ModelCapability::Vision);
+        }
+        if has(params, "reasoning") {
+            caps.push(ModelCapability::Reasoning);
+        }
+        info.capabilities = caps;
+        info.capabilities_reported = true;
+    }
+
+    Some(info)
+}
+
+fn parse_gemini_model(m: &Value) -> Option<ModelInfo> {
+    let name = m["name"].as_str()?;
+    let id = name.strip_prefix("models/").unwrap_or(name);
+    let methods: Vec<&str> = m["supportedGenerationMethods"].as_array()
+        .map(|a| a.iter().filter_map(|v| v.as_str()).collect())
+        .unwrap_or_default();
+
+    let mut caps = Vec::new();
+    if methods.contains(&"generateContent") {
+        // Every generateContent model accepts function declarations and images
+        caps.extend([ModelCapability::Chat, ModelCapability::Tools, ModelCapability::Vision]);
+        if m["thinking"].as_bool() == Some(true) {
+            caps.push(ModelCapability::Reasoning);
+        }
+    }
+    if methods.iter().any(|m| m.starts_with("embed")) {
+        caps.push(ModelCapability::Embedding);
+    }
+
+    Some(ModelInfo {
+        id: id.to_string(),
+        display_name: m["displayName"].as_str().map(|s| s.to_string()),
+        owned_by: Some("google".to_string()),
+        context_window: m["inputTokenLimit"].as_u64(),
+        capabilities: caps,
+        capabilities_reported: !methods.is_empty(),
+    })
+}
+
+async fn ollama_show(client: &Client, config: &AIProviderConfig, model: &str) -> Option<Value> {
+    let url = ollama_url(&config.base_url, "/api/show");
+    let response = with_auth(client.post(&url), config, ListingApi::Ollama)
+        .json(&json!({ "model": model }))
+        .send()
+        .await
+        .ok()?;
+    if !response.status().is_success() {
+        return None;
+    }
+    response.json::<Value>().await.ok()
+}
+
+fn apply_ollama_show(model: &mut ModelInfo, detail: &Value) {
+    // `model_info` keys are prefixed with the architecture, e.g. `llama.context_length`
+    model.context_window = detail["model_info"].as_object()
+        .and_then(|info| info.iter().find(|(k, _)| k.ends_with(".context_length")))
+        .and_then(|(_, v)| v.as_u64());
+
+    let Some(reported) = detail["capabilities"].as_array() else { return };
+    model.capabilities = reported.iter()
+        .filter_map(|c| match c.as_str()? {
+            "completion" => Some(ModelCapability::Chat),
+            "tools" => Some(ModelCapability::Tools),
+            "vision" => Some(ModelCapability::Vision),
+            "thinking" => Some(ModelCapability::Reasoning),
+            "embedding" => Some(ModelCapability::Embedding),
+            _ => None,
+        })
+        .collect();
+    model.capabilities_reported = true;
+}
+
+/// Best-effort capabilities from a model name, for listings that don't report them
+pub fn infer_capabilities(model_id: &str) -> Vec<ModelCapability> {
+    let id = model_id.to_lowercase();
+    let id = id.rsplit('/').next().unwrap_or(&id);
+
+    if id.contains("embed") {
+        return vec![ModelCapability::Embedding];
+    }
+    let non_chat = ["whisper", "tts", "dall-e", "moderation", "transcribe", "gpt-image", "rerank"];
+    if non_chat.iter().any(|m| id.contains(m)) {
+        return vec![];
+    }
+
+    let mut caps = vec![ModelCapability::Chat, ModelCapability::Tools];
+
+    let vision = ["vision", "4o", "gpt-4.1", "gpt-4-turbo", "gpt-5", "claude", "gemini", "glm-4v", "-vl", "llava", "pixtral"];
+    if vision.iter().any(|m| id.contains(m)) {
+        caps.push(ModelCapability::Vision);
+    }
+
+    let reasoning = ["reasoner", "-r1", "thinking", "qwq", "gpt-5"];
+    let o_series = ["o1", "o3", "o4"].iter().any(|p| id.starts_with(p));
+    if o_series || reasoning.iter().any(|m| id.contains(m)) {
+        caps.push(ModelCapability::Reasoning);
+    }
+
+    caps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Minimal HTTP server answering each request path with a canned `(status, body)`
+    async fn mock_server(routes: Vec<(&'static str, u16, &'static str)>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let routes = routes.clone();
+                tokio::spawn(async move {
+                    let mut buf = vec![0u8; 16 * 1024];
+                    let n = socket.read(&mut buf).await.unwrap_or(0);
+                    let request = String::from_utf8_lossy(&buf[..n]);
+                    let path = request.split_whitespace().nth(1).unwrap_or("/").to_string();
+
+                    let (status, body) = routes.iter()
+                        .find(|(prefix, _, _)| path.starts_with(prefix))
+                        .map(|(_, s, b)| (*s, *b))
+                        .unwrap_or((404, "{\"error\":{\"message\":\"no route\"}}"));
+
+                    let response = format!(
+                        "HTTP/1.1 {} MOCK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        status, body.len(), body
+                    );
+                    let _ = socket.write_all(response.as_bytes()).await;
+                });
+            }
+        });
+
+        format!("http://{}", addr)
+    }
+
+    fn provider(base_url: String) -> AIProviderConfig {
+        AIProviderConfig {
+            id: "mock".to_string(),
+            name: "Mock".to_string(),
+            api_key: "sk-test".to_string(),
+            base_url,
+            models: vec!["gpt-4o".to_string()],
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_list_openai_models() {
+        let base = mock_server(vec![(
+            "/v1/models",
+            200,
+            r#"{"data":[{"id":"text-embedding-3-small","owned_by":"openai"},{"id":"gpt-4o","owned_by":"openai"}]}"#,
+        )]).await;
+
+        let models = list_models(&provider(format!("{}/v1/chat/completions", base))).await.unwrap();
+        assert_eq!(models.len(), 2);
+        assert_eq!(models[0].id, "gpt-4o");
+        assert!(models[0].capabilities.contains(&ModelCapability::Vision));
+        assert_eq!(models[1].capabilities, vec![ModelCapability::Embedding]);
+    }
+
+    #[tokio::test]
+    async fn test_list_ollama_models_uses_show_capabilities() {
+        let base = mock_server(vec![
+            ("/api/tags", 200, r#"{"models":[{"name":"qwen3:8b","details":{"family":"qwen3"}}]}"#),
+            ("/api/show", 200, r#"{"capabilities":["completion","tools","thinking"],"model_info":{"qwen3.context_length":40960}}"#),
+        ]).await;
+
+        let mut config = provider(format!("{}/v1/chat/completions", base));
+        config.id = "ollama".to_string();
+        let models = list_models(&config).await.unwrap();
+
+        assert_eq!(models[0].id, "qwen3:8b");
+        assert_eq!(models[0].context_window, Some(40960));
+        assert!(models[0].capabilities_reported);
+        assert_eq!(models[0].capabilities, vec![ModelCapability::Chat, ModelCapability::Tools, ModelCapability::Reasoning]);
+    }
+
+    #[tokio::test]
+    async fn test_connection_reports_auth_error() {
+        let base = mock_server(vec![(
+            "/v1/models",
+            401,
+            r#"{"error":{"message":"Incorrect API key provided"}}"#,
+        )]).await;
+
+        let result = test_connection(&provider(format!("{}/v1/chat/completions", base))).await;
+        assert!(!result.ok);
+        assert_eq!(result.status, Some(401));
+        let error = result.error.unwrap();
+        assert!(error.contains("check the API key"));
+        assert!(error.contains("Incorrect API key provided"));
+    }
+
+    #[tokio::test]
+    async fn test_connection_falls_back_to_completion_without_listing() {
+        let base = mock_server(vec![(
+            "/chat/completions",
+            200,
+            r#"{"choices":[{"message":{"role":"assistant","content":"p"}}]}"#,
+        )]).await;
+
+        let result = test_connection(&provider(format!("{}/chat/completions", base))).await;
+        assert!(result.ok, "{:?}", result.error);
+        assert!(result.endpoint.ends_with("/chat/completions"));
+    }
+
+    #[tokio::test]
+    async fn test_connection_refused_is_explained() {
+        // Bind then drop to get a port nothing listens on
+        let port = TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap().port();
+        let result = test_connection(&provider(format!("http://127.0.0.1:{}/v1/chat/completions", port))).await;
+        assert!(!result.ok);
+        assert!(result.error.unwrap().contains("Could not connect"));
+    }
+
+    #[test]
+    fn test_models_urls() {
+        assert_eq!(openai_models_url("https://api.deepseek.com/chat/completions"), "https://api.deepseek.com/models");
+        assert_eq!(anthropic::models_url("https://api.anthropic.com"), "https://api.anthropic.com/v1/models");
+        assert_eq!(gemini::models_url(""), "https://generativelanguage.googleapis.com/v1beta/models");
+    }
+}
@@ -0,0 +1,421 @@
+//! Gemini `generateContent` / `streamGenerateContent` adapter.
+//!
+//! Maps the OpenAI-shaped history onto Gemini `contents`: roles become `user`/`model`,
+//! tool calls become `functionCall` parts, tool results `functionResponse` parts and
+//! image parts `inlineData`.
+
+use crate::core_traits::ai::{Message, Content, ContentPart, ToolCall, FunctionCall};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use super::StreamChunk;
+
+const DEFAULT_BASE_URL: &str = "https://generativelanguage.googleapis.com/v1beta";
+
+/// Schema keywords Gemini's OpenAPI subset rejects
+const UNSUPPORTED_SCHEMA_KEYS: [&str; 3] = ["additionalProperties", "$schema", "default"];
+
+/// Resolve the model endpoint from a configured base URL.
+///
+/// Accepts the API root (`https://generativelanguage.googleapis.com`), a versioned root
+/// (`.../v1beta`) or an empty string for the public endpoint.
+pub fn endpoint_url(base_url: &str, model: &str, stream: bool) -> String {
+    let root = api_root(base_url);
+    if stream {
+        format!("{}/models/{}:streamGenerateContent?alt=sse", root, model)
+    } else {
+        format!("{}/models/{}:generateContent", root, model)
+    }
+}
+
+/// Model listing endpoint for the same base URL forms as [`endpoint_url`]
+pub fn models_url(base_url: &str) -> String {
+    format!("{}/models", api_root(base_url))
+}
+
+fn api_root(base_url: &str) -> String {
+    let trimmed = base_url.trim_end_matches('/');
+    if trimmed.is_empty() {
+        DEFAULT_BASE_URL.to_string()
+    } else if let Some(idx) = trimmed.find("/models") {
+        // Full endpoint configured: keep the part before the model path
+        trimmed[..idx].to_string()
+    } else if trimmed.ends_with("/v1") || trimmed.ends_with("/v1beta") {
+        trimmed.to_string()
+    } else {
+        format!("{}/v1beta", trimmed)
+    }
+}
+
+pub fn build_request_body(messages: &[Message], tools: Option<&[Value]>) -> Value {
+    let (system, contents) = convert_messages(messages);
+
+    let mut body = json!({ "contents": contents });
+
+    if let Some(system) = system {
+        body["systemInstruction"] = json!({ "parts": [{ "text": system }] });
+    }
+
+    if let Some(tools) = tools {
+        let declarations: Vec<Value> = tools.iter().map(convert_tool).collect();
+        if !declarations.is_empty() {
+            body["tools"] = json!([{ "functionDeclarations": declarations }]);
+        }
+    }
+
+    body
+}
+
+/// Enable thinking with `budget` tokens and ask for thought summaries in the response
+pub fn apply_thinking(body: &mut Value, budget: u32) {
+    body["generationConfig"]["thinkingConfig"] = json!({ "thinkingBudget": budget, "includeThoughts": true });
+}
+
+fn convert_messages(messages: &[Message]) -> (Option<String>, Vec<Value>) {
+    let mut system_parts: Vec<String> = Vec::new();
+    let mut contents: Vec<Value> = Vec::new();
+    // functionResponse needs the function name, tool messages only carry the call id
+    let mut call_names: HashMap<String, String> = HashMap::new();
+
+    for msg in messages {
+        match msg.role.as_str() {
+            "system" => {
+                let text = content_text(&msg.content);
+                if !text.is_empty() {
+                    system_parts.push(text);
+                }
+            }
+            "assistant" => {
+                let mut parts = content_parts(&msg.content);
+                if let Some(tool_calls) = &msg.tool_calls {
+                    for tc in tool_calls {
+                        call_names.insert(tc.id.clone(), tc.function.name.clone());
+                        let args = serde_json::from_str::<Value>(&tc.function.arguments)
+                            .ok()
+                            .filter(|v| v.is_object())
+                            .unwrap_or_else(|| json!({}));
+                        parts.push(json!({
+                            "functionCall": { "name": tc.function.name, "args": args }
+                        }));
+                    }
+                }
+                push_parts(&mut contents, "model", parts);
+            }
+            "tool" => {
+                let call_id = msg.tool_call_id.clone().unwrap_or_default();
+                let name = call_names.get(&call_id).cloned().unwrap_or_default();
+                let part = json!({
+                    "functionResponse": {
+                        "name": name,
+                        "response": { "content": content_text(&msg.content) }
+                    }
+                });
+                push_parts(&mut contents, "user", vec![part]);
+            }
+            _ => push_parts(&mut contents, "user", content_parts(&msg.content)),
+        }
+    }
+
+    let system = if system_parts.is_empty() { None } else { Some(system_parts.join("\n\n")) };
+    (system, contents)
+}
+
+fn push_parts(contents: &mut Vec<Value>, role: &str, parts: Vec<Value>) {
+    if parts.is_empty() {
+        return;
+    }
+
+    if let Some(last) = contents.last_mut() {
+        if last["role"] == role {
+            if let Some(existing) = last["parts"].as_array_mut() {
+                existing.extend(parts);
+                return;
+            }
+        }
+    }
+
+    contents.push(json!({ "role": role, "parts": parts }));
+}
+
+fn content_text(content: &Content) -> String {
+    match content {
+        Content::Text(text) => text.clone(),
+        Content::Parts(parts) => parts.iter()
+            .filter_map(|p| match p {
+                ContentPart::Text { text, .. } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+    }
+}
+
+fn content_parts(content: &Content) -> Vec<Value> {
+    match content {
+        Content::Text(text) => {
+            if text.is_empty() { vec![] } else { vec![json!({ "text": text })] }
+        }
+        Content::Parts(parts) => parts.iter()
+            .filter_map(|p| match p {
+                ContentPart::Text { text, .. } => {
+                    if text.is_empty() { None } else { Some(json!({ "text": text })) }
+                }
+                ContentPart::ImageUrl { image_url } => Some(image_part(&image_url.url)),
+            })
+            .collect(),
+    }
+}
+
+fn image_part(url: &str) -> Value {
+    // data:<mime>;base64,<data>
+    if let Some(rest) = url.strip_prefix("data:") {
+        if let Some((meta, data)) = rest.split_once(',') {
+            return json!({
+                "inlineData": { "mimeType": meta.trim_end_matches(";base64"), "data": data }
+            });
+        }
+    }
+
+    json!({
+        "fileData": { "mimeType": guess_mime_type(url), "fileUri": url }
+    })
+}
+
+fn guess_mime_type(url: &str) -> &'static str {
+    let path = url.split(['?', '#']).next().unwrap_or(url).to_lowercase();
+    if path.ends_with(".png") {
+        "image/png"
+    } else if path.ends_with(".webp") {
+        "image/webp"
+    } else if path.ends_with(".gif") {
+        "image/gif"
+    } else {
+        "image/jpeg"
+    }
+}
+
+/// OpenAI tool definitions to Gemini `functionDeclarations`
+fn convert_tool(tool: &Value) -> Value {
+    let function = if tool["function"].is_object() { &tool["function"] } else { tool };
+
+    let mut declaration = json!({
+        "name": function["name"],
+        "description": function["description"].as_str().unwrap_or("")
+    });
+
+    // Gemini rejects object schemas without properties, so parameterless tools omit the field
+    let parameters = &function["parameters"];
+    if parameters["properties"].as_object().is_some_and(|p| !p.is_empty()) {
+        declaration["parameters"] = clean_schema(parameters);
+    }
+
+    declaration
+}
+
+pub(crate) fn clean_schema(schema: &Value) -> Value {
+    match schema {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .filter(|(k, _)| !UNSUPPORTED_SCHEMA_KEYS.contains(&k.as_str()))
+                .map(|(k, v)| (k.clone(), clean_schema(v)))
+                .collect()
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(clean_schema).collect()),
+        other => other.clone(),
+    }
+}
+
+/// Convert a non-streaming `generateContent` response into an assistant `Message`
+pub fn parse_response(res_json: &Value) -> Result<Message, String> {
+    let candidate = &res_json["candidates"][0];
+    if candidate.is_null() {
+        let reason = res_json["promptFeedback"]["blockReason"].as_str().unwrap_or("no candidates returned");
+        eprintln!("[AIUtils] Error: Gemini returned no candidates: {}", res_json);
+        return Err(format!("Malformed AI response: {}", reason));
+    }
+
+    let mut text = String::new();
+    let mut tool_calls = Vec::new();
+
+    for part in candidate["content"]["parts"].as_array().into_iter().flatten() {
+        if part["thought"].as_bool() == Some(true) {
+            continue;
+        }
+        if let Some(t) = part["text"].as_str() {
+            text.push_str(t);
+        }
+        if part["functionCall"].is_object() {
+            tool_calls.push(function_call_to_tool_call(&part["functionCall"]));
+        }
+    }
+
+    Ok(Message {
+        role: "assistant".to_string(),
+        content: Content::Text(text),
+        tool_calls: if tool_calls.is_empty() { None } else { Some(tool_calls) },
+        tool_call_id: None,
+        thinking_blocks: None,
+    })
+}
+
+fn function_call_to_tool_call(call: &Value) -> ToolCall {
+    // Older Gemini models don't return call ids; the runner needs one to pair results
+    let id = call["id"].as_str()
+        .map(|s| s.to_string())
+        .unwrap_or_else(|| format!("call_{}", uuid::Uuid::new_v4().simple()));
+    let args = if call["args"].is_object() { call["args"].to_string() } else { "{}".to_string() };
+
+    ToolCall {
+        id,
+        r#type: "function".to_string(),
+        function: FunctionCall {
+            name: call["name"].as_str().unwrap_or("").to_string(),
+            arguments: args,
+        },
+    }
+}
+
+/// Decoder for `streamGenerateContent?alt=sse` events.
+///
+/// Each event is a complete response fragment; function calls arrive whole, so the
+/// decoder only has to hand out stable tool-call indices across events.
+#[derive(Default)]
+pub(crate) struct StreamDecoder {
+    next_tool_index: i32,
+}
+
+impl StreamDecoder {
+    pub(crate) fn decode(&mut self, data: &str) -> Result<Vec<StreamChunk>, serde_json::Error> {
+        let event: Value = serde_json::from_str(data)?;
+        let mut chunks = Vec::new();
+
+        if let Some(message) = event["error"]["message"].as_str() {
+            chunks.push(StreamChunk::Error(message.to_string()));
+            return Ok(chunks);
+        }
+
+        for part in event["candidates"][0]["content"]["parts"].as_array().into_iter().flatten() {
+            if part["thought"].as_bool() == Some(true) {
+                if let Some(text) = part["text"].as_str() {
+                    chunks.push(StreamChunk::Reasoning(text.to_string()));
+                }
+                continue;
+            }
+            if let Some(text) = part["text"].as_str() {
+                chunks.push(StreamChunk::Text(text.to_string()));
+            }
+            if part["functionCall"].is_object() {
+                let call = function_call_to_tool_call(&part["functionCall"]);
+                chunks.push(StreamChunk::ToolCall {
+                    index: self.next_tool_index,
+                    id: Some(call.id),
+                    name: Some(call.function.name),
+                    arguments: Some(call.function.arguments),
+                });
+                self.next_tool_index += 1;
+            }
+        }
+
+        if event["usageMetadata"].is_object() {
+            chunks.push(StreamChunk::Usage(event["usageMetadata"].clone()));
+        }
+
+        Ok(chunks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core_traits::ai::ImageUrl;
+
+    fn message(role: &str, content: Content) -> Message {
+        Message { role: role.to_string(), content, tool_calls: None, tool_call_id: None, thinking_blocks: None }
+    }
+
+    #[test]
+    fn test_roles_and_function_response() {
+        let mut assistant = message("assistant", Content::Text(String::new()));
+        assistant.tool_calls = Some(vec![ToolCall {
+            id: "call_1".to_string(),
+            r#type: "function".to_string(),
+            function: FunctionCall { name: "agent_list_dir".to_string(), arguments: r#"{"rel_path":"src"}"#.to_string() },
+        }]);
+        let mut tool = message("tool", Content::Text("main.rs".to_string()));
+        tool.tool_call_id = Some("call_1".to_string());
+
+        let body = build_request_body(&[
+            message("system", Content::Text("Be brief.".to_string())),
+            message("user", Content::Text("List src".to_string())),
+            assistant,
+            tool,
+        ], None);
+
+        assert_eq!(body["systemInstruction"]["parts"][0]["text"], "Be brief.");
+        let contents = body["contents"].as_array().unwrap();
+        assert_eq!(contents.len(), 3);
+        assert_eq!(contents[1]["role"], "model");
+        assert_eq!(contents[1]["parts"][0]["functionCall"]["args"]["rel_path"], "src");
+        assert_eq!(contents[2]["role"], "user");
+        assert_eq!(contents[2]["parts"][0]["functionResponse"]["name"], "agent_list_dir");
+    }
+
+    #[test]
+    fn test_image_data_url_becomes_inline_data() {
+        let body = build_request_body(&[message("user", Content::Parts(vec![
+            ContentPart::Text { text: "What is this?".to_string(), part_type: "text".to_string() },
+            ContentPart::ImageUrl { image_url: ImageUrl { url: "data:image/png;base64,iVBORw0K".to_string() } },
+        ]))], None);
+
+        let inline = &body["contents"][0]["parts"][1]["inlineData"];
+        assert_eq!(inline["mimeType"], "image/png");
+        assert_eq!(inline["data"], "iVBORw0K");
+    }
+
+    #[test]
+    fn test_tools_become_function_declarations() {
+        let tools = vec![json!({
+            "type": "function",
+            "function": {
+                "name": "agent_read_file",
+                "description": "Read content of a file",
+                "parameters": {
+                    "type": "object",
+                    "additionalProperties": false,
+                    "properties": { "rel_path": { "type": "string" } },
+                    "required": ["rel_path"]
+                }
+            }
+        })];
+
+        let body = build_request_body(&[message("user", Content::Text("hi".to_string()))], Some(&tools));
+        let decl = &body["tools"][0]["functionDeclarations"][0];
+        assert_eq!(decl["name"], "agent_read_file");
+        assert!(decl["parameters"].get("additionalProperties").is_none());
+        assert_eq!(decl["parameters"]["required"][0], "rel_path");
+    }
+
+    #[test]
+    fn test_stream_decoder_indexes_calls_across_events() {
+        let mut decoder = StreamDecoder::default();
+        let first = r#"{"candidates":[{"content":{"parts":[{"functionCall":{"name":"a","args":{}}}]}}]}"#;
+        let second = r#"{"candidates":[{"content":{"parts":[{"functionCall":{"name":"b","args":{"x":1}}}]}}]}"#;
+
+        let indices: Vec<i32> = [first, second].iter()
+            .flat_map(|d| decoder.decode(d).unwrap())
+            .filter_map(|c| match c { StreamChunk::ToolCall { index, .. } => Some(index), _ => None })
+            .collect();
+        assert_eq!(indices, vec![0, 1]);
+    }
+
+    #[test]
+    fn test_endpoint_url() {
+        assert_eq!(
+            endpoint_url("https://generativelanguage.googleapis.com", "gemini-2.5-pro", false),
+            "https://generativelanguage.googleapis.com/v1beta/models/gemini-2.5-pro:generateContent"
+        );
+        assert_eq!(
+            endpoint_url("https://generativelanguage.googleapis.com/v1beta/", "gemini-2.5-flash", true),
+            "https://generativelanguage.googleapis.com/v1beta/models/gemini-2.5-flash:streamGenerateContent?alt=sse"
+        );
+    }
+}
@@ -0,0 +1,150 @@
+//! Shared HTTP clients for provider calls.
+//!
+//! Clients are cached per provider so connections are pooled across requests, and are
+//! rebuilt only when the provider's proxy, certificate or connect-timeout settings change.
+//! Per-request settings (extra headers, query parameters, overall timeout) are applied by
+//! [`apply_provider_options`].
+
+use crate::core_traits::ai::AIProviderConfig;
+use once_cell::sync::Lazy;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Certificate, Client, Proxy, RequestBuilder};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(60);
+const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(120);
+const DEFAULT_STREAM_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// provider key -> (settings fingerprint, client)
+static CLIENTS: Lazy<Mutex<HashMap<String, (String, Client)>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn provider_key(config: &AIProviderConfig) -> String {
+    if config.id.is_empty() { config.base_url.clone() } else { config.id.clone() }
+}
+
+/// The client-level settings; a change means the cached client has to be rebuilt
+fn fingerprint(config: &AIProviderConfig) -> String {
+    format!("{:?}|{:?}|{:?}", config.proxy_url, config.ca_cert_path, config.connect_timeout_secs)
+}
+
+/// The shared client for `config`'s provider
+pub fn client_for(config: &AIProviderConfig) -> Result<Client, String> {
+    let key = provider_key(config);
+    let fingerprint = fingerprint(config);
+
+    let mut clients = CLIENTS.lock().unwrap();
+    if let Some((cached_fingerprint, client)) = clients.get(&key) {
+        if *cached_fingerprint == fingerprint {
+            // reqwest clients are reference counted, so this shares the pool
+            return Ok(client.clone());
+        }
+    }
+
+    let client = build_client(config)?;
+    clients.insert(key, (fingerprint, client.clone()));
+    Ok(client)
+}
+
+fn build_client(config: &AIProviderConfig) -> Result<Client, String> {
+    let mut builder = Client::builder()
+        .connect_timeout(config.connect_timeout_secs.map(Duration::from_secs).unwrap_or(DEFAULT_CONNECT_TIMEOUT))
+        .pool_idle_timeout(Duration::from_secs(120))  // Keep connections alive for 120s in pool
+        .pool_max_idle_per_host(10)
+        .tcp_keepalive(Duration::from_secs(30))
+        .http2_keep_alive_interval(Duration::from_secs(20))
+        .http2_keep_alive_timeout(Duration::from_secs(30))
+        .http2_keep_alive_while_idle(true);
+
+    if let Some(proxy_url) = config.proxy_url.as_deref().filter(|p| !p.trim().is_empty()) {
+        let proxy = Proxy::all(proxy_url.trim())
+            .map_err(|e| format!("Invalid proxy URL '{}' for provider '{}': {}", proxy_url, config.name, e))?;
+        builder = builder.proxy(proxy);
+    }
+
+    if let Some(path) = config.ca_cert_path.as_deref().filter(|p| !p.trim().is_empty()) {
+        let pem = std::fs::read(path)
+            .map_err(|e| format!("Failed to read CA certificates from {}: {}", path, e))?;
+        let certs = Certificate::from_pem_bundle(&pem)
+            .map_err(|e| format!("Invalid CA certificates in {}: {}", path, e))?;
+        for cert in certs {
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+
+    builder.build().map_err(|e| {
+        eprintln!("[AIHttp] Failed to create HTTP client for {}: {}", config.name, e);
+        e.to_string()
+    })
+}
+
+/// Apply the provider's extra headers, query parameters and timeout to a request.
+///
+/// Extra headers replace headers of the same name, so they can override the default auth header.
+pub fn apply_provider_options(request: RequestBuilder, config: &AIProviderConfig, stream: bool) -> RequestBuilder {
+    let timeout = config.request_timeout_secs
+        .map(Duration::from_secs)
+        .unwrap_or(if stream { DEFAULT_STREAM_TIMEOUT } else { DEFAULT_REQUEST_TIMEOUT });
+    let mut request = request.timeout(timeout);
+
+    if !config.query_params.is_empty() {
+        request = request.query(&config.query_params);
+    }
+
+    if !config.extra_headers.is_empty() {
+        request = request.headers(extra_headers(config));
+    }
+
+    request
+}
+
+fn extra_headers(config: &AIProviderConfig) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    for (name, value) in &config.extra_headers {
+        match (HeaderName::from_bytes(name.trim().as_bytes()), HeaderValue::from_str(value.trim())) {
+            (Ok(name), Ok(value)) => {
+                headers.insert(name, value);
+            }
+            _ => eprintln!("[AIHttp] Ignoring invalid header '{}' for provider {}", name, config.name),
+        }
+    }
+    headers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn provider(id: &str) -> AIProviderConfig {
+        AIProviderConfig { id: id.to_string(), name: id.to_string(), ..Default::default() }
+    }
+
+    #[test]
+    fn test_invalid_proxy_is_reported() {
+        let mut config = provider("http-test-proxy");
+        config.proxy_url = Some("not a url".to_string());
+        assert!(client_for(&config).unwrap_err().contains("Invalid proxy URL"));
+    }
+
+    #[test]
+    fn test_extra_headers_and_query_are_applied() {
+        let mut config = provider("http-test-headers");
+        config.extra_headers.insert("api-key".to_string(), "azure-key".to_string());
+        config.extra_headers.insert("Authorization".to_string(), "Bearer gateway".to_string());
+        config.query_params.insert("api-version".to_string(), "2024-10-21".to_string());
+
+        let client = client_for(&config).unwrap();
+        let request = apply_provider_options(
+            client.post("https://example.com/chat").header("Authorization", "Bearer original"),
+            &config,
+            false,
+        ).build().unwrap();
+
+        assert_eq!(request.headers()["api-key"], "azure-key");
+        assert_eq!(request.headers().get_all("authorization").iter().count(), 1);
+        assert_eq!(request.headers()["authorization"], "Bearer gateway");
+        assert_eq!(request.url().query(), Some("api-version=2024-10-21"));
+        assert_eq!(request.timeout(), Some(&DEFAULT_REQUEST_TIMEOUT));
+    }
+}
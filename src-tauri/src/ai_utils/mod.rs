@@ -0,0 +1,795 @@
+pub mod anthropic;
+pub mod cancel;
+pub mod discovery;
+pub mod gemini;
+pub mod http;
+pub mod retry;
+pub mod routing;
+pub mod structured;
+
+use crate::core_traits::ai::{Message, Content, ToolCall, AIProviderConfig, AIProtocol, FunctionCall, ModelRoute};
+use crate::usage_ledger::{self, TokenUsage};
+use crate::events::{self, AgentEventKind, AgentToolCall};
+use serde_json::{json, Value};
+use reqwest::{Client, RequestBuilder};
+use std::time::Instant;
+use std::collections::BTreeMap;
+use tauri::{AppHandle, Runtime};
+use futures::stream::StreamExt;
+use eventsource_stream::Eventsource;
+
+pub fn sanitize_messages(messages: &mut Vec<Message>) {
+    let mut i = 0;
+    while i < messages.len() {
+        // Only process assistant messages that have tool_calls
+        if messages[i].role == "assistant" && messages[i].tool_calls.as_ref().map_or(false, |tc| !tc.is_empty()) {
+            let tool_calls = messages[i].tool_calls.clone().unwrap();
+            let mut completed_ids = std::collections::HashSet::new();
+
+            // Scan forward to find all tool response messages
+            let mut j = i + 1;
+            while j < messages.len() && messages[j].role == "tool" {
+                if let Some(id) = &messages[j].tool_call_id {
+                    completed_ids.insert(id.clone());
+                }
+                j += 1;
+            }
+
+            // Filter to keep only tool_calls that have responses
+            let filtered_calls: Vec<_> = tool_calls.into_iter()
+                .filter(|tc| completed_ids.contains(&tc.id))
+                .collect();
+
+            if filtered_calls.is_empty() {
+                // No completed calls - remove tool_calls field entirely
+                messages[i].tool_calls = None;
+            } else {
+                // Update with only completed calls
+                messages[i].tool_calls = Some(filtered_calls);
+            }
+        }
+        i += 1;
+    }
+}
+
+/// Build the HTTP request for the provider's wire protocol.
+///
+/// OpenAI-compatible providers get the body as-is with a `Bearer` token posted to `base_url`;
+/// Anthropic and Gemini go through their adapters. `output` requests native structured output.
+fn build_request(
+    client: &Client,
+    config: &AIProviderConfig,
+    model: &str,
+    messages: &[Message],
+    tools: Option<&[Value]>,
+    stream: bool,
+    output: Option<&structured::JsonOutput>,
+) -> RequestBuilder {
+    let request = match config.protocol {
+        AIProtocol::Anthropic => {
+            let mut body = anthropic::build_request_body(model, messages, tools, stream);
+            if let Some(output) = output {
+                structured::apply_native(&config.protocol, &mut body, output);
+            } else if let Some(budget) = config.reasoning_budget_tokens {
+                // Thinking can't be combined with the forced tool choice used for structured output
+                anthropic::apply_thinking(&mut body, messages, budget);
+            }
+            client.post(anthropic::messages_url(&config.base_url))
+                .header("x-api-key", &config.api_key)
+                .header("anthropic-version", anthropic::API_VERSION)
+                .json(&body)
+        }
+        AIProtocol::Gemini => {
+            let mut body = gemini::build_request_body(messages, tools);
+            if let Some(output) = output {
+                structured::apply_native(&config.protocol, &mut body, output);
+            } else if let Some(budget) = config.reasoning_budget_tokens {
+                gemini::apply_thinking(&mut body, budget);
+            }
+            client.post(gemini::endpoint_url(&config.base_url, model, stream))
+                .header("x-goog-api-key", &config.api_key)
+                .json(&body)
+        }
+        _ => {
+            // Thinking blocks are Anthropic-only; strict OpenAI-compatible servers reject unknown fields
+            let messages: Vec<Message> = messages.iter().cloned()
+                .map(|m| Message { thinking_blocks: None, ..m })
+                .collect();
+            let mut request_body = json!({
+                "model": model,
+                "messages": messages,
+                "stream": stream
+            });
+
+            if stream {
+                // Ask for a final usage chunk so streamed requests can be billed
+                request_body["stream_options"] = json!({ "include_usage": true });
+            }
+
+            if let Some(t) = tools {
+                request_body["tools"] = json!(t);
+            }
+
+            if let Some(output) = output {
+                structured::apply_native(&config.protocol, &mut request_body, output);
+            }
+
+            let request = client.post(&config.base_url).json(&request_body);
+            if config.api_key.is_empty() {
+                // Keyless local servers, or auth supplied through `extra_headers`
+                request
+            } else {
+                request.header("Authorization", format!("Bearer {}", config.api_key))
+            }
+        }
+    };
+
+    http::apply_provider_options(request, config, stream)
+}
+
+pub async fn fetch_ai_completion(
+    config: &AIProviderConfig,
+    messages: Vec<Message>,
+    tools: Option<Vec<Value>>,
+) -> Result<Message, String> {
+    fetch_ai_completion_routed(config, messages, tools).await.map(|(message, _)| message)
+}
+
+/// Non-streaming completion that walks the provider's fallback chain and reports which model answered
+pub async fn fetch_ai_completion_routed(
+    config: &AIProviderConfig,
+    messages: Vec<Message>,
+    tools: Option<Vec<Value>>,
+) -> Result<(Message, ModelRoute), String> {
+    fetch_routed_with_output(config, messages, tools, None).await.map_err(RoutedFailure::into_error)
+}
+
+/// Why [`fetch_routed_with_output`] failed
+pub(crate) enum RoutedFailure {
+    /// `route` rejected the native structured-output fields; the chain stops there
+    OutputRejected { error: String, route: ModelRoute },
+    Failed(String),
+}
+
+impl RoutedFailure {
+    pub(crate) fn into_error(self) -> String {
+        match self {
+            Self::OutputRejected { error, .. } | Self::Failed(error) => error,
+        }
+    }
+}
+
+/// [`fetch_ai_completion_routed`] with optional native structured output
+pub(crate) async fn fetch_routed_with_output(
+    config: &AIProviderConfig,
+    mut messages: Vec<Message>, // Change to mutable to allow sanitization
+    tools: Option<Vec<Value>>,
+    output: Option<&structured::JsonOutput>,
+) -> Result<(Message, ModelRoute), RoutedFailure> {
+    // Apply sanitization before every internal API call
+    sanitize_messages(&mut messages);
+
+    let chain = routing::model_chain(config).map_err(RoutedFailure::Failed)?;
+    let mut failed_attempts = Vec::new();
+
+    for (i, target) in chain.iter().enumerate() {
+        match fetch_from_target(target, &messages, tools.as_deref(), output).await {
+            Ok(message) => {
+                return Ok((message, ModelRoute {
+                    provider_id: target.config.id.clone(),
+                    model: target.model.clone(),
+                    failed_attempts,
+                }));
+            }
+            // Another model would hide the rejection; the structured-output caller retries with the
+            // schema in the prompt instead. This may be a fallback target, so report which one it was.
+            Err(e) if output.is_some() && structured::rejects_native_output(&e) => {
+                return Err(RoutedFailure::OutputRejected {
+                    error: e,
+                    route: ModelRoute {
+                        provider_id: target.config.id.clone(),
+                        model: target.model.clone(),
+                        failed_attempts,
+                    },
+                });
+            }
+            Err(e) => match chain.get(i + 1) {
+                Some(next) => {
+                    let notice = routing::fallback_notice(target, next, &e);
+                    eprintln!("[AIUtils] {}/{} failed ({:?}), falling back to {}/{}",
+                        notice.from_provider, notice.from_model, notice.reason, notice.to_provider, notice.to_model);
+                    failed_attempts.push(format!("{}/{}: {}", target.config.id, target.model, e));
+                }
+                None => return Err(RoutedFailure::Failed(e)),
+            },
+        }
+    }
+
+    Err(RoutedFailure::Failed(format!("No model configured for provider '{}'", config.name)))
+}
+
+async fn fetch_from_target(
+    target: &routing::ModelTarget,
+    messages: &[Message],
+    tools: Option<&[Value]>,
+    output: Option<&structured::JsonOutput>,
+) -> Result<Message, String> {
+    let config = &target.config;
+    let client = http::client_for(config)?;
+    
+    let (response, _slot) = retry::send_with_retry(
+        config,
+        &retry::RetryPolicy::default(),
+        || build_request(&client, config, &target.model, messages, tools, false, output),
+        |_| {},
+    ).await?;
+
+    let status = response.status();
+    let headers = response.headers().clone();
+
+    // Log response details
+    eprintln!("[AIUtils] Response status: {}", status);
+    if let Some(content_type) = headers.get("content-type") {
+        eprintln!("[AIUtils] Content-Type: {:?}", content_type);
+    }
+    if let Some(content_length) = headers.get("content-length") {
+        eprintln!("[AIUtils] Content-Length: {:?}", content_length);
+    }
+
+    // Try to read response as bytes first, then convert to string
+    eprintln!("[AIUtils] Attempting to read response body...");
+    let response_bytes = match response.bytes().await {
+        Ok(bytes) => {
+            eprintln!("[AIUtils] Successfully read {} bytes", bytes.len());
+            bytes
+        }
+        Err(e) => {
+            eprintln!("[AIUtils] Failed to read response bytes: {}", e);
+            eprintln!("[AIUtils] Error kind: {:?}", e);
+            eprintln!("[AIUtils] Is timeout: {}", e.is_timeout());
+            eprintln!("[AIUtils] Is connect: {}", e.is_connect());
+            return Err(format!("Failed to read response bytes: {} (timeout: {}, connect: {})",
+                e, e.is_timeout(), e.is_connect()));
+        }
+    };
+
+    let response_text = String::from_utf8(response_bytes.to_vec()).map_err(|e| {
+        eprintln!("[AIUtils] Failed to decode response as UTF-8: {}", e);
+        eprintln!("[AIUtils] First 100 bytes (as hex): {:02x?}",
+            &response_bytes[..response_bytes.len().min(100)]);
+        format!("Response is not valid UTF-8: {}", e)
+    })?;
+
+    // Try to parse as JSON
+    let res_json: Value = serde_json::from_str(&response_text).map_err(|e| {
+        eprintln!("[AIUtils] JSON Parse Error: {}", e);
+        eprintln!("[AIUtils] Response body (first 500 chars): {}",
+            if response_text.len() > 500 {
+                format!("{}...", &response_text[..500])
+            } else {
+                response_text.clone()
+            }
+        );
+        format!("Failed to parse AI response as JSON: {}", e)
+    })?;
+    
+    let message = match config.protocol {
+        AIProtocol::Anthropic => anthropic::parse_response(&res_json),
+        AIProtocol::Gemini => gemini::parse_response(&res_json),
+        _ => parse_openai_response(&res_json),
+    }?;
+
+    let usage = res_json.get("usage").or_else(|| res_json.get("usageMetadata"));
+    if let Some(usage) = usage.and_then(TokenUsage::from_provider) {
+        usage_ledger::record(&config.id, &target.model, &usage);
+    }
+
+    Ok(message)
+}
+
+fn parse_openai_response(res_json: &Value) -> Result<Message, String> {
+    let choice = &res_json["choices"][0]["message"];
+    if choice.is_null() {
+        eprintln!("[AIUtils] Error: 'choices[0].message' is missing in response: {}", res_json);
+        return Err("Malformed AI response: message field missing".to_string());
+    }
+
+    let role = choice["role"].as_str().unwrap_or("assistant").to_string();
+    // `reasoning_content` is left out: DeepSeek rejects requests that send it back
+    let content_text = choice["content"].as_str().unwrap_or("").to_string();
+    
+    let mut tool_calls: Option<Vec<ToolCall>> = None;
+    if let Some(tc_array) = choice["tool_calls"].as_array() {
+        let mut calls = Vec::new();
+        for tc_val in tc_array {
+            calls.push(ToolCall {
+                id: tc_val["id"].as_str().unwrap_or("").to_string(),
+                r#type: "function".to_string(),
+                function: FunctionCall {
+                    name: tc_val["function"]["name"].as_str().unwrap_or("").to_string(),
+                    arguments: tc_val["function"]["arguments"].as_str().unwrap_or("{}").to_string(),
+                }
+            });
+        }
+        tool_calls = Some(calls);
+    }
+
+    Ok(Message {
+        role,
+        content: Content::Text(content_text),
+        tool_calls,
+        tool_call_id: None,
+        thinking_blocks: None,
+    })
+}
+
+// Streaming response data structures
+#[derive(serde::Deserialize, Debug)]
+struct OpenAIStreamResponse {
+    #[serde(default)]
+    choices: Vec<StreamChoice>,
+    #[serde(default)]
+    usage: Option<Value>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct StreamChoice {
+    delta: StreamDelta,
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct StreamDelta {
+    content: Option<String>,
+    /// DeepSeek-R1, GLM and Qwen reasoning models
+    reasoning_content: Option<String>,
+    /// OpenRouter and Ollama
+    reasoning: Option<String>,
+    tool_calls: Option<Vec<ToolCallChunk>>,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+struct ToolCallChunk {
+    index: i32,
+    id: Option<String>,
+    function: Option<FunctionChunk>,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+struct FunctionChunk {
+    name: Option<String>,
+    arguments: Option<String>,
+}
+
+struct StreamingToolCall {
+    id: String,
+    name: String,
+    arguments: String,
+}
+
+/// Protocol-neutral pieces decoded from a single SSE event
+pub(crate) enum StreamChunk {
+    Text(String),
+    /// Reasoning/thinking text, shown to the user but never part of the answer
+    Reasoning(String),
+    ToolCall {
+        index: i32,
+        id: Option<String>,
+        name: Option<String>,
+        arguments: Option<String>,
+    },
+    Usage(Value),
+    Stop,
+    Error(String),
+}
+
+fn decode_openai_stream_event(data: &str) -> Result<Vec<StreamChunk>, serde_json::Error> {
+    if data == "[DONE]" {
+        return Ok(vec![StreamChunk::Stop]);
+    }
+
+    let stream_response = serde_json::from_str::<OpenAIStreamResponse>(data)?;
+    let mut chunks = Vec::new();
+
+    // Providers that honour `stream_options.include_usage` send it on the last chunk
+    if let Some(u) = stream_response.usage {
+        chunks.push(StreamChunk::Usage(u));
+    }
+
+    if let Some(choice) = stream_response.choices.into_iter().next() {
+        if let Some(reasoning) = choice.delta.reasoning_content.or(choice.delta.reasoning) {
+            chunks.push(StreamChunk::Reasoning(reasoning));
+        }
+        if let Some(content) = choice.delta.content {
+            chunks.push(StreamChunk::Text(content));
+        }
+
+        for tc in choice.delta.tool_calls.unwrap_or_default() {
+            let (name, arguments) = match tc.function {
+                Some(f) => (f.name, f.arguments),
+                None => (None, None),
+            };
+            chunks.push(StreamChunk::ToolCall { index: tc.index, id: tc.id, name, arguments });
+        }
+    }
+
+    Ok(chunks)
+}
+
+/// Providers may split usage across events (Anthropic sends input tokens first, output tokens last)
+fn merge_usage(usage: &mut Option<Value>, update: Value) {
+    if update.is_null() {
+        return;
+    }
+
+    match (usage.as_mut(), update) {
+        (Some(Value::Object(existing)), Value::Object(fields)) => existing.extend(fields),
+        (_, update) => *usage = Some(update),
+    }
+}
+
+/// Incremental events produced while an SSE completion stream is consumed
+#[derive(Debug, Clone)]
+pub enum StreamEvent {
+    /// A chunk of assistant text
+    Content(String),
+    /// A chunk of reasoning. It is not part of the returned message, so it never goes back
+    /// into the history sent to the model.
+    Reasoning(String),
+    /// A tool call fragment. `arguments` holds everything received so far for this call,
+    /// `arguments_delta` only the part that arrived with this chunk.
+    ToolCall {
+        index: i32,
+        id: String,
+        name: String,
+        arguments_delta: String,
+        arguments: String,
+    },
+    /// A recoverable transport error; the stream keeps going
+    Warning(String),
+    /// The request failed transiently and will be re-sent after a delay
+    Retry(retry::RetryNotice),
+    /// The previous model failed before producing output; the request moves to the next one
+    Fallback(routing::FallbackNotice),
+    /// The stream finished. Carries normalized token usage when the provider sent it,
+    /// and the provider/model that actually answered.
+    Done {
+        usage: Option<TokenUsage>,
+        provider_id: String,
+        model: String,
+    },
+}
+
+fn extract_partial_value(json_str: &str, key: &str) -> Option<String> {
+    // Enhanced regex to find string values in partial JSON, including multiline content
+    // Matches: "key": "value..." where value can contain escaped quotes and newlines
+    let pattern = format!(r#""{}"\s*:\s*"((?:[^"\\]|\\.|[\n\r])*)"#, key);
+    if let Ok(re) = regex::Regex::new(&pattern) {
+        if let Some(caps) = re.captures(json_str) {
+            let mut val = caps[1].to_string();
+            // Enhanced unescaping for display purposes
+            val = val.replace("\\n", "\n")
+                     .replace("\\r", "\r")
+                     .replace("\\t", "\t")
+                     .replace("\\\"", "\"")
+                     .replace("\\\\", "\\");
+            return Some(val);
+        }
+    }
+    None
+}
+
+/// Streaming completion shared by chat and agents.
+///
+/// Sends a streaming request in the provider's protocol, forwards every delta to `on_event`
+/// as it arrives and returns the fully assembled assistant message once the stream ends.
+/// Falls back along the provider's model chain as long as nothing has been streamed yet.
+pub async fn stream_ai_completion<F>(
+    config: &AIProviderConfig,
+    messages: Vec<Message>,
+    tools: Option<Vec<Value>>,
+    mut on_event: F,
+) -> Result<Message, String>
+where
+    F: FnMut(StreamEvent) + Send,
+{
+    // 1. Sanitize messages
+    let mut clean_messages = messages;
+    sanitize_messages(&mut clean_messages);
+
+    let chain = routing::model_chain(config)?;
+
+    for (i, target) in chain.iter().enumerate() {
+        // Once output reached the UI, switching models would splice two answers together
+        let mut emitted = false;
+        let result = {
+            let mut forward = |event: StreamEvent| {
+                if matches!(event, StreamEvent::Content(_) | StreamEvent::Reasoning(_) | StreamEvent::ToolCall { .. }) {
+                    emitted = true;
+                }
+                on_event(event);
+            };
+            stream_from_target(target, &clean_messages, tools.as_deref(), &mut forward).await
+        };
+
+        match result {
+            Ok(message) => return Ok(message),
+            Err(e) => match chain.get(i + 1) {
+                Some(next) if !emitted => {
+                    let notice = routing::fallback_notice(target, next, &e);
+                    eprintln!("[AIStream] {}/{} failed ({:?}), falling back to {}/{}",
+                        notice.from_provider, notice.from_model, notice.reason, notice.to_provider, notice.to_model);
+                    on_event(StreamEvent::Fallback(notice));
+                }
+                _ => return Err(e),
+            },
+        }
+    }
+
+    Err(format!("No model configured for provider '{}'", config.name))
+}
+
+async fn stream_from_target(
+    target: &routing::ModelTarget,
+    clean_messages: &[Message],
+    tools: Option<&[Value]>,
+    on_event: &mut (dyn FnMut(StreamEvent) + Send),
+) -> Result<Message, String> {
+    let config = &target.config;
+    // Each target may be a different provider with its own proxy/TLS settings
+    let client = http::client_for(config)?;
+
+    // 3. Send HTTP request
+    // Transient failures (429/503, dropped connections) are retried before any content is streamed
+    let (response, _slot) = retry::send_with_retry(
+        config,
+        &retry::RetryPolicy::default(),
+        || build_request(&client, config, &target.model, clean_messages, tools, true, None),
+        |notice| on_event(StreamEvent::Retry(notice.clone())),
+    ).await?;
+
+    // 4. Process SSE stream
+    let mut stream = response.bytes_stream().eventsource();
+    let mut accumulated_content = String::new();
+    let mut accumulated_reasoning = String::new();
+    // Keyed by the provider's call index, so the assembled calls keep the model's order
+    let mut accumulated_tool_calls: BTreeMap<i32, StreamingToolCall> = BTreeMap::new();
+    let mut usage: Option<Value> = None;
+    let mut anthropic_decoder = anthropic::StreamDecoder::default();
+    let mut gemini_decoder = gemini::StreamDecoder::default();
+    let mut event_count = 0;
+
+    // Stream statistics tracking
+    let start_time = Instant::now();
+    let mut last_event_time = Instant::now();
+
+    while let Some(event) = stream.next().await {
+        event_count += 1;
+        let now = Instant::now();
+        let time_since_last = now.duration_since(last_event_time).as_secs_f64();
+        last_event_time = now;
+
+        match event {
+            Ok(event) => {
+                // Log stream statistics every 50 events
+                if event_count % 50 == 0 {
+                    let elapsed = start_time.elapsed().as_secs_f64();
+                    eprintln!("[AIStream] Event #{} | Stats: {:.1}s elapsed, {:.3}s since last",
+                        event_count, elapsed, time_since_last);
+                }
+
+                let decoded = match config.protocol {
+                    AIProtocol::Anthropic => anthropic_decoder.decode(&event.data),
+                    AIProtocol::Gemini => gemini_decoder.decode(&event.data),
+                    _ => decode_openai_stream_event(&event.data),
+                };
+
+                let chunks = match decoded {
+                    Ok(chunks) => chunks,
+                    Err(_) => {
+                        eprintln!("[AIStream] Failed to parse JSON at event #{}. First 200 chars: {}",
+                            event_count,
+                            if event.data.len() > 200 {
+                                format!("{}...", &event.data[..200])
+                            } else {
+                                event.data.clone()
+                            }
+                        );
+                        continue;
+                    }
+                };
+
+                let mut finished = false;
+                for chunk in chunks {
+                    match chunk {
+                        StreamChunk::Text(content) => {
+                            if content.is_empty() {
+                                continue;
+                            }
+                            accumulated_content.push_str(&content);
+                            on_event(StreamEvent::Content(content));
+                        }
+                        StreamChunk::Reasoning(reasoning) => {
+                            if reasoning.is_empty() {
+                                continue;
+                            }
+                            accumulated_reasoning.push_str(&reasoning);
+                            on_event(StreamEvent::Reasoning(reasoning));
+                        }
+                        StreamChunk::ToolCall { index, id, name, arguments } => {
+                            let st = accumulated_tool_calls.entry(index).or_insert_with(|| StreamingToolCall {
+                                id: String::new(),
+                                name: String::new(),
+                                arguments: String::new(),
+                            });
+
+                            if let Some(id) = id {
+                                st.id = id;
+                            }
+                            if let Some(name) = name {
+                                st.name.push_str(&name);
+                            }
+                            let arguments_delta = arguments.unwrap_or_default();
+                            st.arguments.push_str(&arguments_delta);
+
+                            on_event(StreamEvent::ToolCall {
+                                index,
+                                id: st.id.clone(),
+                                name: st.name.clone(),
+                                arguments_delta,
+                                arguments: st.arguments.clone(),
+                            });
+                        }
+                        StreamChunk::Usage(u) => merge_usage(&mut usage, u),
+                        StreamChunk::Stop => finished = true,
+                        StreamChunk::Error(message) => {
+                            eprintln!("[AIStream] Provider reported an error: {}", message);
+                            return Err(format!("Stream error: {}", message));
+                        }
+                    }
+                }
+
+                if finished {
+                    break;
+                }
+            }
+            Err(e) => {
+                let elapsed = start_time.elapsed().as_secs_f64();
+                let error_source = std::error::Error::source(&e)
+                    .map(|s| s.to_string())
+                    .unwrap_or_else(|| "None".to_string());
+                eprintln!(
+                    "[AIStream] Stream error at event {}: {:.1}s elapsed, {:.3}s since last, error: {}, source: {}",
+                    event_count, elapsed, time_since_last, e, error_source
+                );
+
+                // Check if this is a recoverable error (encoding/decoding/connection issues)
+                let error_str = e.to_string().to_lowercase();
+                // Detect recoverable errors: encoding errors, connection timeouts, stream interruptions
+                if error_str.contains("decoding") ||
+                   error_str.contains("utf8") ||
+                   error_str.contains("charset") ||
+                   error_str.contains("response body") ||  // Catch connection timeout read errors
+                   error_str.contains("unexpected eof") ||  // Connection closed unexpectedly
+                   error_str.contains("connection") {      // Generic connection issues
+                    // Log warning and attempt to continue
+                    eprintln!("[AIStream] Recoverable error at event #{}: {}. Attempting to continue...",
+                        event_count, e);
+                    on_event(StreamEvent::Warning(
+                        format!("Stream interrupted at event #{}, attempting recovery...", event_count)
+                    ));
+                    continue;
+                }
+
+                return Err(format!("Stream error: {}", e));
+            }
+        }
+    }
+
+    let total_time = start_time.elapsed().as_secs_f64();
+    eprintln!("[AIStream] Stream completed. Events: {}, Time: {:.1}s, Content: {} chars, Reasoning: {} chars, Tools: {}",
+        event_count, total_time, accumulated_content.len(), accumulated_reasoning.len(), accumulated_tool_calls.len());
+
+    let usage = usage.as_ref()
+        .and_then(TokenUsage::from_provider)
+        .map(|u| u.with_reasoning_estimate(&accumulated_reasoning));
+    if let Some(u) = &usage {
+        usage_ledger::record(&config.id, &target.model, u);
+    }
+
+    on_event(StreamEvent::Done {
+        usage,
+        provider_id: config.id.clone(),
+        model: target.model.clone(),
+    });
+
+    // 5. Build final Message
+    let tool_calls = if accumulated_tool_calls.is_empty() {
+        None
+    } else {
+        Some(
+            accumulated_tool_calls
+                .values()
+                .map(|st| ToolCall {
+                    id: st.id.clone(),
+                    r#type: "function".to_string(),
+                    function: FunctionCall {
+                        name: st.name.clone(),
+                        arguments: st.arguments.clone(),
+                    },
+                })
+                .collect::<Vec<_>>()
+        )
+    };
+
+    // Signed thinking goes back with this turn's tool results
+    let thinking_blocks = match (&config.protocol, &tool_calls) {
+        (AIProtocol::Anthropic, Some(_)) => Some(anthropic_decoder.thinking_blocks()).filter(|b| !b.is_empty()),
+        _ => None,
+    };
+
+    Ok(Message {
+        role: "assistant".to_string(),
+        content: Content::Text(accumulated_content),
+        tool_calls,
+        tool_call_id: None,
+        thinking_blocks,
+    })
+}
+
+/// Agent-specific streaming chat that returns a Message (unlike stream_chat which only emits events)
+pub async fn agent_stream_chat<R: Runtime>(
+    app: &AppHandle<R>,
+    config: &AIProviderConfig,
+    messages: Vec<Message>,
+    agent_id: &str,
+    tools: Option<Vec<Value>>,
+) -> Result<Message, String> {
+    let event_name = format!("agent_{}", agent_id);
+    eprintln!("[AgentStream] agent_stream_chat called with agent_id: {}, event_name: {}", agent_id, event_name);
+
+    stream_ai_completion(config, messages, tools, |event| match event {
+        StreamEvent::Content(content) => {
+            // Send to frontend in real-time as 'thinking' type
+            events::emit_agent(app, &event_name, AgentEventKind::Thinking { content });
+        }
+        StreamEvent::Reasoning(content) => {
+            events::emit_agent(app, &event_name, AgentEventKind::Reasoning { content });
+        }
+        StreamEvent::ToolCall { index, id, name, arguments, .. } => {
+            // Emit partial tool call to frontend immediately after each chunk
+            let tool_name = if name.is_empty() { "unknown".to_string() } else { name };
+            let tool_id = if id.is_empty() {
+                format!("{}_{}", agent_id, index)
+            } else {
+                id
+            };
+
+            // Try full parse first
+            let args_val: Value = serde_json::from_str(&arguments).unwrap_or_else(|_| {
+                // If not valid JSON, try to extract fields manually for better progressive UI
+                let mut map = serde_json::Map::new();
+                if let Some(path) = extract_partial_value(&arguments, "rel_path") {
+                    map.insert("rel_path".to_string(), json!(path));
+                }
+                if let Some(content) = extract_partial_value(&arguments, "content") {
+                    map.insert("content".to_string(), json!(content));
+                }
+                Value::Object(map)
+            });
+
+            events::emit_agent(app, &event_name, AgentEventKind::ToolCall {
+                tool_call: AgentToolCall { id: tool_id, tool: tool_name, args: args_val, is_partial: true, auto_approved: false },
+            });
+        }
+        StreamEvent::Warning(message) => {
+            events::emit_agent(app, &event_name, AgentEventKind::Warning { message });
+        }
+        StreamEvent::Retry(notice) => {
+            events::emit_agent(app, &event_name, AgentEventKind::retry(notice));
+        }
+        StreamEvent::Fallback(notice) => {
+            events::emit_agent(app, &event_name, AgentEventKind::fallback(notice));
+        }
+        StreamEvent::Done { .. } => {}
+    }).await
+}
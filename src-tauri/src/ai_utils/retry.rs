@@ -0,0 +1,254 @@
+//! Shared retry policy for AI HTTP calls.
+//!
+//! Rate limits (429), overloads (503/529) and dropped connections are retried with
+//! exponential backoff and jitter, honouring `Retry-After` when the provider sends it.
+//! Requests to the same provider also share a small concurrency limit so parallel
+//! agents don't trip rate limits in the first place.
+
+use crate::core_traits::ai::AIProviderConfig;
+use once_cell::sync::Lazy;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::{RequestBuilder, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use ts_rs::TS;
+
+/// Maximum in-flight requests per provider
+const MAX_CONCURRENT_REQUESTS_PER_PROVIDER: usize = 4;
+
+static PROVIDER_LIMITERS: Lazy<Mutex<HashMap<String, Arc<Semaphore>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    /// Upper bound for server-provided `Retry-After` values
+    pub max_retry_after: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 4,
+            base_delay: Duration::from_millis(1000),
+            max_delay: Duration::from_secs(30),
+            max_retry_after: Duration::from_secs(120),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Full-jitter exponential backoff: a random delay in `[0, min(max, base * 2^attempt))`
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exp = self.base_delay.saturating_mul(1u32 << attempt.min(16));
+        let ceiling = exp.min(self.max_delay).as_millis().max(1) as u64;
+        // Keep at least half the window so retries don't fire back-to-back
+        let jittered = ceiling / 2 + random_u64() % (ceiling / 2 + 1);
+        Duration::from_millis(jittered)
+    }
+}
+
+/// Sent to the frontend before each retry so the UI can explain the pause
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct RetryNotice {
+    pub attempt: u32,
+    pub max_retries: u32,
+    #[ts(type = "number")]
+    pub delay_ms: u64,
+    pub reason: String,
+    pub status: Option<u16>,
+}
+
+/// Whether a failed status is worth retrying. Auth, validation and not-found errors are fatal.
+pub fn is_retryable_status(status: StatusCode) -> bool {
+    match status.as_u16() {
+        408 | 409 | 425 | 429 => true,
+        // 529 is Anthropic's "overloaded"
+        500 | 502 | 503 | 504 | 529 => true,
+        _ => false,
+    }
+}
+
+/// Parse `Retry-After` as either delta-seconds or an HTTP date
+pub fn parse_retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+
+    if let Ok(secs) = value.parse::<f64>() {
+        if secs >= 0.0 {
+            return Some(Duration::from_millis((secs * 1000.0) as u64));
+        }
+    }
+
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let delta = date.with_timezone(&chrono::Utc) - chrono::Utc::now();
+    Some(delta.to_std().unwrap_or(Duration::ZERO))
+}
+
+/// Wait for a free slot in the provider's concurrency limit.
+///
+/// The permit is returned to the caller so streaming responses keep their slot until the
+/// stream has been fully consumed.
+pub async fn acquire_provider_slot(config: &AIProviderConfig) -> OwnedSemaphorePermit {
+    let key = if config.id.is_empty() { config.base_url.clone() } else { config.id.clone() };
+    let semaphore = {
+        let mut limiters = PROVIDER_LIMITERS.lock().unwrap();
+        limiters
+            .entry(key)
+            .or_insert_with(|| Arc::new(Semaphore::new(MAX_CONCURRENT_REQUESTS_PER_PROVIDER)))
+            .clone()
+    };
+
+    // The semaphore is never closed, so acquiring can't fail
+    semaphore.acquire_owned().await.expect("provider limiter closed")
+}
+
+/// Send a request built by `build`, retrying transient failures according to `policy`.
+///
+/// Returns the successful response together with the provider slot it occupies.
+/// Non-retryable statuses and exhausted retries produce the usual `AI API Error (...)` message.
+pub async fn send_with_retry<B, R>(
+    config: &AIProviderConfig,
+    policy: &RetryPolicy,
+    build: B,
+    mut on_retry: R,
+) -> Result<(Response, OwnedSemaphorePermit), String>
+where
+    B: Fn() -> RequestBuilder + Send,
+    R: FnMut(&RetryNotice) + Send,
+{
+    let mut permit = acquire_provider_slot(config).await;
+    let mut attempt = 0;
+
+    loop {
+        let (reason, status, retry_after) = match build().send().await {
+            Ok(response) if response.status().is_success() => return Ok((response, permit)),
+            Ok(response) => {
+                let status = response.status();
+                let retry_after = parse_retry_after(response.headers());
+                let body = response.text().await.unwrap_or_default();
+                eprintln!("[AIRetry] API HTTP Error {}: {}", status, body);
+
+                if !is_retryable_status(status) || attempt >= policy.max_retries {
+                    return Err(format!("AI API Error ({}): {}", status, body));
+                }
+                (format!("AI API Error ({})", status), Some(status.as_u16()), retry_after)
+            }
+            Err(e) => {
+                if !(e.is_connect() || e.is_timeout()) || attempt >= policy.max_retries {
+                    return Err(format!("Network error: {}", e));
+                }
+                (format!("Network error: {}", e), None, None)
+            }
+        };
+
+        attempt += 1;
+        let delay = match retry_after {
+            Some(d) => d.min(policy.max_retry_after),
+            None => policy.backoff(attempt - 1),
+        };
+
+        let notice = RetryNotice {
+            attempt,
+            max_retries: policy.max_retries,
+            delay_ms: delay.as_millis() as u64,
+            reason,
+            status,
+        };
+        eprintln!("[AIRetry] {} - retry {}/{} in {}ms", notice.reason, attempt, policy.max_retries, notice.delay_ms);
+        on_retry(&notice);
+
+        // The slot is free while backing off, so other requests to the provider aren't held up by this one
+        drop(permit);
+        tokio::time::sleep(delay).await;
+        permit = acquire_provider_slot(config).await;
+    }
+}
+
+fn random_u64() -> u64 {
+    // RandomState is seeded per instance, which is plenty for jitter
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    hasher.write_u64(std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0));
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn test_retryable_statuses() {
+        assert!(is_retryable_status(StatusCode::TOO_MANY_REQUESTS));
+        assert!(is_retryable_status(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(is_retryable_status(StatusCode::from_u16(529).unwrap()));
+        assert!(!is_retryable_status(StatusCode::UNAUTHORIZED));
+        assert!(!is_retryable_status(StatusCode::BAD_REQUEST));
+        assert!(!is_retryable_status(StatusCode::NOT_FOUND));
+    }
+
+    #[test]
+    fn test_parse_retry_after_seconds() {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("7"));
+        assert_eq!(parse_retry_after(&headers), Some(Duration::from_secs(7)));
+
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"));
+        assert_eq!(parse_retry_after(&headers), Some(Duration::ZERO));
+
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("soon"));
+        assert_eq!(parse_retry_after(&headers), None);
+    }
+
+    #[test]
+    fn test_backoff_stays_within_bounds() {
+        let policy = RetryPolicy::default();
+        for attempt in 0..10 {
+            let delay = policy.backoff(attempt);
+            assert!(delay <= policy.max_delay);
+            assert!(delay >= policy.base_delay.min(policy.max_delay) / 2);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_slot_is_released_while_backing_off() {
+        // A server that always answers 429 with a one-second Retry-After
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            use std::io::{Read, Write};
+            for mut stream in listener.incoming().flatten() {
+                let _ = stream.read(&mut [0u8; 4096]);
+                let _ = stream.write_all(b"HTTP/1.1 429 Too Many Requests\r\nRetry-After: 1\r\nContent-Length: 0\r\n\r\n");
+            }
+        });
+        let config = AIProviderConfig { id: format!("retry-test-{}", random_u64()), ..Default::default() };
+        let policy = RetryPolicy { max_retries: 1, ..Default::default() };
+
+        let retrying_config = config.clone();
+        let request = tokio::spawn(async move {
+            let client = reqwest::Client::new();
+            send_with_retry(&retrying_config, &policy, || client.get(&url), |_| {}).await.map(|_| ())
+        });
+        tokio::time::sleep(Duration::from_millis(300)).await;
+
+        // Every slot is free while the request sleeps out its Retry-After
+        let mut permits = Vec::new();
+        for _ in 0..MAX_CONCURRENT_REQUESTS_PER_PROVIDER {
+            let permit = tokio::time::timeout(Duration::from_millis(200), acquire_provider_slot(&config)).await;
+            permits.push(permit.expect("slot held during backoff"));
+        }
+        drop(permits);
+        assert!(request.await.unwrap().unwrap_err().contains("429"));
+    }
+}
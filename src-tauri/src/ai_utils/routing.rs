@@ -0,0 +1,175 @@
+//! Model selection and fallback chains.
+//!
+//! A request is tried against an ordered list of `(provider, model)` targets: the selected
+//! model, then the provider's `fallback_models`, then each of its `fallback_providers`.
+//! The next target is used when the current one errors, is rate limited or runs out of
+//! context window.
+
+use crate::core_traits::ai::AIProviderConfig;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+/// Nested fallback providers are followed this many levels deep at most
+const MAX_FALLBACK_DEPTH: usize = 2;
+
+/// One attempt in a fallback chain
+#[derive(Debug, Clone)]
+pub struct ModelTarget {
+    pub config: AIProviderConfig,
+    pub model: String,
+}
+
+/// Why the previous target was abandoned
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, TS)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum FallbackReason {
+    ContextLimit,
+    RateLimited,
+    Error,
+}
+
+/// Sent to the frontend when a request moves on to the next model
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct FallbackNotice {
+    pub from_provider: String,
+    pub from_model: String,
+    pub to_provider: String,
+    pub to_model: String,
+    pub reason: FallbackReason,
+    pub error: String,
+}
+
+/// Return a copy of `config` with `model` as its primary model, for per-request selection.
+///
+/// The chosen model doesn't have to be listed in `models`.
+pub fn select_model(config: &AIProviderConfig, model: &str) -> AIProviderConfig {
+    let mut selected = config.clone();
+    selected.models.retain(|m| m != model);
+    selected.models.insert(0, model.to_string());
+    selected
+}
+
+/// The primary model of a provider, if it has one configured
+pub fn primary_model(config: &AIProviderConfig) -> Option<&str> {
+    config.models.iter().map(|m| m.as_str()).find(|m| !m.trim().is_empty())
+}
+
+/// Build the ordered list of targets for a request
+pub fn model_chain(config: &AIProviderConfig) -> Result<Vec<ModelTarget>, String> {
+    let mut chain = Vec::new();
+    collect_targets(config, 0, &mut chain);
+
+    if chain.is_empty() {
+        return Err(format!("No model configured for provider '{}'", config.name));
+    }
+    Ok(chain)
+}
+
+fn collect_targets(config: &AIProviderConfig, depth: usize, chain: &mut Vec<ModelTarget>) {
+    let models = primary_model(config).into_iter()
+        .chain(config.fallback_models.iter().map(|m| m.as_str()));
+
+    for model in models {
+        if model.trim().is_empty() {
+            continue;
+        }
+        let duplicate = chain.iter().any(|t| t.config.id == config.id && t.config.base_url == config.base_url && t.model == model);
+        if !duplicate {
+            chain.push(ModelTarget { config: config.clone(), model: model.to_string() });
+        }
+    }
+
+    if depth < MAX_FALLBACK_DEPTH {
+        for fallback in &config.fallback_providers {
+            collect_targets(fallback, depth + 1, chain);
+        }
+    }
+}
+
+/// Classify a request error for fallback reporting
+pub fn classify_error(error: &str) -> FallbackReason {
+    let lower = error.to_lowercase();
+    let context_markers = [
+        "context_length_exceeded",
+        "maximum context length",
+        "context window",
+        "prompt is too long",
+        "too many tokens",
+        "input is too long",
+    ];
+
+    if context_markers.iter().any(|m| lower.contains(m)) {
+        FallbackReason::ContextLimit
+    } else if lower.contains("(429") || lower.contains("rate limit") || lower.contains("rate_limit") {
+        FallbackReason::RateLimited
+    } else {
+        FallbackReason::Error
+    }
+}
+
+pub fn fallback_notice(from: &ModelTarget, to: &ModelTarget, error: &str) -> FallbackNotice {
+    FallbackNotice {
+        from_provider: from.config.id.clone(),
+        from_model: from.model.clone(),
+        to_provider: to.config.id.clone(),
+        to_model: to.model.clone(),
+        reason: classify_error(error),
+        error: error.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn provider(id: &str, models: &[&str]) -> AIProviderConfig {
+        AIProviderConfig {
+            id: id.to_string(),
+            name: id.to_string(),
+            models: models.iter().map(|m| m.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_chain_order() {
+        let mut primary = provider("openai", &["gpt-4o", "gpt-4o-mini"]);
+        primary.fallback_models = vec!["gpt-4o-mini".to_string()];
+        primary.fallback_providers = vec![provider("zhipu", &["glm-4.6"])];
+
+        let chain: Vec<(String, String)> = model_chain(&primary).unwrap()
+            .into_iter()
+            .map(|t| (t.config.id, t.model))
+            .collect();
+
+        assert_eq!(chain, vec![
+            ("openai".to_string(), "gpt-4o".to_string()),
+            ("openai".to_string(), "gpt-4o-mini".to_string()),
+            ("zhipu".to_string(), "glm-4.6".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_empty_models_is_an_error_not_a_panic() {
+        assert!(model_chain(&provider("empty", &[])).is_err());
+    }
+
+    #[test]
+    fn test_select_model_moves_choice_first() {
+        let selected = select_model(&provider("openai", &["gpt-4o", "o3"]), "o3");
+        assert_eq!(selected.models, vec!["o3".to_string(), "gpt-4o".to_string()]);
+    }
+
+    #[test]
+    fn test_classify_error() {
+        assert_eq!(
+            classify_error("AI API Error (400 Bad Request): This model's maximum context length is 128000 tokens"),
+            FallbackReason::ContextLimit
+        );
+        assert_eq!(classify_error("AI API Error (429 Too Many Requests): slow down"), FallbackReason::RateLimited);
+        assert_eq!(classify_error("Network error: connection refused"), FallbackReason::Error);
+    }
+}
@@ -0,0 +1,338 @@
+//! JSON-schema constrained completions.
+//!
+//! Providers with native support get the schema directly: OpenAI-compatible
+//! `response_format: json_schema`, a forced tool call on Anthropic and
+//! `responseSchema` on Gemini. Providers that reject it fall back to a schema
+//! instruction in the prompt. Either way the output is validated and the model is
+//! asked to repair it a limited number of times.
+
+use crate::core_traits::ai::{AIProviderConfig, AIProtocol, Content, ContentPart, Message, ModelRoute};
+use once_cell::sync::Lazy;
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::sync::Mutex;
+use super::{gemini, RoutedFailure};
+
+/// Repair rounds after the first answer fails validation
+const MAX_REPAIR_ATTEMPTS: usize = 2;
+
+/// Prefix of the error returned when the output never matched the schema
+pub const VALIDATION_ERROR_PREFIX: &str = "Schema validation failed";
+
+/// (provider id, model) pairs that rejected native structured output; as the primary model they get the prompt fallback directly
+static NATIVE_UNSUPPORTED: Lazy<Mutex<HashSet<(String, String)>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+/// A named JSON Schema the response has to match
+#[derive(Debug, Clone)]
+pub struct JsonOutput {
+    pub name: String,
+    pub schema: Value,
+}
+
+impl JsonOutput {
+    pub fn new(name: Option<String>, schema: Value) -> Self {
+        // Tool and schema names are limited to `[a-zA-Z0-9_-]{1,64}` by OpenAI and Anthropic
+        let name: String = name.unwrap_or_else(|| "response".to_string())
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+            .take(64)
+            .collect();
+        let name = if name.is_empty() { "response".to_string() } else { name };
+        Self { name, schema }
+    }
+
+    /// Native modes require an object at the root; other schemas are wrapped in `{ "value": ... }`
+    fn is_wrapped(&self) -> bool {
+        self.schema["type"].as_str() != Some("object")
+    }
+
+    fn wire_schema(&self) -> Value {
+        if self.is_wrapped() {
+            json!({
+                "type": "object",
+                "properties": { "value": self.schema },
+                "required": ["value"]
+            })
+        } else {
+            self.schema.clone()
+        }
+    }
+
+    fn unwrap_value(&self, value: Value) -> Value {
+        match value {
+            Value::Object(mut map) if self.is_wrapped() && map.len() == 1 && map.contains_key("value") => {
+                map.remove("value").unwrap_or(Value::Null)
+            }
+            other => other,
+        }
+    }
+}
+
+/// Add the provider's native structured-output fields to a request body
+pub(crate) fn apply_native(protocol: &AIProtocol, body: &mut Value, output: &JsonOutput) {
+    let schema = output.wire_schema();
+    match protocol {
+        AIProtocol::Anthropic => {
+            body["tools"] = json!([{
+                "name": output.name,
+                "description": "Respond by calling this tool with the requested data.",
+                "input_schema": schema
+            }]);
+            body["tool_choice"] = json!({ "type": "tool", "name": output.name });
+        }
+        AIProtocol::Gemini => {
+            body["generationConfig"]["responseMimeType"] = json!("application/json");
+            body["generationConfig"]["responseSchema"] = gemini::clean_schema(&schema);
+        }
+        _ => {
+            body["response_format"] = json!({
+                "type": "json_schema",
+                "json_schema": { "name": output.name, "schema": schema }
+            });
+        }
+    }
+}
+
+/// Request a completion whose answer matches `output.schema`.
+///
+/// Returns the parsed value, or an error starting with [`VALIDATION_ERROR_PREFIX`] when the
+/// model kept producing output that doesn't match.
+pub async fn fetch_structured_completion(
+    config: &AIProviderConfig,
+    messages: Vec<Message>,
+    output: &JsonOutput,
+) -> Result<(Value, ModelRoute), String> {
+    let validator = jsonschema::validator_for(&output.schema)
+        .map_err(|e| format!("Invalid JSON schema: {}", e))?;
+
+    let primary = (config.id.clone(), config.models.first().cloned().unwrap_or_default());
+    let mut native = !NATIVE_UNSUPPORTED.lock().unwrap().contains(&primary);
+    let mut history = messages;
+    if !native {
+        history.push(schema_instruction(output));
+    }
+
+    let mut last_problem = String::new();
+    let mut attempt = 0;
+
+    while attempt <= MAX_REPAIR_ATTEMPTS {
+        let result = super::fetch_routed_with_output(
+            config,
+            history.clone(),
+            None,
+            if native { Some(output) } else { None },
+        ).await;
+
+        let (message, route) = match result {
+            Ok(r) => r,
+            // The rejecting model may be a fallback reached after the primary failed, so that is the one remembered
+            Err(RoutedFailure::OutputRejected { route, .. }) => {
+                println!("[Structured] {}/{} rejected native structured output, using prompt fallback", route.provider_id, route.model);
+                NATIVE_UNSUPPORTED.lock().unwrap().insert((route.provider_id, route.model));
+                native = false;
+                history.push(schema_instruction(output));
+                continue;
+            }
+            Err(failure) => return Err(failure.into_error()),
+        };
+
+        let raw = raw_output(&message, output);
+        let problem = match parse_json(&raw) {
+            Ok(value) => {
+                let value = if native { output.unwrap_value(value) } else { value };
+                let errors: Vec<String> = validator.iter_errors(&value)
+                    .map(|e| format!("{}: {}", display_path(&e.instance_path().to_string()), e))
+                    .collect();
+                if errors.is_empty() {
+                    return Ok((value, route));
+                }
+                errors.join("; ")
+            }
+            Err(e) => format!("not valid JSON ({})", e),
+        };
+
+        eprintln!("[Structured] Attempt {} did not match the schema: {}", attempt + 1, problem);
+        history.push(Message {
+            role: "assistant".to_string(),
+            content: Content::Text(raw),
+            tool_calls: None,
+            tool_call_id: None,
+            thinking_blocks: None,
+        });
+        history.push(Message {
+            role: "user".to_string(),
+            content: Content::Text(format!(
+                "That response does not match the required JSON schema: {}\nReply again with only the corrected JSON.",
+                problem
+            )),
+            tool_calls: None,
+            tool_call_id: None,
+            thinking_blocks: None,
+        });
+        last_problem = problem;
+        attempt += 1;
+    }
+
+    Err(format!("{} after {} attempts: {}", VALIDATION_ERROR_PREFIX, attempt, last_problem))
+}
+
+fn schema_instruction(output: &JsonOutput) -> Message {
+    Message {
+        role: "system".to_string(),
+        content: Content::Text(format!(
+            "Respond with a single JSON value that matches this JSON Schema. \
+             Output only the JSON, without markdown fences or explanations.\n\n{}",
+            serde_json::to_string_pretty(&output.schema).unwrap_or_default()
+        )),
+        tool_calls: None,
+        tool_call_id: None,
+        thinking_blocks: None,
+    }
+}
+
+/// A 400/422 that names the structured-output fields means the model doesn't support them
+pub(crate) fn rejects_native_output(error: &str) -> bool {
+    let lower = error.to_lowercase();
+    let client_error = lower.contains("(400") || lower.contains("(422");
+    client_error && ["response_format", "json_schema", "responseschema", "tool_choice"].iter().any(|m| lower.contains(m))
+}
+
+/// The JSON text of a response: the forced tool call's arguments, or the message text
+fn raw_output(message: &Message, output: &JsonOutput) -> String {
+    let forced_call = message.tool_calls.as_ref()
+        .and_then(|calls| calls.iter().find(|c| c.function.name == output.name));
+    if let Some(call) = forced_call {
+        return call.function.arguments.clone();
+    }
+
+    match &message.content {
+        Content::Text(text) => text.clone(),
+        Content::Parts(parts) => parts.iter()
+            .filter_map(|p| match p {
+                ContentPart::Text { text, .. } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join(""),
+    }
+}
+
+/// Parse JSON from model output, tolerating markdown fences and surrounding prose
+fn parse_json(raw: &str) -> Result<Value, serde_json::Error> {
+    let trimmed = raw.trim();
+    if let Ok(value) = serde_json::from_str(trimmed) {
+        return Ok(value);
+    }
+
+    let unfenced = trimmed
+        .strip_prefix("```json")
+        .or_else(|| trimmed.strip_prefix("```"))
+        .and_then(|s| s.trim_end().strip_suffix("```"))
+        .map(str::trim);
+    if let Some(inner) = unfenced {
+        if let Ok(value) = serde_json::from_str(inner) {
+            return Ok(value);
+        }
+    }
+
+    // Outermost object or array embedded in prose
+    let start = trimmed.find(['{', '[']);
+    let end = trimmed.rfind(['}', ']']);
+    match (start, end) {
+        (Some(s), Some(e)) if e > s => serde_json::from_str(&trimmed[s..=e]),
+        _ => serde_json::from_str(trimmed),
+    }
+}
+
+fn display_path(path: &str) -> &str {
+    if path.is_empty() { "(root)" } else { path }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_json_tolerates_fences_and_prose() {
+        assert_eq!(parse_json("```json\n{\"a\": 1}\n```").unwrap(), json!({"a": 1}));
+        assert_eq!(parse_json("Here you go: {\"a\": [1, 2]} hope it helps").unwrap(), json!({"a": [1, 2]}));
+        assert!(parse_json("no json here").is_err());
+    }
+
+    #[test]
+    fn test_non_object_schema_is_wrapped_for_native_modes() {
+        let output = JsonOutput::new(Some("commit message".to_string()), json!({ "type": "array", "items": { "type": "string" } }));
+        assert_eq!(output.name, "commit_message");
+
+        let mut body = json!({});
+        apply_native(&AIProtocol::Anthropic, &mut body, &output);
+        assert_eq!(body["tool_choice"]["name"], "commit_message");
+        assert_eq!(body["tools"][0]["input_schema"]["properties"]["value"]["type"], "array");
+
+        assert_eq!(output.unwrap_value(json!({ "value": ["a"] })), json!(["a"]));
+    }
+
+    #[test]
+    fn test_rejects_native_output() {
+        assert!(rejects_native_output("AI API Error (400 Bad Request): response_format.type json_schema is not supported"));
+        assert!(!rejects_native_output("AI API Error (401 Unauthorized): invalid key"));
+    }
+
+    #[tokio::test]
+    async fn test_native_rejection_switches_to_prompt_before_falling_back() {
+        use crate::cassette::{Cassette, Interaction, MockServer};
+
+        let server = MockServer::replay(Cassette::default()
+            .with(Interaction::error(400, "response_format json_schema is not supported by this model"))
+            .with(Interaction::text(r#"{"title": "Fix login"}"#)))
+            .await
+            .unwrap();
+        let config = AIProviderConfig {
+            id: format!("structured-test-{}", uuid::Uuid::new_v4()),
+            base_url: server.chat_url(),
+            models: vec!["local-model".to_string()],
+            fallback_models: vec!["other-model".to_string()],
+            ..Default::default()
+        };
+        let output = JsonOutput::new(None, json!({ "type": "object", "properties": { "title": { "type": "string" } }, "required": ["title"] }));
+
+        let (value, route) = fetch_structured_completion(&config, vec![], &output).await.unwrap();
+
+        assert_eq!(value["title"], "Fix login");
+        // The same model is asked again with the schema in the prompt, not the fallback model
+        assert_eq!(route.model, "local-model");
+        let requests = server.requests();
+        assert!(requests[1].get("response_format").is_none());
+        assert!(NATIVE_UNSUPPORTED.lock().unwrap().contains(&(config.id.clone(), "local-model".to_string())));
+    }
+
+    #[tokio::test]
+    async fn test_rejection_by_a_fallback_is_remembered_for_that_model() {
+        use crate::cassette::{Cassette, Interaction, MockServer};
+
+        // The primary is missing both times; the fallback rejects the schema, then answers from the prompt
+        let server = MockServer::replay(Cassette::default()
+            .with(Interaction::error(404, "model not found"))
+            .with(Interaction::error(400, "response_format json_schema is not supported by this model"))
+            .with(Interaction::error(404, "model not found"))
+            .with(Interaction::text(r#"{"title": "Fix login"}"#)))
+            .await
+            .unwrap();
+        let config = AIProviderConfig {
+            id: format!("structured-test-{}", uuid::Uuid::new_v4()),
+            base_url: server.chat_url(),
+            models: vec!["local-model".to_string()],
+            fallback_models: vec!["other-model".to_string()],
+            ..Default::default()
+        };
+        let output = JsonOutput::new(None, json!({ "type": "object", "properties": { "title": { "type": "string" } }, "required": ["title"] }));
+
+        let (value, route) = fetch_structured_completion(&config, vec![], &output).await.unwrap();
+
+        assert_eq!(value["title"], "Fix login");
+        assert_eq!(route.model, "other-model");
+        let unsupported = NATIVE_UNSUPPORTED.lock().unwrap();
+        assert!(unsupported.contains(&(config.id.clone(), "other-model".to_string())));
+        assert!(!unsupported.contains(&(config.id.clone(), "local-model".to_string())));
+    }
+}
@@ -0,0 +1,271 @@
+use tauri::command;
+use serde::Serialize;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use image::{DynamicImage, ImageFormat, ImageReader};
+use image::imageops::FilterType;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use crate::core_traits::ai::{Message, Content, ContentPart};
+
+/// Longest edge sent to providers. Anthropic downsizes anything larger and OpenAI
+/// tiles it away, so bigger images only cost upload time.
+const MAX_LONG_EDGE: u32 = 1568;
+
+/// JPEG quality for re-encoded photos and screenshots without transparency
+const JPEG_QUALITY: u8 = 85;
+
+/// Used when an image's size can't be determined (a remote URL), roughly a 1024x1024 image
+const DEFAULT_IMAGE_TOKENS: usize = 765;
+
+const IMAGE_EXTENSIONS: [&str; 7] = ["png", "jpg", "jpeg", "webp", "gif", "bmp", "tiff"];
+
+/// A local image ready to be sent as an `image_url` content part
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PreparedImage {
+    pub source_path: String,
+    pub data_url: String,
+    pub mime_type: String,
+    pub width: u32,
+    pub height: u32,
+    pub original_width: u32,
+    pub original_height: u32,
+    pub bytes: usize,
+    pub estimated_tokens: usize,
+}
+
+pub fn is_image_path(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| IMAGE_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+fn attachments_dir(project_root: &str) -> PathBuf {
+    Path::new(project_root).join(".ifai").join("attachments")
+}
+
+/// Resolve `file://` URLs and project-relative paths (e.g. `.ifai/attachments/shot.png`)
+fn resolve_path(path: &str, project_root: Option<&str>) -> PathBuf {
+    let path = path.strip_prefix("file://").unwrap_or(path);
+    let candidate = PathBuf::from(path);
+    match project_root {
+        Some(root) if candidate.is_relative() => Path::new(root).join(candidate),
+        _ => candidate,
+    }
+}
+
+/// Scale `(width, height)` down so the longest edge fits `max_edge`, keeping the aspect ratio
+fn fit_within(width: u32, height: u32, max_edge: u32) -> (u32, u32) {
+    let long_edge = width.max(height);
+    if long_edge <= max_edge {
+        return (width, height);
+    }
+    let scale = max_edge as f64 / long_edge as f64;
+    (
+        ((width as f64 * scale).round() as u32).max(1),
+        ((height as f64 * scale).round() as u32).max(1),
+    )
+}
+
+/// Vision token estimate following OpenAI's high-detail tiling: fit in 2048x2048,
+/// scale the short side to 768, then 170 tokens per 512px tile plus 85 base tokens.
+pub fn estimate_image_tokens(width: u32, height: u32) -> usize {
+    if width == 0 || height == 0 {
+        return DEFAULT_IMAGE_TOKENS;
+    }
+    let (w, h) = fit_within(width, height, 2048);
+    let short_edge = w.min(h);
+    let (w, h) = if short_edge > 768 {
+        let scale = 768.0 / short_edge as f64;
+        ((w as f64 * scale).round() as u32, (h as f64 * scale).round() as u32)
+    } else {
+        (w, h)
+    };
+
+    let tiles = w.div_ceil(512) as usize * h.div_ceil(512) as usize;
+    85 + 170 * tiles
+}
+
+/// Token estimate for an `image_url` part. Data URLs are measured, other URLs get a default.
+pub fn image_url_tokens(url: &str) -> usize {
+    data_url_dimensions(url)
+        .map(|(w, h)| estimate_image_tokens(w, h))
+        .unwrap_or(DEFAULT_IMAGE_TOKENS)
+}
+
+fn data_url_dimensions(url: &str) -> Option<(u32, u32)> {
+    let (meta, data) = url.strip_prefix("data:")?.split_once(',')?;
+    if !meta.ends_with(";base64") {
+        return None;
+    }
+    let bytes = BASE64.decode(data).ok()?;
+    // Only the header is parsed, not the pixels
+    ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .ok()?
+        .into_dimensions()
+        .ok()
+}
+
+/// Decode, downscale and re-encode image bytes into a data URL
+fn prepare_image_bytes(source_path: String, bytes: &[u8]) -> Result<PreparedImage, String> {
+    let image = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .map_err(|e| format!("Failed to read image {}: {}", source_path, e))?
+        .decode()
+        .map_err(|e| format!("Failed to decode image {}: {}", source_path, e))?;
+
+    let (original_width, original_height) = (image.width(), image.height());
+    let (width, height) = fit_within(original_width, original_height, MAX_LONG_EDGE);
+    let image = if (width, height) != (original_width, original_height) {
+        image.resize_exact(width, height, FilterType::Lanczos3)
+    } else {
+        image
+    };
+
+    let (encoded, mime_type) = encode(&image)?;
+    let data_url = format!("data:{};base64,{}", mime_type, BASE64.encode(&encoded));
+
+    Ok(PreparedImage {
+        source_path,
+        data_url,
+        mime_type: mime_type.to_string(),
+        width,
+        height,
+        original_width,
+        original_height,
+        bytes: encoded.len(),
+        estimated_tokens: estimate_image_tokens(width, height),
+    })
+}
+
+/// PNG keeps transparency; everything else becomes JPEG, which is much smaller for photos and screenshots
+fn encode(image: &DynamicImage) -> Result<(Vec<u8>, &'static str), String> {
+    let mut buffer = Cursor::new(Vec::new());
+    if image.color().has_alpha() {
+        image.write_to(&mut buffer, ImageFormat::Png)
+            .map_err(|e| format!("Failed to encode PNG: {}", e))?;
+        Ok((buffer.into_inner(), "image/png"))
+    } else {
+        let encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(&mut buffer, JPEG_QUALITY);
+        image.to_rgb8().write_with_encoder(encoder)
+            .map_err(|e| format!("Failed to encode JPEG: {}", e))?;
+        Ok((buffer.into_inner(), "image/jpeg"))
+    }
+}
+
+pub fn prepare_image(path: &Path) -> Result<PreparedImage, String> {
+    let bytes = std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    prepare_image_bytes(path.to_string_lossy().to_string(), &bytes)
+}
+
+/// Replace local `image_url`s (absolute paths, `file://` URLs or project-relative paths) with data URLs.
+/// Images that can't be read are left as they are so the provider reports the problem.
+pub fn inline_local_images(messages: &mut [Message], project_root: Option<&str>) {
+    for msg in messages.iter_mut() {
+        let Content::Parts(parts) = &mut msg.content else { continue };
+        for part in parts.iter_mut() {
+            let ContentPart::ImageUrl { image_url } = part else { continue };
+            let url = image_url.url.as_str();
+            if url.starts_with("data:") || url.starts_with("http://") || url.starts_with("https://") {
+                continue;
+            }
+
+            match prepare_image(&resolve_path(url, project_root)) {
+                Ok(prepared) => {
+                    println!("[Attachments] Inlined {} ({}x{}, {} bytes)",
+                        url, prepared.width, prepared.height, prepared.bytes);
+                    image_url.url = prepared.data_url;
+                }
+                Err(e) => eprintln!("[Attachments] {}", e),
+            }
+        }
+    }
+}
+
+/// [`inline_local_images`] on the blocking pool; reading and resizing images would stall async commands
+pub async fn inline_local_images_async(mut messages: Vec<Message>, project_root: Option<String>) -> Result<Vec<Message>, String> {
+    tokio::task::spawn_blocking(move || {
+        inline_local_images(&mut messages, project_root.as_deref());
+        messages
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))
+}
+
+pub fn prepare_images_blocking(paths: &[String], project_root: Option<&str>) -> Vec<Result<PreparedImage, String>> {
+    paths.iter()
+        .map(|p| prepare_image(&resolve_path(p, project_root)))
+        .collect()
+}
+
+/// Convert local images (dropped files, `.ifai/attachments` screenshots) into data URLs.
+/// Non-image paths are skipped; unreadable images fail the whole call.
+#[command]
+pub async fn prepare_image_attachments(paths: Vec<String>, project_root: Option<String>) -> Result<Vec<PreparedImage>, String> {
+    let paths: Vec<String> = paths.into_iter()
+        .filter(|p| is_image_path(Path::new(p)))
+        .collect();
+
+    tokio::task::spawn_blocking(move || {
+        prepare_images_blocking(&paths, project_root.as_deref())
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Image files saved under `.ifai/attachments`, newest first
+#[command]
+pub async fn list_image_attachments(project_root: String) -> Result<Vec<String>, String> {
+    let dir = attachments_dir(&project_root);
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut files: Vec<(std::time::SystemTime, String)> = std::fs::read_dir(&dir)
+        .map_err(|e| e.to_string())?
+        .filter_map(|e| e.ok())
+        .filter(|e| is_image_path(&e.path()))
+        .map(|e| {
+            let modified = e.metadata().and_then(|m| m.modified()).unwrap_or(std::time::UNIX_EPOCH);
+            (modified, e.path().to_string_lossy().to_string())
+        })
+        .collect();
+
+    files.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+    Ok(files.into_iter().map(|(_, path)| path).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    fn png_bytes(width: u32, height: u32) -> Vec<u8> {
+        let image = DynamicImage::ImageRgb8(RgbImage::from_pixel(width, height, Rgb([200, 30, 30])));
+        let mut buffer = Cursor::new(Vec::new());
+        image.write_to(&mut buffer, ImageFormat::Png).unwrap();
+        buffer.into_inner()
+    }
+
+    #[test]
+    fn test_large_image_is_downscaled_to_jpeg_data_url() {
+        let prepared = prepare_image_bytes("shot.png".to_string(), &png_bytes(3136, 1000)).unwrap();
+        assert_eq!((prepared.width, prepared.height), (1568, 500));
+        assert_eq!(prepared.mime_type, "image/jpeg");
+        assert!(prepared.data_url.starts_with("data:image/jpeg;base64,"));
+        assert_eq!(image_url_tokens(&prepared.data_url), prepared.estimated_tokens);
+    }
+
+    #[test]
+    fn test_image_token_estimate() {
+        // 1024x1024 -> 768x768 -> 4 tiles
+        assert_eq!(estimate_image_tokens(1024, 1024), 765);
+        assert_eq!(estimate_image_tokens(100, 100), 255);
+        assert_eq!(image_url_tokens("https://example.com/cat.png"), DEFAULT_IMAGE_TOKENS);
+    }
+}
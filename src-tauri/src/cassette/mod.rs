@@ -0,0 +1,206 @@
+//! Record/replay of provider traffic for deterministic, offline tests.
+//!
+//! A cassette is a JSON fixture of OpenAI-compatible request bodies and the responses they
+//! got: a JSON body for normal completions, or the raw SSE `data:` payloads for streams.
+//! [`MockServer`] serves a cassette over HTTP (or records one by proxying to a real
+//! provider) and [`CassetteAIService`] routes an `AIService` through it.
+//!
+//! Debug builds of the app pick a cassette up from the environment:
+//! `IFAI_CASSETTE=<file>` replays it, adding `IFAI_CASSETTE_UPSTREAM=<chat completions url>`
+//! records real traffic into it instead.
+
+pub mod server;
+pub mod service;
+
+pub use server::MockServer;
+pub use service::CassetteAIService;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::Path;
+
+const CASSETTE_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Cassette {
+    #[serde(default = "default_version")]
+    pub version: u32,
+    #[serde(default)]
+    pub interactions: Vec<Interaction>,
+}
+
+fn default_version() -> u32 {
+    CASSETTE_VERSION
+}
+
+impl Default for Cassette {
+    fn default() -> Self {
+        Self { version: CASSETTE_VERSION, interactions: vec![] }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Interaction {
+    /// The request body as sent to the provider
+    #[serde(default)]
+    pub request: Value,
+    pub response: RecordedResponse,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    #[serde(default = "default_status")]
+    pub status: u16,
+    /// Body of a non-streaming response
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<Value>,
+    /// `data:` payloads of a streaming response, in order (including `[DONE]`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub events: Option<Vec<String>>,
+}
+
+fn default_status() -> u16 {
+    200
+}
+
+impl Cassette {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read cassette {}: {}", path.display(), e))?;
+        serde_json::from_str(&content)
+            .map_err(|e| format!("Invalid cassette {}: {}", path.display(), e))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let content = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        std::fs::write(path, content).map_err(|e| format!("Failed to write cassette {}: {}", path.display(), e))
+    }
+
+    pub fn with(mut self, interaction: Interaction) -> Self {
+        self.interactions.push(interaction);
+        self
+    }
+
+    /// The interaction to replay for `request`: the first unused one recorded for an identical
+    /// request, otherwise the next unused one in recording order
+    pub(crate) fn find(&self, used: &[bool], request: &Value) -> Option<usize> {
+        let key = request_key(request);
+        let unused = || (0..self.interactions.len()).filter(|i| !used.get(*i).copied().unwrap_or(false));
+
+        unused()
+            .find(|i| request_key(&self.interactions[*i].request) == key)
+            .or_else(|| unused().next())
+    }
+}
+
+/// Request fields that identify an interaction. Streaming flags are ignored so a recording
+/// can be replayed by either code path.
+fn request_key(request: &Value) -> Value {
+    json!({
+        "model": request["model"],
+        "messages": request["messages"],
+        "tools": request["tools"],
+    })
+}
+
+impl Interaction {
+    /// A non-streaming completion answering with `content`
+    pub fn text(content: &str) -> Self {
+        Self::completion(json!({ "role": "assistant", "content": content }))
+    }
+
+    /// A non-streaming completion answering with a single tool call
+    pub fn tool_call(id: &str, name: &str, arguments: Value) -> Self {
+        Self::completion(json!({
+            "role": "assistant",
+            "content": null,
+            "tool_calls": [{
+                "id": id,
+                "type": "function",
+                "function": { "name": name, "arguments": arguments.to_string() }
+            }]
+        }))
+    }
+
+    fn completion(message: Value) -> Self {
+        Self {
+            request: Value::Null,
+            response: RecordedResponse {
+                status: 200,
+                body: Some(json!({
+                    "choices": [{ "index": 0, "message": message, "finish_reason": "stop" }],
+                    "usage": { "prompt_tokens": 10, "completion_tokens": 5 }
+                })),
+                events: None,
+            },
+        }
+    }
+
+    /// A streamed completion delivering `chunks` as content deltas
+    pub fn stream_text(chunks: &[&str]) -> Self {
+        let events = chunks.iter()
+            .map(|c| json!({ "choices": [{ "index": 0, "delta": { "content": c } }] }))
+            .collect();
+        Self::stream(events)
+    }
+
+    /// A streamed tool call, with the arguments split across two deltas like real providers do
+    pub fn stream_tool_call(id: &str, name: &str, arguments: Value) -> Self {
+        Self::stream_tool_calls(&[(id, name, arguments)])
+    }
+
+    /// Several streamed tool calls in one turn. Every call is opened before any arguments
+    /// are completed, so the deltas of different calls interleave.
+    pub fn stream_tool_calls(calls: &[(&str, &str, Value)]) -> Self {
+        let split: Vec<(String, String)> = calls.iter()
+            .map(|(_, _, arguments)| {
+                let arguments = arguments.to_string();
+                let (head, tail) = arguments.split_at(arguments.len() / 2);
+                (head.to_string(), tail.to_string())
+            })
+            .collect();
+
+        let heads = calls.iter().zip(&split).enumerate().map(|(index, ((id, name, _), (head, _)))| {
+            json!({ "choices": [{ "index": 0, "delta": { "tool_calls": [{
+                "index": index, "id": id, "type": "function",
+                "function": { "name": name, "arguments": head }
+            }] } }] })
+        });
+        let tails = split.iter().enumerate().map(|(index, (_, tail))| {
+            json!({ "choices": [{ "index": 0, "delta": { "tool_calls": [{
+                "index": index, "function": { "arguments": tail }
+            }] } }] })
+        });
+
+        Self::stream(heads.chain(tails).collect())
+    }
+
+    fn stream(mut events: Vec<Value>) -> Self {
+        events.push(json!({
+            "choices": [],
+            "usage": { "prompt_tokens": 10, "completion_tokens": 5 }
+        }));
+        let mut events: Vec<String> = events.iter().map(|e| e.to_string()).collect();
+        events.push("[DONE]".to_string());
+
+        Self {
+            request: Value::Null,
+            response: RecordedResponse { status: 200, body: None, events: Some(events) },
+        }
+    }
+
+    /// An error response, e.g. to exercise retries and fallbacks
+    pub fn error(status: u16, message: &str) -> Self {
+        Self {
+            request: Value::Null,
+            response: RecordedResponse {
+                status,
+                body: Some(json!({ "error": { "message": message } })),
+                events: None,
+            },
+        }
+    }
+}
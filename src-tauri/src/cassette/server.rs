@@ -0,0 +1,385 @@
+//! Local OpenAI-compatible HTTP server that replays a cassette, or records one by
+//! proxying requests to a real provider.
+
+use super::{Cassette, Interaction, RecordedResponse};
+use eventsource_stream::Eventsource;
+use futures::stream::StreamExt;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+enum Mode {
+    Replay,
+    Record { upstream: String, client: reqwest::Client },
+}
+
+struct ServerState {
+    cassette: Cassette,
+    used: Vec<bool>,
+    /// Request bodies received so far, for assertions in tests
+    requests: Vec<Value>,
+    /// Where recordings are saved
+    path: Option<PathBuf>,
+    mode: Mode,
+}
+
+struct HttpRequest {
+    method: String,
+    path: String,
+    headers: HashMap<String, String>,
+    body: Vec<u8>,
+}
+
+pub struct MockServer {
+    addr: SocketAddr,
+    state: Arc<Mutex<ServerState>>,
+    task: JoinHandle<()>,
+}
+
+impl MockServer {
+    /// Serve the interactions of `cassette`
+    pub async fn replay(cassette: Cassette) -> Result<Self, String> {
+        let used = vec![false; cassette.interactions.len()];
+        Self::start(ServerState { cassette, used, requests: vec![], path: None, mode: Mode::Replay }).await
+    }
+
+    pub async fn replay_file(path: &Path) -> Result<Self, String> {
+        Self::replay(Cassette::load(path)?).await
+    }
+
+    /// Forward every request to `upstream` (a chat completions URL) and save the
+    /// exchanges to `path`, replacing any previous recording
+    pub async fn record(path: &Path, upstream: &str) -> Result<Self, String> {
+        let client = reqwest::Client::builder()
+            .build()
+            .map_err(|e| e.to_string())?;
+        Self::start(ServerState {
+            cassette: Cassette::default(),
+            used: vec![],
+            requests: vec![],
+            path: Some(path.to_path_buf()),
+            mode: Mode::Record { upstream: upstream.to_string(), client },
+        }).await
+    }
+
+    async fn start(state: ServerState) -> Result<Self, String> {
+        let listener = TcpListener::bind("127.0.0.1:0").await.map_err(|e| e.to_string())?;
+        let addr = listener.local_addr().map_err(|e| e.to_string())?;
+        let state = Arc::new(Mutex::new(state));
+
+        let accept_state = state.clone();
+        let task = tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                let state = accept_state.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = handle_connection(socket, state).await {
+                        eprintln!("[MockServer] {}", e);
+                    }
+                });
+            }
+        });
+
+        println!("[MockServer] Listening on {}", addr);
+        Ok(Self { addr, state, task })
+    }
+
+    pub fn base_url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// URL to use as an OpenAI-protocol provider's `base_url`
+    pub fn chat_url(&self) -> String {
+        format!("{}/v1/chat/completions", self.base_url())
+    }
+
+    /// Request bodies received so far
+    pub fn requests(&self) -> Vec<Value> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    /// Interactions that haven't been replayed yet
+    pub fn unused_interactions(&self) -> usize {
+        self.state.lock().unwrap().used.iter().filter(|u| !**u).count()
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn handle_connection(mut socket: TcpStream, state: Arc<Mutex<ServerState>>) -> Result<(), String> {
+    let request = read_request(&mut socket).await?;
+
+    if request.method == "GET" && request.path.trim_end_matches('/').ends_with("/models") {
+        let listing = model_listing(&state.lock().unwrap().cassette);
+        return write_json(&mut socket, 200, &listing).await;
+    }
+
+    let body: Value = serde_json::from_slice(&request.body).unwrap_or(Value::Null);
+
+    // Pick what to do while holding the lock, then release it before any I/O
+    let upstream = {
+        let mut state = state.lock().unwrap();
+        state.requests.push(body.clone());
+        match &state.mode {
+            Mode::Replay => None,
+            Mode::Record { upstream, client } => Some((upstream.clone(), client.clone())),
+        }
+    };
+
+    match upstream {
+        Some((upstream, client)) => record(&mut socket, &state, &client, &upstream, &request, body).await,
+        None => replay(&mut socket, &state, &body).await,
+    }
+}
+
+async fn replay(socket: &mut TcpStream, state: &Arc<Mutex<ServerState>>, body: &Value) -> Result<(), String> {
+    let response = {
+        let mut state = state.lock().unwrap();
+        match state.cassette.find(&state.used, body) {
+            Some(index) => {
+                state.used[index] = true;
+                Some(state.cassette.interactions[index].response.clone())
+            }
+            None => None,
+        }
+    };
+
+    let Some(response) = response else {
+        let error = json!({ "error": { "message": "No recorded interaction left for this request" } });
+        return write_json(socket, 500, &error).await;
+    };
+
+    match response.events {
+        Some(events) => {
+            write_sse_head(socket, response.status).await?;
+            for data in events {
+                write_sse_event(socket, &data).await?;
+            }
+            socket.shutdown().await.map_err(|e| e.to_string())
+        }
+        None => write_json(socket, response.status, &response.body.unwrap_or(Value::Null)).await,
+    }
+}
+
+async fn record(
+    socket: &mut TcpStream,
+    state: &Arc<Mutex<ServerState>>,
+    client: &reqwest::Client,
+    upstream: &str,
+    request: &HttpRequest,
+    body: Value,
+) -> Result<(), String> {
+    let mut outgoing = client.post(upstream).json(&body);
+    if let Some(auth) = request.headers.get("authorization") {
+        outgoing = outgoing.header("Authorization", auth);
+    }
+
+    let response = match outgoing.send().await {
+        Ok(r) => r,
+        Err(e) => {
+            // Transport failures aren't recorded; replaying them wouldn't be meaningful
+            let error = json!({ "error": { "message": format!("Upstream request failed: {}", e) } });
+            return write_json(socket, 502, &error).await;
+        }
+    };
+
+    let status = response.status().as_u16();
+    let is_stream = response.headers().get("content-type")
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| ct.contains("text/event-stream"));
+
+    let recorded = if is_stream {
+        write_sse_head(socket, status).await?;
+        let mut events = Vec::new();
+        let mut stream = response.bytes_stream().eventsource();
+        while let Some(event) = stream.next().await {
+            match event {
+                Ok(event) => {
+                    write_sse_event(socket, &event.data).await?;
+                    events.push(event.data);
+                }
+                Err(e) => {
+                    eprintln!("[MockServer] Upstream stream error: {}", e);
+                    break;
+                }
+            }
+        }
+        socket.shutdown().await.map_err(|e| e.to_string())?;
+        RecordedResponse { status, body: None, events: Some(events) }
+    } else {
+        let text = response.text().await.map_err(|e| e.to_string())?;
+        let json = serde_json::from_str(&text).unwrap_or(Value::String(text));
+        write_json(socket, status, &json).await?;
+        RecordedResponse { status, body: Some(json), events: None }
+    };
+
+    let mut state = state.lock().unwrap();
+    state.cassette.interactions.push(Interaction { request: body, response: recorded });
+    state.used.push(true);
+    if let Some(path) = &state.path {
+        state.cassette.save(path)?;
+    }
+    Ok(())
+}
+
+async fn read_request(socket: &mut TcpStream) -> Result<HttpRequest, String> {
+    let mut reader = BufReader::new(socket);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).await.map_err(|e| e.to_string())?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let path = parts.next().unwrap_or("/").to_string();
+
+    let mut headers = HashMap::new();
+    loop {
+        let mut line = String::new();
+        let n = reader.read_line(&mut line).await.map_err(|e| e.to_string())?;
+        let line = line.trim_end();
+        if n == 0 || line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+        }
+    }
+
+    let length = headers.get("content-length").and_then(|l| l.parse::<usize>().ok()).unwrap_or(0);
+    let mut body = vec![0u8; length];
+    reader.read_exact(&mut body).await.map_err(|e| e.to_string())?;
+
+    Ok(HttpRequest { method, path, headers, body })
+}
+
+async fn write_json<W: AsyncWrite + Unpin>(socket: &mut W, status: u16, body: &Value) -> Result<(), String> {
+    let body = body.to_string();
+    let response = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status, reason_phrase(status), body.len(), body
+    );
+    socket.write_all(response.as_bytes()).await.map_err(|e| e.to_string())?;
+    socket.shutdown().await.map_err(|e| e.to_string())
+}
+
+async fn write_sse_head<W: AsyncWrite + Unpin>(socket: &mut W, status: u16) -> Result<(), String> {
+    let head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n",
+        status, reason_phrase(status)
+    );
+    socket.write_all(head.as_bytes()).await.map_err(|e| e.to_string())
+}
+
+async fn write_sse_event<W: AsyncWrite + Unpin>(socket: &mut W, data: &str) -> Result<(), String> {
+    socket.write_all(format!("data: {}\n\n", data).as_bytes()).await.map_err(|e| e.to_string())?;
+    socket.flush().await.map_err(|e| e.to_string())
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+    reqwest::StatusCode::from_u16(status).ok()
+        .and_then(|s| s.canonical_reason())
+        .unwrap_or("Unknown")
+}
+
+/// `/models` listing built from the models the cassette was recorded with
+fn model_listing(cassette: &Cassette) -> Value {
+    let mut models: Vec<&str> = cassette.interactions.iter()
+        .filter_map(|i| i.request["model"].as_str())
+        .collect();
+    models.sort();
+    models.dedup();
+
+    json!({
+        "object": "list",
+        "data": models.iter().map(|m| json!({ "id": m, "object": "model", "owned_by": "cassette" })).collect::<Vec<_>>()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai_utils::{self, StreamEvent};
+    use crate::core_traits::ai::{AIProviderConfig, Content, Message};
+
+    fn provider(server: &MockServer) -> AIProviderConfig {
+        AIProviderConfig {
+            id: "cassette".to_string(),
+            name: "Cassette".to_string(),
+            api_key: "sk-test".to_string(),
+            base_url: server.chat_url(),
+            models: vec!["gpt-4o-mini".to_string()],
+            ..Default::default()
+        }
+    }
+
+    fn user(text: &str) -> Vec<Message> {
+        vec![Message { role: "user".to_string(), content: Content::Text(text.to_string()), tool_calls: None, tool_call_id: None, thinking_blocks: None }]
+    }
+
+    #[tokio::test]
+    async fn test_replay_completion_and_stream() {
+        let cassette = Cassette::default()
+            .with(Interaction::text("pong"))
+            .with(Interaction::stream_text(&["Hel", "lo"]));
+        let server = MockServer::replay(cassette).await.unwrap();
+        let config = provider(&server);
+
+        let reply = ai_utils::fetch_ai_completion(&config, user("ping"), None).await.unwrap();
+        assert!(matches!(reply.content, Content::Text(ref t) if t == "pong"));
+
+        let mut streamed = String::new();
+        ai_utils::stream_ai_completion(&config, user("hi"), None, |event| {
+            if let StreamEvent::Content(c) = event {
+                streamed.push_str(&c);
+            }
+        }).await.unwrap();
+        assert_eq!(streamed, "Hello");
+
+        assert_eq!(server.requests().len(), 2);
+        assert_eq!(server.unused_interactions(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_streamed_tool_calls_keep_model_order() {
+        let calls: Vec<(String, Value)> = (0..12)
+            .map(|i| (format!("call_{}", i), json!({ "rel_path": format!("file_{}.rs", i) })))
+            .collect();
+        let specs: Vec<(&str, &str, Value)> = calls.iter()
+            .map(|(id, args)| (id.as_str(), "agent_read_file", args.clone()))
+            .collect();
+        let server = MockServer::replay(Cassette::default().with(Interaction::stream_tool_calls(&specs))).await.unwrap();
+
+        let reply = ai_utils::stream_ai_completion(&provider(&server), user("read them"), None, |_| {}).await.unwrap();
+        let ids: Vec<String> = reply.tool_calls.unwrap().into_iter().map(|tc| tc.id).collect();
+        let expected: Vec<String> = calls.into_iter().map(|(id, _)| id).collect();
+        assert_eq!(ids, expected);
+    }
+
+    #[tokio::test]
+    async fn test_record_then_replay_file() {
+        let upstream = MockServer::replay(Cassette::default().with(Interaction::stream_tool_call(
+            "call_1", "agent_read_file", json!({ "rel_path": "src/main.rs" }),
+        ))).await.unwrap();
+
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("cassette.json");
+        let recorder = MockServer::record(&path, &upstream.chat_url()).await.unwrap();
+
+        let recorded = ai_utils::stream_ai_completion(&provider(&recorder), user("read main"), None, |_| {}).await.unwrap();
+        drop(recorder);
+
+        let replayer = MockServer::replay_file(&path).await.unwrap();
+        let replayed = ai_utils::stream_ai_completion(&provider(&replayer), user("read main"), None, |_| {}).await.unwrap();
+
+        let args = |m: &Message| m.tool_calls.as_ref().unwrap()[0].function.arguments.clone();
+        assert_eq!(args(&recorded), r#"{"rel_path":"src/main.rs"}"#);
+        assert_eq!(args(&replayed), args(&recorded));
+    }
+}
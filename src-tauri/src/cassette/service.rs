@@ -0,0 +1,76 @@
+//! `AIService` that sends every request through a [`MockServer`].
+
+use super::{Cassette, MockServer};
+use crate::community::BasicAIService;
+use crate::core_traits::ai::{AIProtocol, AIProviderConfig, AIService, Message, ModelRoute};
+use std::path::Path;
+
+pub struct CassetteAIService {
+    server: MockServer,
+    inner: BasicAIService,
+}
+
+impl CassetteAIService {
+    pub async fn replay(cassette: Cassette) -> Result<Self, String> {
+        Ok(Self { server: MockServer::replay(cassette).await?, inner: BasicAIService })
+    }
+
+    pub async fn replay_file(path: &Path) -> Result<Self, String> {
+        Ok(Self { server: MockServer::replay_file(path).await?, inner: BasicAIService })
+    }
+
+    /// Record real traffic to `upstream` (an OpenAI-compatible chat completions URL) into `path`
+    pub async fn record(path: &Path, upstream: &str) -> Result<Self, String> {
+        Ok(Self { server: MockServer::record(path, upstream).await?, inner: BasicAIService })
+    }
+
+    /// Set up from `IFAI_CASSETTE` / `IFAI_CASSETTE_UPSTREAM`, if present
+    pub async fn from_env() -> Option<Result<Self, String>> {
+        let path = std::env::var("IFAI_CASSETTE").ok()?;
+        let path = Path::new(&path);
+        Some(match std::env::var("IFAI_CASSETTE_UPSTREAM") {
+            Ok(upstream) => {
+                println!("[Cassette] Recording {} into {}", upstream, path.display());
+                Self::record(path, &upstream).await
+            }
+            Err(_) => {
+                println!("[Cassette] Replaying {}", path.display());
+                Self::replay_file(path).await
+            }
+        })
+    }
+
+    pub fn server(&self) -> &MockServer {
+        &self.server
+    }
+
+    /// Point the provider (and its fallbacks) at the mock server, which speaks the OpenAI protocol
+    fn redirect(&self, config: &AIProviderConfig) -> AIProviderConfig {
+        let mut redirected = config.clone();
+        redirected.base_url = self.server.chat_url();
+        redirected.protocol = AIProtocol::default();
+        redirected.fallback_providers = config.fallback_providers.iter().map(|p| self.redirect(p)).collect();
+        redirected
+    }
+}
+
+#[async_trait::async_trait]
+impl AIService for CassetteAIService {
+    async fn chat(&self, config: &AIProviderConfig, messages: Vec<Message>) -> Result<Message, String> {
+        self.inner.chat(&self.redirect(config), messages).await
+    }
+
+    async fn chat_routed(&self, config: &AIProviderConfig, messages: Vec<Message>) -> Result<(Message, ModelRoute), String> {
+        self.inner.chat_routed(&self.redirect(config), messages).await
+    }
+
+    async fn stream_chat(
+        &self,
+        config: &AIProviderConfig,
+        messages: Vec<Message>,
+        event_id: &str,
+        callback: Box<dyn Fn(String) + Send>,
+    ) -> Result<(), String> {
+        self.inner.stream_chat(&self.redirect(config), messages, event_id, callback).await
+    }
+}
@@ -1,7 +1,10 @@
 use tauri::State;
+use crate::AppState;
 use crate::agent_system::Supervisor;
 #[cfg(feature = "commercial")]
 use crate::agent_system::{AgentContext, runner};
+#[cfg(feature = "commercial")]
+use tauri::Emitter;
 use serde::Serialize;
 use std::collections::HashMap;
 use crate::core_traits::agent::AgentStatus;
@@ -17,16 +20,27 @@ pub struct AgentInfo {
 #[tauri::command]
 pub async fn launch_agent(
     app: tauri::AppHandle,
+    state: State<'_, AppState>,
     supervisor: State<'_, Supervisor>,
     id: String,
     agent_type: String,
     task: String,
     project_root: String,
     provider_config: AIProviderConfig,
+    model: Option<String>,
+    providers: Option<Vec<AIProviderConfig>>,
 ) -> Result<String, String> {
     #[cfg(feature = "commercial")]
     {
         println!("[AgentSystem] launch_agent called with id: {}, agent_type: {}", id, agent_type);
+        // Same IFAI.md provider/model overrides as chat
+        let (provider_config, settings) = crate::project_config::resolve_settings(
+            Some(&project_root),
+            provider_config,
+            &providers.unwrap_or_default(),
+            model.as_deref(),
+        );
+        let _ = app.emit(&format!("agent_{}_settings", id), &settings);
         supervisor.register_agent(id.clone(), agent_type.clone()).await;
 
         let context = AgentContext {
@@ -38,11 +52,12 @@ pub async fn launch_agent(
         };
 
         let supervisor_inner = supervisor.inner().clone();
+        let rag = state.rag_service.clone();
         let id_clone = id.clone();
         let agent_type_clone = agent_type.clone();
         
         tokio::spawn(async move {
-            runner::run_agent_task(app, supervisor_inner, id_clone, agent_type_clone, context).await;
+            runner::run_agent_task(app, supervisor_inner, rag, id_clone, agent_type_clone, context).await;
         });
         
         println!("[AgentSystem] Agent launched: {} ({})", id, agent_type);
@@ -1,5 +1,8 @@
 use crate::AppState;
 use crate::core_traits::rag::RagResult;
+use crate::events::IndexStatus;
+use crate::community::rag::symbols::{SymbolDefinition, SymbolReference};
+use crate::hybrid_search::{self, HybridHit};
 
 // For optimized directory scanning
 use walkdir::WalkDir;
@@ -13,6 +16,33 @@ pub async fn init_rag_index(
     state.rag_service.index_project(&root_path).await
 }
 
+/// Whether the project is indexed, how stale it is and where a running build is
+#[tauri::command]
+pub async fn rag_index_status(
+    state: tauri::State<'_, AppState>,
+    root_path: String
+) -> Result<IndexStatus, String> {
+    state.rag_service.index_status(&root_path).await
+}
+
+/// Stop the running index build; the index from before it stays in use. False when none is running.
+#[tauri::command]
+pub async fn cancel_rag_index(
+    state: tauri::State<'_, AppState>,
+    root_path: String
+) -> Result<bool, String> {
+    Ok(state.rag_service.cancel_index(&root_path))
+}
+
+/// Stop re-indexing a closed project as its files change. False when it wasn't being watched.
+#[tauri::command]
+pub async fn stop_rag_watch(
+    state: tauri::State<'_, AppState>,
+    root_path: String
+) -> Result<bool, String> {
+    Ok(state.rag_service.stop_watching(&root_path))
+}
+
 #[tauri::command]
 pub async fn search_semantic(
     state: tauri::State<'_, AppState>,
@@ -22,13 +52,37 @@ pub async fn search_semantic(
     state.rag_service.search(&query, limit).await
 }
 
+/// Text matches and semantic hits fused into one ranked list, each with the reasons it matched
 #[tauri::command]
 pub async fn search_hybrid(
     state: tauri::State<'_, AppState>,
+    root_path: String,
     query: String,
     limit: usize
-) -> Result<Vec<String>, String> {
-    state.rag_service.search(&query, limit).await
+) -> Result<Vec<HybridHit>, String> {
+    hybrid_search::hybrid_search(state.rag_service.as_ref(), &root_path, &query, limit).await
+}
+
+/// Workspace symbol search from the syntax-tree index; needs no language server
+#[tauri::command]
+pub async fn find_symbol(
+    state: tauri::State<'_, AppState>,
+    root_path: String,
+    query: String,
+    limit: usize
+) -> Result<Vec<SymbolDefinition>, String> {
+    state.rag_service.find_symbol(&root_path, &query, limit).await
+}
+
+/// Lines that mention a symbol's name
+#[tauri::command]
+pub async fn find_references(
+    state: tauri::State<'_, AppState>,
+    root_path: String,
+    name: String,
+    limit: usize
+) -> Result<Vec<SymbolReference>, String> {
+    state.rag_service.find_references(&root_path, &name, limit).await
 }
 
 #[tauri::command]
@@ -236,8 +290,8 @@ pub async fn agent_scan_directory(
 /// Scan directory recursively with progress callback
 /// Sends explore_progress events as each directory is scanned
 /// Uses walkdir for high performance
-pub async fn agent_scan_directory_with_progress(
-    app: &tauri::AppHandle,
+pub async fn agent_scan_directory_with_progress<R: tauri::Runtime>(
+    app: &tauri::AppHandle<R>,
     event_id: &str,
     root_path: String,
     rel_path: String,
@@ -248,14 +302,10 @@ pub async fn agent_scan_directory_with_progress(
     use serde_json::json;
     use std::path::Path;
     use std::collections::HashMap;
-    use tauri::Emitter;
-
-    #[derive(Clone, serde::Serialize)]
-    struct ScanStatus {
-        total: usize,
-        scanned: usize,
-        status: String,
-    }
+    use crate::events::{
+        self, AgentEventKind, DirectoryScan, DirectoryScanStatus, ExploreProgress, ExploreStage,
+        ScanProgress,
+    };
 
     let base_path = Path::new(&root_path).join(&rel_path);
     let max_files = max_files.unwrap_or(500);
@@ -296,7 +346,7 @@ pub async fn agent_scan_directory_with_progress(
     // STEP 2: Scan with progress events and manual filtering
     let mut files: Vec<String> = Vec::new();
     let mut directories: Vec<String> = Vec::new();
-    let mut by_directory: HashMap<String, ScanStatus> = HashMap::new();
+    let mut by_directory: HashMap<String, DirectoryScan> = HashMap::new();
     let mut dirs_scanned = 0;
     let mut current_dir_path: Option<String> = None;
 
@@ -341,10 +391,10 @@ pub async fn agent_scan_directory_with_progress(
                 directories.push(full_rel.clone());
             }
 
-            by_directory.entry(full_rel.clone()).or_insert_with(|| ScanStatus {
+            by_directory.entry(full_rel.clone()).or_insert_with(|| DirectoryScan {
                 total: total_estimate,
                 scanned: dirs_scanned,
-                status: "scanning".to_string(),
+                status: DirectoryScanStatus::Scanning,
             });
 
             continue;
@@ -353,19 +403,19 @@ pub async fn agent_scan_directory_with_progress(
         // Check if we entered a new directory (for files)
         if current_dir_path.as_deref() != Some(file_dir) {
             if let Some(prev_dir) = &current_dir_path {
-                by_directory.insert(prev_dir.clone(), ScanStatus {
+                by_directory.insert(prev_dir.clone(), DirectoryScan {
                     total: total_estimate,
                     scanned: dirs_scanned,
-                    status: "completed".to_string(),
+                    status: DirectoryScanStatus::Completed,
                 });
 
                 dirs_scanned += 1;
             }
 
-            by_directory.insert(file_dir.to_string(), ScanStatus {
+            by_directory.insert(file_dir.to_string(), DirectoryScan {
                 total: total_estimate,
                 scanned: dirs_scanned,
-                status: "scanning".to_string(),
+                status: DirectoryScanStatus::Scanning,
             });
 
             current_dir_path = Some(file_dir.to_string());
@@ -376,31 +426,18 @@ pub async fn agent_scan_directory_with_progress(
             files.push(full_rel.clone());
 
             // Emit per-file progress
-            let by_dir_serializable: HashMap<String, serde_json::Value> = by_directory
-                .iter()
-                .map(|(k, v)| {
-                    (k.clone(), json!({
-                        "total": v.total,
-                        "scanned": v.scanned,
-                        "status": v.status
-                    }))
-                })
-                .collect();
-
-            let progress = json!({
-                "type": "explore_progress",
-                "exploreProgress": {
-                    "phase": "scanning",
-                    "currentPath": file_dir,
-                    "currentFile": &full_rel,
-                    "progress": {
-                        "total": total_estimate,
-                        "scanned": dirs_scanned,
-                        "byDirectory": by_dir_serializable
-                    }
-                }
+            events::emit_agent(app, event_id, AgentEventKind::ExploreProgress {
+                explore_progress: ExploreProgress {
+                    phase: ExploreStage::Scanning,
+                    current_path: Some(file_dir.to_string()),
+                    current_file: Some(full_rel.clone()),
+                    progress: ScanProgress {
+                        total: total_estimate,
+                        scanned: dirs_scanned,
+                        by_directory: by_directory.clone(),
+                    },
+                },
             });
-            let _ = app.emit(event_id, progress);
         }
 
         if files.len() >= max_files {
@@ -411,10 +448,10 @@ pub async fn agent_scan_directory_with_progress(
 
     // Mark final directory as completed
     if let Some(last_dir) = &current_dir_path {
-        by_directory.insert(last_dir.clone(), ScanStatus {
+        by_directory.insert(last_dir.clone(), DirectoryScan {
             total: total_estimate,
             scanned: dirs_scanned + 1,
-            status: "completed".to_string(),
+            status: DirectoryScanStatus::Completed,
         });
     }
 
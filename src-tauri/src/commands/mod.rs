@@ -1,3 +1,4 @@
 pub mod prompt_commands;
 pub mod agent_commands;
-pub mod core_wrappers;
\ No newline at end of file
+pub mod core_wrappers;
+pub mod provider_commands;
\ No newline at end of file
@@ -0,0 +1,17 @@
+use crate::ai_utils::discovery::{self, ConnectionTestResult, ModelInfo};
+use crate::core_traits::ai::AIProviderConfig;
+
+/// List the models the provider serves, with their capabilities
+#[tauri::command]
+pub async fn list_provider_models(provider_config: AIProviderConfig) -> Result<Vec<ModelInfo>, String> {
+    println!("[Provider] Listing models for {}", provider_config.id);
+    discovery::list_models(&provider_config).await
+}
+
+/// Check that the provider is reachable and the API key is accepted
+#[tauri::command]
+pub async fn test_provider_connection(provider_config: AIProviderConfig) -> Result<ConnectionTestResult, String> {
+    let result = discovery::test_connection(&provider_config).await;
+    println!("[Provider] Connection test for {}: ok={} ({}ms)", provider_config.id, result.ok, result.latency_ms);
+    Ok(result)
+}
@@ -6,6 +6,14 @@ pub mod impls {
     use ifainew_core;
     use tauri::{AppHandle, Manager};
 
+    /// The core has its own copies of the message and provider types with only the base
+    /// fields; routing, HTTP settings and thinking blocks are handled on this side, so
+    /// the extra fields are dropped here.
+    fn to_core<T: serde::Serialize, U: serde::de::DeserializeOwned>(value: &T) -> Result<U, String> {
+        let json = serde_json::to_value(value).map_err(|e| e.to_string())?;
+        serde_json::from_value(json).map_err(|e| e.to_string())
+    }
+
     pub struct CommercialAIService {
         pub app: AppHandle,
     }
@@ -29,11 +37,10 @@ pub mod impls {
             event_id: &str,
             _callback: Box<dyn Fn(String) + Send>,
         ) -> Result<(), String> {
-            // 直接透传，无需序列化转换
             ifainew_core::ai::stream_chat(
                 self.app.clone(),
-                config.clone(),
-                messages,
+                to_core(config)?,
+                to_core(&messages)?,
                 event_id.to_string(),
                 true
             ).await
@@ -1,7 +1,9 @@
-use crate::core_traits::ai::{AIService, AIProviderConfig, Message};
-use crate::core_traits::rag::RagService;
+pub mod rag;
+
+use crate::core_traits::ai::{AIService, AIProviderConfig, Message, ModelRoute};
 use crate::core_traits::agent::AgentService;
-use crate::ai_utils;
+use crate::ai_utils::{self, StreamEvent};
+use crate::events::{ChatEvent, ChatEventKind, ToolCallDelta};
 
 pub struct BasicAIService;
 
@@ -15,50 +17,55 @@ impl AIService for BasicAIService {
         ai_utils::fetch_ai_completion(config, messages, None).await
     }
 
+    async fn chat_routed(
+        &self,
+        config: &AIProviderConfig,
+        messages: Vec<Message>,
+    ) -> Result<(Message, ModelRoute), String> {
+        ai_utils::fetch_ai_completion_routed(config, messages, None).await
+    }
+
     async fn stream_chat(
         &self,
         config: &AIProviderConfig,
         messages: Vec<Message>,
-        _event_id: &str,
+        event_id: &str,
         callback: Box<dyn Fn(String) + Send>,
     ) -> Result<(), String> {
-        // Simple implementation that calls fetch_ai_completion and calls callback with full content
-        // This simulates streaming by sending the whole content at once
-        // TODO: Implement true streaming in ai_utils for community edition
-        match ai_utils::fetch_ai_completion(config, messages, None).await {
-            Ok(msg) => {
-                match msg.content {
-                    crate::core_traits::ai::Content::Text(text) => {
-                        callback(text);
-                    }
-                    _ => {}
+        let event_id = event_id.to_string();
+        // Shared with the stream so the turn's thinking blocks can be sent after it ends
+        let callback = std::sync::Arc::new(std::sync::Mutex::new(callback));
+        let on_event = callback.clone();
+        let message = ai_utils::stream_ai_completion(config, messages, None, move |event| {
+            let kind = match event {
+                StreamEvent::Content(content) => ChatEventKind::Content { content },
+                StreamEvent::Reasoning(content) => ChatEventKind::Reasoning { content },
+                StreamEvent::ToolCall { index, id, name, arguments_delta, .. } => {
+                    // The frontend merges fragments by id, so every fragment must carry one
+                    let id = if id.is_empty() { format!("{}_{}", event_id, index) } else { id };
+                    ChatEventKind::ToolCall { tool_call: ToolCallDelta::function(id, name, arguments_delta) }
                 }
-                Ok(())
-            }
-            Err(e) => Err(e),
+                StreamEvent::Warning(message) => ChatEventKind::Warning { message },
+                StreamEvent::Retry(notice) => ChatEventKind::retry(notice),
+                StreamEvent::Fallback(notice) => ChatEventKind::fallback(notice),
+                StreamEvent::Done { usage, provider_id, model } => ChatEventKind::Done {
+                    usage,
+                    provider: provider_id,
+                    model,
+                },
+            };
+            (on_event.lock().unwrap())(ChatEvent::new(kind).to_payload());
+        })
+        .await?;
+
+        if let Some(thinking_blocks) = message.thinking_blocks {
+            (callback.lock().unwrap())(ChatEvent::new(ChatEventKind::ThinkingBlocks { thinking_blocks }).to_payload());
         }
+        Ok(())
     }
 }
 
-pub struct CommunityRagService;
-
-#[async_trait::async_trait]
-impl RagService for CommunityRagService {
-    async fn index_project(&self, _root: &str) -> Result<(), String> {
-        Err("RAG indexing is available in Commercial Edition.".to_string())
-    }
-
-    async fn search(&self, _query: &str, _top_k: usize) -> Result<Vec<String>, String> {
-        Ok(vec![])
-    }
-
-    async fn retrieve_context(&self, _query: &str, _root: &str) -> Result<crate::core_traits::rag::RagResult, String> {
-        Ok(crate::core_traits::rag::RagResult {
-            context: String::new(),
-            references: vec![],
-        })
-    }
-}
+pub use rag::CommunityRagService;
 
 pub struct CommunityAgentService;
 
@@ -0,0 +1,207 @@
+//! BM25 keyword index over code chunks.
+
+use super::chunker::{Chunk, FileStamp};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Bump when the on-disk layout or tokenization changes; older indexes are rebuilt
+pub const INDEX_VERSION: u32 = 2;
+
+const K1: f32 = 1.2;
+const B: f32 = 0.75;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Bm25Index {
+    pub version: u32,
+    pub chunks: Vec<Chunk>,
+    pub files: HashMap<String, FileStamp>,
+    /// term -> (chunk index, term frequency)
+    postings: HashMap<String, Vec<(u32, u32)>>,
+    chunk_lens: Vec<u32>,
+    avg_len: f32,
+}
+
+impl Bm25Index {
+    pub fn build(chunks: Vec<Chunk>, files: HashMap<String, FileStamp>) -> Self {
+        let mut postings: HashMap<String, Vec<(u32, u32)>> = HashMap::new();
+        let mut chunk_lens = Vec::with_capacity(chunks.len());
+
+        for (id, chunk) in chunks.iter().enumerate() {
+            let terms = tokenize(&format!("{}\n{}", chunk.file_path, chunk.content));
+            chunk_lens.push(terms.len() as u32);
+
+            let mut tf: HashMap<String, u32> = HashMap::new();
+            for term in terms {
+                *tf.entry(term).or_default() += 1;
+            }
+            for (term, count) in tf {
+                postings.entry(term).or_default().push((id as u32, count));
+            }
+        }
+
+        let avg_len = if chunk_lens.is_empty() {
+            0.0
+        } else {
+            chunk_lens.iter().map(|l| *l as f32).sum::<f32>() / chunk_lens.len() as f32
+        };
+
+        Self { version: INDEX_VERSION, chunks, files, postings, chunk_lens, avg_len }
+    }
+
+    /// Best-scoring chunks for `query`, highest first
+    pub fn search(&self, query: &str, top_k: usize) -> Vec<(Chunk, f32)> {
+        let n = self.chunks.len() as f32;
+        let mut scores: HashMap<u32, f32> = HashMap::new();
+
+        let mut terms = tokenize(query);
+        terms.sort();
+        terms.dedup();
+        for term in terms {
+            let Some(postings) = self.postings.get(&term) else { continue };
+            let df = postings.len() as f32;
+            let idf = (1.0 + (n - df + 0.5) / (df + 0.5)).ln();
+            for (id, tf) in postings {
+                let tf = *tf as f32;
+                let len = self.chunk_lens[*id as usize] as f32;
+                let norm = tf * (K1 + 1.0) / (tf + K1 * (1.0 - B + B * len / self.avg_len.max(1.0)));
+                *scores.entry(*id).or_default() += idf * norm;
+            }
+        }
+
+        let mut ranked: Vec<(u32, f32)> = scores.into_iter().collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        ranked.into_iter()
+            .take(top_k)
+            .map(|(id, score)| (self.chunks[id as usize].clone(), score))
+            .collect()
+    }
+
+    pub fn load(path: &Path) -> Option<Self> {
+        let bytes = fs::read(path).ok()?;
+        let (index, _): (Self, usize) = bincode::serde::decode_from_slice(&bytes, bincode::config::standard()).ok()?;
+        (index.version == INDEX_VERSION).then_some(index)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        let bytes = bincode::serde::encode_to_vec(self, bincode::config::standard()).map_err(|e| e.to_string())?;
+        // Write to a temp file first so a crash never leaves a truncated index behind
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, bytes).map_err(|e| e.to_string())?;
+        fs::rename(&tmp, path).map_err(|e| e.to_string())
+    }
+}
+
+/// Lowercased terms. Identifiers are kept whole and also split on camelCase and `_`,
+/// so `parseConfig` matches queries for "parseconfig", "parse" and "config".
+/// CJK text has no spaces, so each CJK character is its own term.
+pub fn tokenize(text: &str) -> Vec<String> {
+    let mut terms = Vec::new();
+    for word in text.split(|c: char| !(c.is_alphanumeric() || c == '_')) {
+        if word.is_empty() {
+            continue;
+        }
+        if word.chars().any(is_cjk) {
+            let mut run = String::new();
+            for c in word.chars() {
+                if is_cjk(c) {
+                    push_identifier(&run, &mut terms);
+                    run.clear();
+                    terms.push(c.to_string());
+                } else {
+                    run.push(c);
+                }
+            }
+            push_identifier(&run, &mut terms);
+        } else {
+            push_identifier(word, &mut terms);
+        }
+    }
+    terms
+}
+
+fn push_identifier(word: &str, terms: &mut Vec<String>) {
+    let parts = split_identifier(word);
+    let whole = word.trim_matches('_').to_lowercase();
+    if whole.chars().count() < 2 {
+        return;
+    }
+    if parts.len() > 1 {
+        terms.extend(parts.into_iter().filter(|p| p.chars().count() > 1));
+    }
+    terms.push(whole);
+}
+
+fn split_identifier(word: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let chars: Vec<char> = word.chars().collect();
+    for (i, &c) in chars.iter().enumerate() {
+        if c == '_' {
+            if !current.is_empty() {
+                parts.push(std::mem::take(&mut current).to_lowercase());
+            }
+            continue;
+        }
+        // Break before an uppercase letter that follows a lowercase one (parseConfig),
+        // or that starts a word after an acronym (HTTPServer -> http, server)
+        let boundary = c.is_uppercase() && i > 0 && !current.is_empty() && (
+            chars[i - 1].is_lowercase()
+                || chars[i - 1].is_ascii_digit()
+                || chars.get(i + 1).is_some_and(|n| n.is_lowercase())
+        );
+        if boundary {
+            parts.push(std::mem::take(&mut current).to_lowercase());
+        }
+        current.push(c);
+    }
+    if !current.is_empty() {
+        parts.push(current.to_lowercase());
+    }
+    parts
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32, 0x3040..=0x30FF | 0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xAC00..=0xD7AF | 0xF900..=0xFAFF)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(path: &str, content: &str) -> Chunk {
+        Chunk { file_path: path.to_string(), line_start: 1, line_end: 1, content: content.to_string(), ..Default::default() }
+    }
+
+    #[test]
+    fn test_tokenize_splits_identifiers() {
+        let terms = tokenize("fn parseConfig(HTTPServer, max_retries) 配置");
+        for expected in ["parseconfig", "parse", "config", "httpserver", "http", "server", "max_retries", "max", "retries", "配", "置"] {
+            assert!(terms.contains(&expected.to_string()), "missing {}", expected);
+        }
+    }
+
+    #[test]
+    fn test_search_ranks_matching_chunk_first() {
+        let index = Bm25Index::build(vec![
+            chunk("src/ui.ts", "render the sidebar and toolbar"),
+            chunk("src/config.rs", "pub fn load_config(path: &Path) -> Config { parse the config file }"),
+            chunk("src/net.rs", "open a socket and send bytes"),
+        ], HashMap::new());
+
+        let hits = index.search("where is the config loaded", 2);
+        assert_eq!(hits[0].0.file_path, "src/config.rs");
+        assert!(index.search("nonexistent_term", 5).is_empty());
+
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let path = dir.join("bm25.bin");
+        index.save(&path).unwrap();
+        let loaded = Bm25Index::load(&path).unwrap();
+        assert_eq!(loaded.search("config", 1)[0].0.file_path, "src/config.rs");
+    }
+}
@@ -0,0 +1,170 @@
+//! Splits project files into line-addressed chunks for indexing.
+//!
+//! Source files in languages with a tree-sitter grammar are split on definitions
+//! (see [`super::syntax`]); everything else goes through the text splitter.
+
+use ignore::WalkBuilder;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use text_splitter::{ChunkConfig, TextSplitter};
+use super::syntax;
+
+/// Target chunk size in characters; the splitter prefers semantic boundaries inside this range
+const CHUNK_CAPACITY: std::ops::Range<usize> = 600..1500;
+const CHUNK_OVERLAP: usize = 200;
+const MAX_FILE_BYTES: u64 = 512 * 1024;
+const MAX_DEPTH: usize = 12;
+
+/// Generated files that are large, noisy and rarely what a question is about
+const SKIPPED_FILES: [&str; 5] = ["Cargo.lock", "package-lock.json", "yarn.lock", "pnpm-lock.yaml", "bun.lockb"];
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Chunk {
+    /// Path relative to the project root, always with `/` separators
+    pub file_path: String,
+    /// 1-based, inclusive
+    pub line_start: usize,
+    pub line_end: usize,
+    pub content: String,
+    /// Name of the function, type or impl the chunk covers, for syntax-aware chunks
+    pub symbol: Option<String>,
+    /// `function`, `method`, `struct`, `impl`, `class`, ...
+    pub kind: Option<String>,
+    /// Enclosing impl, class or module, e.g. `IndexStore` for its methods
+    pub scope: Option<String>,
+    pub language: Option<String>,
+}
+
+impl Chunk {
+    /// Symbol with its scope, e.g. `IndexStore::refresh` or `Store.get`
+    pub fn qualified_symbol(&self) -> Option<String> {
+        let symbol = self.symbol.as_ref()?;
+        Some(match &self.scope {
+            Some(scope) => format!("{}{}{}", scope, syntax::scope_separator(self.language.as_deref()), symbol),
+            None => symbol.clone(),
+        })
+    }
+}
+
+/// Size and modification time, used to skip re-chunking unchanged files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileStamp {
+    pub len: u64,
+    pub modified_secs: u64,
+}
+
+/// Walk `root` honoring .gitignore and skipping hidden entries
+fn walk(root: &Path) -> ignore::Walk {
+    WalkBuilder::new(root)
+        .standard_filters(true)
+        .hidden(true)
+        .max_depth(Some(MAX_DEPTH))
+        .build()
+}
+
+/// Indexable files under `root` with their stamps, honoring .gitignore and skipping hidden files
+pub fn scan_files(root: &Path) -> Vec<(String, FileStamp)> {
+    walk(root)
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_some_and(|ft| ft.is_file()))
+        .filter(|e| !SKIPPED_FILES.contains(&e.file_name().to_string_lossy().as_ref()))
+        .filter_map(|e| {
+            let metadata = e.metadata().ok()?;
+            if metadata.len() == 0 || metadata.len() > MAX_FILE_BYTES {
+                return None;
+            }
+            let modified_secs = metadata.modified().ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            let rel = e.path().strip_prefix(root).ok()?.to_string_lossy().replace('\\', "/");
+            Some((rel, FileStamp { len: metadata.len(), modified_secs }))
+        })
+        .collect()
+}
+
+/// `root` and the directories below it that a scan would enter
+pub fn scan_dirs(root: &Path) -> Vec<PathBuf> {
+    walk(root)
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_some_and(|ft| ft.is_dir()))
+        .map(|e| e.into_path())
+        .collect()
+}
+
+/// File contents as text, or None for unreadable, binary and non-UTF-8 files
+pub fn read_text(root: &Path, rel_path: &str) -> Option<String> {
+    let bytes = fs::read(root.join(rel_path)).ok()?;
+    if bytes.iter().take(8192).any(|b| *b == 0) {
+        return None;
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Read and chunk one file. Binary and non-UTF-8 files yield no chunks.
+pub fn chunk_file(root: &Path, rel_path: &str) -> Vec<Chunk> {
+    read_text(root, rel_path)
+        .map(|text| chunk_text(rel_path, &text))
+        .unwrap_or_default()
+}
+
+pub fn chunk_text(rel_path: &str, text: &str) -> Vec<Chunk> {
+    syntax::chunk_source(rel_path, text).unwrap_or_else(|| split_text(rel_path, text, 1))
+}
+
+/// Split with the text splitter. Line numbers start at `first_line`.
+pub fn split_text(rel_path: &str, text: &str, first_line: usize) -> Vec<Chunk> {
+    let config = ChunkConfig::new(CHUNK_CAPACITY)
+        .with_overlap(CHUNK_OVERLAP)
+        .expect("overlap is smaller than the chunk capacity");
+    let splitter = TextSplitter::new(config);
+
+    // Offsets arrive in order, so line numbers are counted incrementally
+    let language = syntax::language_name(rel_path).map(str::to_string);
+    let mut line = first_line;
+    let mut counted_to = 0;
+    splitter
+        .chunk_indices(text)
+        .map(|(offset, content)| {
+            if offset >= counted_to {
+                line += text[counted_to..offset].matches('\n').count();
+            } else {
+                // Overlapping chunks start before the end of the previous one
+                line -= text[offset..counted_to].matches('\n').count();
+            }
+            counted_to = offset;
+            let line_end = line + content.trim_end_matches('\n').matches('\n').count();
+            Chunk {
+                file_path: rel_path.to_string(),
+                line_start: line,
+                line_end,
+                content: content.to_string(),
+                language: language.clone(),
+                ..Default::default()
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunks_carry_line_numbers() {
+        let text = (1..=200).map(|i| format!("fn line_{}() {{}}\n", i)).collect::<String>();
+        let chunks = split_text("src/lib.rs", &text, 1);
+
+        assert!(chunks.len() > 1);
+        assert_eq!(chunks[0].line_start, 1);
+        for chunk in &chunks {
+            let first = chunk.content.lines().next().unwrap();
+            assert_eq!(first, format!("fn line_{}() {{}}", chunk.line_start));
+            let last = chunk.content.lines().last().unwrap();
+            assert_eq!(last, format!("fn line_{}() {{}}", chunk.line_end));
+        }
+        assert_eq!(chunks.last().unwrap().line_end, 200);
+    }
+}
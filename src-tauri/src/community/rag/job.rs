@@ -0,0 +1,100 @@
+//! One run of the indexer: the phase it is in, how many files of that phase are done,
+//! and a flag that stops it at the next file.
+
+use crate::events::{IndexPhase, IndexProgressEvent, PROTOCOL_VERSION};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Per-file progress events closer together than this are skipped
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+pub const CANCELLED: &str = "Indexing cancelled";
+
+pub type ProgressListener = Arc<dyn Fn(IndexProgressEvent) + Send + Sync>;
+
+struct Progress {
+    phase: IndexPhase,
+    processed: usize,
+    total: usize,
+    file: Option<String>,
+    last_sent: Option<Instant>,
+}
+
+pub struct IndexJob {
+    root: String,
+    cancelled: AtomicBool,
+    progress: Mutex<Progress>,
+    listener: Option<ProgressListener>,
+}
+
+impl IndexJob {
+    pub fn new(root: &str, listener: Option<ProgressListener>) -> Self {
+        Self {
+            root: root.to_string(),
+            cancelled: AtomicBool::new(false),
+            progress: Mutex::new(Progress { phase: IndexPhase::Scan, processed: 0, total: 0, file: None, last_sent: None }),
+            listener,
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Err once the job is cancelled. Checked between files, so a cancel takes effect within one file.
+    pub fn check(&self) -> Result<(), String> {
+        if self.is_cancelled() { Err(CANCELLED.to_string()) } else { Ok(()) }
+    }
+
+    pub fn start_phase(&self, phase: IndexPhase, total: usize) {
+        let event = {
+            let mut progress = self.progress.lock().unwrap();
+            *progress = Progress { phase, processed: 0, total, file: None, last_sent: Some(Instant::now()) };
+            self.event(&progress)
+        };
+        self.send(event);
+    }
+
+    /// Count one file of the current phase as done
+    pub fn advance(&self, file: &str) {
+        let event = {
+            let mut progress = self.progress.lock().unwrap();
+            progress.processed += 1;
+            progress.file = Some(file.to_string());
+            let due = progress.last_sent.is_none_or(|sent| sent.elapsed() >= PROGRESS_INTERVAL);
+            if !due && progress.processed < progress.total {
+                return;
+            }
+            progress.last_sent = Some(Instant::now());
+            self.event(&progress)
+        };
+        self.send(event);
+    }
+
+    /// Where the job is now, for status queries
+    pub fn snapshot(&self) -> IndexProgressEvent {
+        self.event(&self.progress.lock().unwrap())
+    }
+
+    fn event(&self, progress: &Progress) -> IndexProgressEvent {
+        IndexProgressEvent {
+            v: PROTOCOL_VERSION,
+            root: self.root.clone(),
+            phase: progress.phase,
+            processed: progress.processed,
+            total: progress.total,
+            file: progress.file.clone(),
+        }
+    }
+
+    fn send(&self, event: IndexProgressEvent) {
+        if let Some(listener) = &self.listener {
+            listener(event);
+        }
+    }
+}
@@ -0,0 +1,751 @@
+//! Offline RAG for the community edition, persisted under `.ifai/index`:
+//! a BM25 keyword index that needs no network access, plus a local semantic index
+//! whose embedding model is downloaded once on first use, and a symbol index of definitions
+//! and references. Indexed projects are watched and re-indexed incrementally as files change.
+//!
+//! A build runs in phases (scan, chunk, embed, persist), reports per-file progress and can be
+//! cancelled; nothing is written until the last phase, so a cancelled build leaves the previous index.
+
+pub mod bm25;
+pub mod chunker;
+pub mod job;
+pub mod semantic;
+pub mod symbols;
+pub mod syntax;
+pub mod watcher;
+
+use crate::core_traits::rag::{RagReference, RagResult, RagService};
+use crate::events::{IndexPhase, IndexProgressEvent, IndexState, IndexStatus, IndexStatusEvent, PROTOCOL_VERSION};
+use bm25::Bm25Index;
+use chunker::{Chunk, FileStamp};
+use job::{IndexJob, ProgressListener};
+use semantic::{Embedder, FastEmbedder, SemanticIndex};
+use symbols::{SymbolDefinition, SymbolIndex, SymbolReference};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::UNIX_EPOCH;
+use watcher::ProjectWatcher;
+
+const RETRIEVE_TOP_K: usize = 8;
+
+pub fn index_dir(project_root: &str) -> PathBuf {
+    Path::new(project_root).join(".ifai").join("index")
+}
+
+fn bm25_path(project_root: &str) -> PathBuf {
+    index_dir(project_root).join("bm25.bin")
+}
+
+fn vectors_path(project_root: &str) -> PathBuf {
+    index_dir(project_root).join("vectors.bin")
+}
+
+/// Files added, modified or removed since the previous index
+#[derive(Debug, Default, Clone)]
+pub struct IndexDelta {
+    /// Added or modified
+    pub changed: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl IndexDelta {
+    pub fn is_empty(&self) -> bool {
+        self.changed.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Scan and chunk `root`, reusing chunks from `previous` for files whose size and mtime are unchanged
+pub fn build_index(root: &str, previous: Option<&Bm25Index>, job: &IndexJob) -> Result<(Bm25Index, IndexDelta), String> {
+    let root_path = Path::new(root);
+    job.start_phase(IndexPhase::Scan, 0);
+    let files = chunker::scan_files(root_path);
+    let is_unchanged = |rel_path: &String, stamp: &FileStamp| previous.is_some_and(|p| p.files.get(rel_path) == Some(stamp));
+    job.start_phase(IndexPhase::Chunk, files.iter().filter(|(path, stamp)| !is_unchanged(path, stamp)).count());
+
+    let mut reused: HashMap<&str, Vec<&Chunk>> = HashMap::new();
+    if let Some(previous) = previous {
+        for chunk in &previous.chunks {
+            reused.entry(chunk.file_path.as_str()).or_default().push(chunk);
+        }
+    }
+
+    let mut chunks = Vec::new();
+    let mut delta = IndexDelta::default();
+    for (rel_path, stamp) in &files {
+        if is_unchanged(rel_path, stamp) {
+            // Unchanged files without chunks (binary, non-UTF-8) stay skipped
+            if let Some(old) = reused.get(rel_path.as_str()) {
+                chunks.extend(old.iter().map(|c| (*c).clone()));
+            }
+        } else {
+            job.check()?;
+            delta.changed.push(rel_path.clone());
+            chunks.extend(chunker::chunk_file(root_path, rel_path));
+            job.advance(rel_path);
+        }
+    }
+    let files: HashMap<String, FileStamp> = files.into_iter().collect();
+    if let Some(previous) = previous {
+        delta.removed = previous.files.keys().filter(|path| !files.contains_key(*path)).cloned().collect();
+    }
+
+    println!("[RAG] Indexed {} files ({} re-chunked, {} removed), {} chunks",
+        files.len(), delta.changed.len(), delta.removed.len(), chunks.len());
+    Ok((Bm25Index::build(chunks, files), delta))
+}
+
+/// Files added, modified or removed since `indexed` was built, relative to `root`
+pub fn stale_files(root: &str, indexed: &HashMap<String, FileStamp>) -> HashSet<String> {
+    let files: HashMap<String, FileStamp> = chunker::scan_files(Path::new(root)).into_iter().collect();
+    let changed = files.iter().filter(|(path, stamp)| indexed.get(*path) != Some(*stamp)).map(|(path, _)| path.clone());
+    let removed = indexed.keys().filter(|path| !files.contains_key(*path)).cloned();
+    changed.chain(removed).collect()
+}
+
+/// Total size of the files in `dir`
+fn dir_size(dir: &Path) -> u64 {
+    std::fs::read_dir(dir)
+        .map(|entries| entries.filter_map(|e| e.ok()?.metadata().ok()).filter(|m| m.is_file()).map(|m| m.len()).sum())
+        .unwrap_or(0)
+}
+
+pub type StatusListener = Arc<dyn Fn(IndexStatusEvent) + Send + Sync>;
+
+/// Loaded indexes by project root, shared between the service and its watchers
+pub struct IndexStore {
+    keyword: RwLock<HashMap<String, Arc<Bm25Index>>>,
+    semantic: RwLock<HashMap<String, Arc<SemanticIndex>>>,
+    symbols: RwLock<HashMap<String, Arc<SymbolIndex>>>,
+    /// Created on first use, since loading the model may download it
+    embedder: Mutex<Option<Arc<dyn Embedder>>>,
+    /// Serializes rebuilds so a watcher and an explicit re-index never write the same files at once
+    build_lock: tokio::sync::Mutex<()>,
+    /// The running build by project root, for progress queries and cancellation
+    jobs: Mutex<HashMap<String, Arc<IndexJob>>>,
+    /// How the last build of each root ended, when it failed or was cancelled
+    outcomes: Mutex<HashMap<String, (IndexState, Option<String>)>>,
+    /// Paths changed since the last build, by root. Kept by the watcher so status queries don't
+    /// rescan the project; an index loaded from disk is compared against the files once.
+    stale: Mutex<HashMap<String, HashSet<String>>>,
+    status_listener: Option<StatusListener>,
+    progress_listener: Option<ProgressListener>,
+}
+
+impl IndexStore {
+    fn new() -> Self {
+        Self {
+            keyword: RwLock::new(HashMap::new()),
+            semantic: RwLock::new(HashMap::new()),
+            symbols: RwLock::new(HashMap::new()),
+            embedder: Mutex::new(None),
+            build_lock: tokio::sync::Mutex::new(()),
+            jobs: Mutex::new(HashMap::new()),
+            outcomes: Mutex::new(HashMap::new()),
+            stale: Mutex::new(HashMap::new()),
+            status_listener: None,
+            progress_listener: None,
+        }
+    }
+
+    /// Record paths the watcher saw change and report the index as stale
+    pub fn mark_stale(&self, root: &str, paths: &HashSet<PathBuf>) {
+        let pending = {
+            let mut stale = self.stale.lock().unwrap();
+            let changed = stale.entry(root.to_string()).or_default();
+            changed.extend(paths.iter()
+                .filter_map(|p| p.strip_prefix(root).ok())
+                .map(|p| p.to_string_lossy().replace('\\', "/")));
+            changed.len()
+        };
+        self.emit_status(root, IndexState::Stale, pending, None);
+    }
+
+    pub fn emit_status(&self, root: &str, state: IndexState, pending_files: usize, error: Option<String>) {
+        if let Some(listener) = &self.status_listener {
+            listener(IndexStatusEvent { v: PROTOCOL_VERSION, root: root.to_string(), state, pending_files, error });
+        }
+    }
+
+    async fn embedder(&self) -> Result<Arc<dyn Embedder>, String> {
+        if let Some(embedder) = self.embedder.lock().unwrap().clone() {
+            return Ok(embedder);
+        }
+        let embedder: Arc<dyn Embedder> = Arc::new(
+            tokio::task::spawn_blocking(FastEmbedder::new)
+                .await
+                .map_err(|e| format!("Task join error: {}", e))??,
+        );
+        *self.embedder.lock().unwrap() = Some(embedder.clone());
+        Ok(embedder)
+    }
+
+    /// The in-memory keyword index, else the persisted one
+    async fn load_keyword(&self, root: &str) -> Result<Option<Arc<Bm25Index>>, String> {
+        if let Some(index) = self.keyword.read().unwrap().get(root).cloned() {
+            return Ok(Some(index));
+        }
+        let path = bm25_path(root);
+        let loaded = tokio::task::spawn_blocking(move || Bm25Index::load(&path))
+            .await
+            .map_err(|e| format!("Task join error: {}", e))?
+            .map(Arc::new);
+        if let Some(index) = &loaded {
+            self.keyword.write().unwrap().insert(root.to_string(), index.clone());
+        }
+        Ok(loaded)
+    }
+
+    /// The in-memory semantic index, else the persisted one
+    async fn load_semantic(&self, root: &str) -> Result<Option<Arc<SemanticIndex>>, String> {
+        if let Some(index) = self.semantic.read().unwrap().get(root).cloned() {
+            return Ok(Some(index));
+        }
+        let path = vectors_path(root);
+        let loaded = tokio::task::spawn_blocking(move || SemanticIndex::load(&path))
+            .await
+            .map_err(|e| format!("Task join error: {}", e))?
+            .map(Arc::new);
+        if let Some(index) = &loaded {
+            self.semantic.write().unwrap().insert(root.to_string(), index.clone());
+        }
+        Ok(loaded)
+    }
+
+    /// The in-memory symbol index, else the persisted one
+    async fn load_symbols(&self, root: &str) -> Result<Option<Arc<SymbolIndex>>, String> {
+        if let Some(index) = self.symbols.read().unwrap().get(root).cloned() {
+            return Ok(Some(index));
+        }
+        let path = symbols::symbols_path(root);
+        let loaded = tokio::task::spawn_blocking(move || SymbolIndex::load(&path))
+            .await
+            .map_err(|e| format!("Task join error: {}", e))?
+            .map(Arc::new);
+        if let Some(index) = &loaded {
+            self.symbols.write().unwrap().insert(root.to_string(), index.clone());
+        }
+        Ok(loaded)
+    }
+
+    /// Bring the indexes for `root` up to date and persist them, reporting status and progress as it goes.
+    /// Only changed files are re-chunked. With `incremental`, the semantic index also looks at
+    /// just those files; otherwise every file is re-hashed, though unchanged content is never re-embedded.
+    pub async fn refresh(&self, root: &str, incremental: bool) -> Result<Arc<Bm25Index>, String> {
+        let _guard = self.build_lock.lock().await;
+        let job = Arc::new(IndexJob::new(root, self.progress_listener.clone()));
+        self.jobs.lock().unwrap().insert(root.to_string(), job.clone());
+        self.emit_status(root, IndexState::Indexing, 0, None);
+        // Paths the watcher marks from here on may not be in this build, so they stay stale
+        let building = self.stale.lock().unwrap().get(root).cloned().unwrap_or_default();
+
+        let result = self.refresh_locked(root, incremental, &job).await;
+        self.jobs.lock().unwrap().remove(root);
+        let outcome = match &result {
+            Ok(_) => None,
+            Err(_) if job.is_cancelled() => Some((IndexState::Cancelled, None)),
+            Err(e) => Some((IndexState::Failed, Some(e.clone()))),
+        };
+        match outcome {
+            Some((state, error)) => {
+                self.emit_status(root, state, 0, error.clone());
+                self.outcomes.lock().unwrap().insert(root.to_string(), (state, error));
+            }
+            None => {
+                let remaining = {
+                    let mut stale = self.stale.lock().unwrap();
+                    let paths = stale.entry(root.to_string()).or_default();
+                    paths.retain(|path| !building.contains(path));
+                    paths.len()
+                };
+                match remaining {
+                    0 => self.emit_status(root, IndexState::Ready, 0, None),
+                    pending => self.emit_status(root, IndexState::Stale, pending, None),
+                }
+                self.outcomes.lock().unwrap().remove(root);
+            }
+        }
+        result
+    }
+
+    async fn refresh_locked(&self, root: &str, incremental: bool, job: &Arc<IndexJob>) -> Result<Arc<Bm25Index>, String> {
+        let previous = self.load_keyword(root).await?;
+        let root_owned = root.to_string();
+        let build_job = job.clone();
+        let (index, delta) = tokio::task::spawn_blocking(move || build_index(&root_owned, previous.as_deref(), &build_job))
+            .await
+            .map_err(|e| format!("Task join error: {}", e))??;
+
+        let semantic = if incremental && delta.is_empty() {
+            None
+        } else {
+            match self.build_semantic(root, incremental.then_some(delta), job).await {
+                Ok(index) => Some(index),
+                Err(e) if job.is_cancelled() => return Err(e),
+                // Keyword search keeps working when the embedding model is unavailable (e.g. offline on first run)
+                Err(e) => {
+                    eprintln!("[RAG] Semantic index skipped: {}", e);
+                    None
+                }
+            }
+        };
+        job.check()?;
+
+        let previous_symbols = self.load_symbols(root).await?;
+        let root_owned = root.to_string();
+        let persist_job = job.clone();
+        let (index, semantic, symbols) = tokio::task::spawn_blocking(move || -> Result<(Bm25Index, Option<SemanticIndex>, Arc<SymbolIndex>), String> {
+            persist_job.start_phase(IndexPhase::Persist, if semantic.is_some() { 3 } else { 2 });
+            index.save(&bm25_path(&root_owned))?;
+            persist_job.advance("bm25.bin");
+            // The symbol index is brought up to date from the same scan
+            let symbols = symbols::refresh(&root_owned, previous_symbols, &index.files);
+            persist_job.advance("symbols.bin");
+            if let Some(semantic) = &semantic {
+                match semantic.save(&vectors_path(&root_owned)) {
+                    Ok(()) => persist_job.advance("vectors.bin"),
+                    Err(e) => eprintln!("[RAG] Failed to save semantic index: {}", e),
+                }
+            }
+            Ok((index, semantic, symbols))
+        })
+        .await
+        .map_err(|e| format!("Task join error: {}", e))??;
+
+        let index = Arc::new(index);
+        self.symbols.write().unwrap().insert(root.to_string(), symbols);
+        self.keyword.write().unwrap().insert(root.to_string(), index.clone());
+        if let Some(semantic) = semantic {
+            self.semantic.write().unwrap().insert(root.to_string(), Arc::new(semantic));
+        }
+        Ok(index)
+    }
+
+    /// The semantic index updated for `delta`, or rebuilt without one; not yet persisted
+    async fn build_semantic(&self, root: &str, delta: Option<IndexDelta>, job: &Arc<IndexJob>) -> Result<SemanticIndex, String> {
+        let embedder = self.embedder().await?;
+        job.check()?;
+        let previous = self.load_semantic(root).await?;
+        let root_owned = root.to_string();
+        let job = job.clone();
+        tokio::task::spawn_blocking(move || match (&previous, &delta) {
+            (Some(previous), Some(delta)) => previous.update(&root_owned, delta, embedder.as_ref(), &job),
+            _ => SemanticIndex::build(&root_owned, previous.as_deref(), embedder.as_ref(), &job),
+        })
+        .await
+        .map_err(|e| format!("Task join error: {}", e))?
+    }
+
+    /// Stop the running build for `root` at its next file; false when none is running
+    pub fn cancel(&self, root: &str) -> bool {
+        match self.jobs.lock().unwrap().get(root) {
+            Some(job) => {
+                println!("[RAG] Cancelling index build for {}", root);
+                job.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub async fn status(&self, root: &str) -> Result<IndexStatus, String> {
+        let job = self.jobs.lock().unwrap().get(root).cloned();
+        let outcome = self.outcomes.lock().unwrap().get(root).cloned();
+        let keyword = self.load_keyword(root).await?;
+        let semantic = self.semantic.read().unwrap().contains_key(root);
+
+        let known_stale = self.stale.lock().unwrap().get(root).map(|paths| paths.len());
+
+        let root_owned = root.to_string();
+        let files_index = keyword.clone();
+        let (scanned, last_build_ms, on_disk_bytes, semantic) = tokio::task::spawn_blocking(move || {
+            // Only an index that hasn't been compared with the files yet is scanned
+            let scanned = match (known_stale, &files_index) {
+                (None, Some(index)) => Some(stale_files(&root_owned, &index.files)),
+                _ => None,
+            };
+            let last_build_ms = files_index.as_ref()
+                .and_then(|_| std::fs::metadata(bm25_path(&root_owned)).ok()?.modified().ok())
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_millis() as u64);
+            let semantic = semantic || vectors_path(&root_owned).exists();
+            (scanned, last_build_ms, dir_size(&index_dir(&root_owned)), semantic)
+        })
+        .await
+        .map_err(|e| format!("Task join error: {}", e))?;
+        let stale_files = match scanned {
+            Some(paths) => {
+                let count = paths.len();
+                self.stale.lock().unwrap().entry(root.to_string()).or_insert(paths);
+                count
+            }
+            None => known_stale.unwrap_or(0),
+        };
+
+        let (state, error) = match (&job, outcome, &keyword) {
+            (Some(_), _, _) => (IndexState::Indexing, None),
+            (None, Some(outcome), _) => outcome,
+            (None, None, None) => (IndexState::NotIndexed, None),
+            (None, None, Some(_)) if stale_files > 0 => (IndexState::Stale, None),
+            (None, None, Some(_)) => (IndexState::Ready, None),
+        };
+        Ok(IndexStatus {
+            root: root.to_string(),
+            state,
+            progress: job.map(|job| job.snapshot()),
+            indexed_files: keyword.as_ref().map_or(0, |index| index.files.len()),
+            chunks: keyword.as_ref().map_or(0, |index| index.chunks.len()),
+            stale_files,
+            last_build_ms,
+            on_disk_bytes,
+            semantic,
+            error,
+        })
+    }
+
+    /// The loaded or persisted keyword index, else a fresh build
+    async fn keyword_index(&self, root: &str) -> Result<Arc<Bm25Index>, String> {
+        match self.load_keyword(root).await? {
+            Some(index) => Ok(index),
+            None => self.refresh(root, false).await,
+        }
+    }
+
+    /// The loaded or persisted symbol index, else a fresh build
+    async fn symbol_index(&self, root: &str) -> Result<Arc<SymbolIndex>, String> {
+        if let Some(index) = self.load_symbols(root).await? {
+            return Ok(index);
+        }
+        self.refresh(root, false).await?;
+        self.symbols.read().unwrap().get(root).cloned().ok_or_else(|| "Symbol index was not built".to_string())
+    }
+
+    /// Cosine top-k from the semantic index; None if there is none yet
+    async fn semantic_search(&self, root: &str, query: &str, top_k: usize) -> Result<Option<Vec<(Chunk, f32)>>, String> {
+        let Some(index) = self.load_semantic(root).await? else {
+            return Ok(None);
+        };
+        let embedder = self.embedder().await?;
+        if embedder.model_id() != index.model() {
+            return Ok(None);
+        }
+        let query = query.to_string();
+        let query_vector = tokio::task::spawn_blocking(move || embedder.embed(vec![query]))
+            .await
+            .map_err(|e| format!("Task join error: {}", e))??
+            .pop()
+            .ok_or("Embedding returned no vector")?;
+        Ok(Some(index.search(&query_vector, top_k)))
+    }
+}
+
+pub struct CommunityRagService {
+    store: Arc<IndexStore>,
+    /// Background watchers by project root, started when a project is indexed
+    watchers: Mutex<HashMap<String, ProjectWatcher>>,
+    /// Root used by `search`, which is not given one
+    last_root: RwLock<Option<String>>,
+}
+
+impl CommunityRagService {
+    pub fn new() -> Self {
+        Self {
+            store: Arc::new(IndexStore::new()),
+            watchers: Mutex::new(HashMap::new()),
+            last_root: RwLock::new(None),
+        }
+    }
+
+    /// Report index status changes (e.g. to the frontend as `rag:index_status` events)
+    pub fn with_status_listener(mut self, listener: impl Fn(IndexStatusEvent) + Send + Sync + 'static) -> Self {
+        Arc::get_mut(&mut self.store)
+            .expect("listener is set before the store is shared")
+            .status_listener = Some(Arc::new(listener));
+        self
+    }
+
+    /// Report per-file progress of index builds (e.g. to the frontend as `rag:index_progress` events)
+    pub fn with_progress_listener(mut self, listener: impl Fn(IndexProgressEvent) + Send + Sync + 'static) -> Self {
+        Arc::get_mut(&mut self.store)
+            .expect("listener is set before the store is shared")
+            .progress_listener = Some(Arc::new(listener));
+        self
+    }
+
+    #[cfg(test)]
+    fn with_embedder(self, embedder: Arc<dyn Embedder>) -> Self {
+        *self.store.embedder.lock().unwrap() = Some(embedder);
+        self
+    }
+
+    /// Semantic hits when there is a semantic index, else BM25 hits
+    async fn ranked_hits(&self, root: &str, query: &str, top_k: usize) -> Result<Vec<(Chunk, f32)>, String> {
+        match self.store.semantic_search(root, query, top_k).await {
+            Ok(Some(hits)) => Ok(hits),
+            Ok(None) => Ok(self.store.keyword_index(root).await?.search(query, top_k)),
+            Err(e) => {
+                eprintln!("[RAG] Semantic search failed, using keyword search: {}", e);
+                Ok(self.store.keyword_index(root).await?.search(query, top_k))
+            }
+        }
+    }
+
+    fn watch(&self, root: &str) {
+        let mut watchers = self.watchers.lock().unwrap();
+        if watchers.contains_key(root) {
+            return;
+        }
+        match ProjectWatcher::start(self.store.clone(), root) {
+            Ok(watcher) => {
+                println!("[RAG] Watching {} for changes", root);
+                watchers.insert(root.to_string(), watcher);
+            }
+            Err(e) => eprintln!("[RAG] Failed to watch {}: {}", root, e),
+        }
+    }
+}
+
+impl Default for CommunityRagService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// References for the best chunks, with absolute paths so the frontend can open them
+pub fn to_references(root: &str, hits: &[(Chunk, f32)]) -> Vec<RagReference> {
+    hits.iter()
+        .map(|(chunk, _)| RagReference {
+            file_path: Path::new(root).join(&chunk.file_path).to_string_lossy().to_string(),
+            line_start: chunk.line_start,
+            line_end: chunk.line_end,
+            content: chunk.content.clone(),
+            symbol: chunk.qualified_symbol(),
+            language: chunk.language.clone(),
+        })
+        .collect()
+}
+
+pub fn format_context(hits: &[(Chunk, f32)]) -> String {
+    hits.iter()
+        .map(|(chunk, _)| {
+            let symbol = chunk.qualified_symbol().map(|s| format!(", {}", s)).unwrap_or_default();
+            format!(
+                "File: {} (lines {}-{}{})\n```\n{}\n```",
+                chunk.file_path, chunk.line_start, chunk.line_end, symbol, chunk.content.trim_end()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+#[async_trait::async_trait]
+impl RagService for CommunityRagService {
+    async fn index_project(&self, root: &str) -> Result<(), String> {
+        self.store.refresh(root, false).await?;
+        *self.last_root.write().unwrap() = Some(root.to_string());
+        self.watch(root);
+        Ok(())
+    }
+
+    /// Cosine top-k over the semantic index, or BM25 when there is no semantic index yet
+    async fn search(&self, query: &str, top_k: usize) -> Result<Vec<String>, String> {
+        let Some(root) = self.last_root.read().unwrap().clone() else {
+            return Ok(vec![]);
+        };
+        let hits = self.ranked_hits(&root, query, top_k).await?;
+        Ok(hits
+            .into_iter()
+            .map(|(chunk, _)| format!("{}:{}-{}", chunk.file_path, chunk.line_start, chunk.line_end))
+            .collect())
+    }
+
+    /// Chat context from the best semantic hits, or BM25 hits when there is no semantic index yet
+    async fn retrieve_context(&self, query: &str, root: &str) -> Result<RagResult, String> {
+        let hits = self.ranked_hits(root, query, RETRIEVE_TOP_K).await?;
+        *self.last_root.write().unwrap() = Some(root.to_string());
+        // An index loaded from disk is kept current from here on, like one built this session
+        self.watch(root);
+        Ok(RagResult {
+            context: format_context(&hits),
+            references: to_references(root, &hits),
+        })
+    }
+
+    async fn search_chunks(&self, query: &str, root: &str, top_k: usize) -> Result<Vec<RagReference>, String> {
+        let hits = self.ranked_hits(root, query, top_k).await?;
+        Ok(to_references(root, &hits))
+    }
+
+    async fn index_status(&self, root: &str) -> Result<IndexStatus, String> {
+        self.store.status(root).await
+    }
+
+    fn cancel_index(&self, root: &str) -> bool {
+        self.store.cancel(root)
+    }
+
+    fn stop_watching(&self, root: &str) -> bool {
+        let stopped = self.watchers.lock().unwrap().remove(root).is_some();
+        if stopped {
+            println!("[RAG] Stopped watching {}", root);
+        }
+        stopped
+    }
+
+    async fn find_symbol(&self, root: &str, query: &str, limit: usize) -> Result<Vec<SymbolDefinition>, String> {
+        let index = self.store.symbol_index(root).await?;
+        self.watch(root);
+        Ok(index.find_definitions(query, limit))
+    }
+
+    async fn find_references(&self, root: &str, name: &str, limit: usize) -> Result<Vec<SymbolReference>, String> {
+        let hits = self.store.symbol_index(root).await?.find_references(name, limit);
+        self.watch(root);
+        let root = root.to_string();
+        tokio::task::spawn_blocking(move || symbols::with_source_lines(&root, hits))
+            .await
+            .map_err(|e| format!("Task join error: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use semantic::tests::KeywordEmbedder;
+    use std::fs;
+    use std::sync::atomic::AtomicUsize;
+
+    #[tokio::test]
+    async fn test_retrieve_context_from_local_index() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("src/config.rs"), "use std::path::Path;\n\npub fn load_config(path: &Path) {\n    // read the settings file\n}\n").unwrap();
+        fs::write(root.join("src/ui.ts"), "export function renderSidebar() {}\n").unwrap();
+        let root_str = root.to_string_lossy().to_string();
+
+        let service = CommunityRagService::new().with_embedder(Arc::new(KeywordEmbedder { calls: AtomicUsize::new(0) }));
+        service.index_project(&root_str).await.unwrap();
+        assert!(bm25_path(&root_str).exists());
+        assert!(vectors_path(&root_str).exists());
+        assert_eq!(service.search("render", 1).await.unwrap(), vec!["src/ui.ts:1-1".to_string()]);
+        // Indexing started a watcher, which stops when the project is closed
+        assert!(service.stop_watching(&root_str));
+        assert!(!service.stop_watching(&root_str));
+
+        // A fresh service picks up the persisted indexes
+        let service = CommunityRagService::new().with_embedder(Arc::new(KeywordEmbedder { calls: AtomicUsize::new(0) }));
+        let result = service.retrieve_context("where is load_config", &root_str).await.unwrap();
+        assert_eq!(result.references[0].file_path, root.join("src/config.rs").to_string_lossy());
+        // The function is its own chunk, separate from the imports above it
+        let reference = result.references.iter().find(|r| r.symbol.as_deref() == Some("load_config")).unwrap();
+        assert_eq!((reference.line_start, reference.line_end), (3, 5));
+        assert_eq!(reference.language.as_deref(), Some("rust"));
+        assert!(result.context.contains("File: src/config.rs (lines 3-5, load_config)"));
+
+        // Chat context comes from the semantic index, so it finds code that shares no keyword with the question
+        assert!(service.store.keyword_index(&root_str).await.unwrap().search("configuration", RETRIEVE_TOP_K).is_empty());
+        let result = service.retrieve_context("configuration", &root_str).await.unwrap();
+        assert_eq!(result.references[0].file_path, root.join("src/config.rs").to_string_lossy());
+    }
+
+    /// Cancels the build it is embedding for
+    struct CancellingEmbedder {
+        store: Arc<IndexStore>,
+        root: String,
+    }
+
+    impl Embedder for CancellingEmbedder {
+        fn model_id(&self) -> &str {
+            "keyword-test"
+        }
+
+        fn embed(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>, String> {
+            self.store.cancel(&self.root);
+            Ok(texts.iter().map(|_| vec![1.0, 0.0]).collect())
+        }
+    }
+
+    /// Reports a file as changed while the build it is embedding for is running
+    struct MarkingEmbedder {
+        store: Arc<IndexStore>,
+        root: String,
+        changed: PathBuf,
+    }
+
+    impl Embedder for MarkingEmbedder {
+        fn model_id(&self) -> &str {
+            "keyword-test"
+        }
+
+        fn embed(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>, String> {
+            self.store.mark_stale(&self.root, &HashSet::from([self.changed.clone()]));
+            Ok(texts.iter().map(|_| vec![1.0, 0.0]).collect())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_changes_during_a_build_stay_stale() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+        fs::write(root.join("main.rs"), "fn main() {}\n").unwrap();
+        let root_str = root.to_string_lossy().to_string();
+
+        let service = CommunityRagService::new();
+        service.store.mark_stale(&root_str, &HashSet::from([root.join("main.rs")]));
+        let embedder = MarkingEmbedder { store: service.store.clone(), root: root_str.clone(), changed: root.join("lib.rs") };
+        *service.store.embedder.lock().unwrap() = Some(Arc::new(embedder));
+        service.store.refresh(&root_str, false).await.unwrap();
+
+        // main.rs was part of the build; lib.rs changed after it had started
+        let status = service.index_status(&root_str).await.unwrap();
+        assert_eq!(status.state, IndexState::Stale);
+        assert_eq!(status.stale_files, 1);
+    }
+
+    #[tokio::test]
+    async fn test_index_status_progress_and_cancel() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("src/config.rs"), "pub fn load_config() {}\n").unwrap();
+        fs::write(root.join("src/ui.ts"), "export function renderSidebar() {}\n").unwrap();
+        let root_str = root.to_string_lossy().to_string();
+
+        let progress = Arc::new(Mutex::new(Vec::new()));
+        let seen = progress.clone();
+        let service = CommunityRagService::new()
+            .with_progress_listener(move |event| seen.lock().unwrap().push((event.phase, event.processed, event.total)))
+            .with_embedder(Arc::new(KeywordEmbedder { calls: AtomicUsize::new(0) }));
+        assert_eq!(service.index_status(&root_str).await.unwrap().state, IndexState::NotIndexed);
+
+        service.store.refresh(&root_str, false).await.unwrap();
+        let mut phases: Vec<IndexPhase> = progress.lock().unwrap().iter().map(|(phase, _, _)| *phase).collect();
+        phases.dedup();
+        assert_eq!(phases, vec![IndexPhase::Scan, IndexPhase::Chunk, IndexPhase::Embed, IndexPhase::Persist]);
+        assert!(progress.lock().unwrap().contains(&(IndexPhase::Chunk, 2, 2)));
+
+        let status = service.index_status(&root_str).await.unwrap();
+        assert_eq!(status.state, IndexState::Ready);
+        assert_eq!((status.indexed_files, status.stale_files), (2, 0));
+        assert!(status.is_searchable() && status.semantic && status.on_disk_bytes > 0);
+        assert!(status.progress.is_none());
+
+        // A build cancelled while embedding the edit writes nothing; the previous index stays in use
+        fs::write(root.join("src/config.rs"), "pub fn load_config(path: &str) {}\n").unwrap();
+        // Reported by the watcher; status doesn't rescan an index it has already compared
+        service.store.mark_stale(&root_str, &HashSet::from([root.join("src/config.rs")]));
+        assert_eq!(service.index_status(&root_str).await.unwrap().state, IndexState::Stale);
+        let embedder = CancellingEmbedder { store: service.store.clone(), root: root_str.clone() };
+        *service.store.embedder.lock().unwrap() = Some(Arc::new(embedder));
+        let persisted = fs::metadata(bm25_path(&root_str)).unwrap().modified().unwrap();
+
+        assert_eq!(service.store.refresh(&root_str, true).await.unwrap_err(), job::CANCELLED);
+        let status = service.index_status(&root_str).await.unwrap();
+        assert_eq!(status.state, IndexState::Cancelled);
+        assert_eq!(status.stale_files, 1);
+        assert!(status.is_searchable());
+        assert_eq!(fs::metadata(bm25_path(&root_str)).unwrap().modified().unwrap(), persisted);
+        assert!(!service.cancel_index(&root_str));
+    }
+}
@@ -0,0 +1,285 @@
+//! Local semantic index: chunks embedded with fastembed, persisted with bincode.
+//!
+//! Embeddings are stored by file content hash, so unchanged (or merely renamed) files
+//! are never re-embedded when a project is reopened.
+
+use super::chunker::{self, Chunk};
+use super::job::IndexJob;
+use super::IndexDelta;
+use crate::events::IndexPhase;
+use fastembed::{EmbeddingModel, InitOptions, TextEmbedding};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Bump when the on-disk layout changes; older indexes are rebuilt
+pub const INDEX_VERSION: u32 = 2;
+const EMBED_BATCH_SIZE: usize = 32;
+
+/// Turns text into vectors. The index only compares vectors made by the same `model_id`.
+pub trait Embedder: Send + Sync {
+    fn model_id(&self) -> &str;
+    fn embed(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>, String>;
+}
+
+/// fastembed running on the CPU. The model is downloaded once into `~/.ifai/models`.
+pub struct FastEmbedder {
+    model: Mutex<TextEmbedding>,
+}
+
+impl FastEmbedder {
+    pub fn new() -> Result<Self, String> {
+        let options = InitOptions::new(EmbeddingModel::AllMiniLML6V2)
+            .with_cache_dir(models_dir())
+            .with_show_download_progress(false);
+        let model = TextEmbedding::try_new(options)
+            .map_err(|e| format!("Failed to load embedding model: {}", e))?;
+        Ok(Self { model: Mutex::new(model) })
+    }
+}
+
+impl Embedder for FastEmbedder {
+    fn model_id(&self) -> &str {
+        "all-MiniLM-L6-v2"
+    }
+
+    fn embed(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>, String> {
+        self.model.lock().unwrap()
+            .embed(texts, Some(EMBED_BATCH_SIZE))
+            .map_err(|e| format!("Embedding failed: {}", e))
+    }
+}
+
+fn models_dir() -> PathBuf {
+    let home = std::env::var("HOME").or_else(|_| std::env::var("USERPROFILE")).unwrap_or_else(|_| ".".to_string());
+    Path::new(&home).join(".ifai").join("models")
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct EmbeddedChunk {
+    line_start: usize,
+    line_end: usize,
+    content: String,
+    symbol: Option<String>,
+    kind: Option<String>,
+    scope: Option<String>,
+    language: Option<String>,
+    /// Unit length, so cosine similarity is a dot product
+    vector: Vec<f32>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SemanticIndex {
+    version: u32,
+    model: String,
+    /// Relative path -> content hash
+    files: HashMap<String, String>,
+    /// Content hash -> embedded chunks of that content
+    embeddings: HashMap<String, Vec<EmbeddedChunk>>,
+}
+
+impl SemanticIndex {
+    /// Embed the indexable files under `root`, reusing vectors from `previous` for content it has seen
+    pub fn build(root: &str, previous: Option<&SemanticIndex>, embedder: &dyn Embedder, job: &IndexJob) -> Result<Self, String> {
+        let paths: Vec<String> = chunker::scan_files(Path::new(root)).into_iter().map(|(path, _)| path).collect();
+        Self::embed_files(root, HashMap::new(), &paths, previous, embedder, job)
+    }
+
+    /// Apply a watcher delta: drop removed files and re-embed changed ones whose content is new
+    pub fn update(&self, root: &str, delta: &IndexDelta, embedder: &dyn Embedder, job: &IndexJob) -> Result<Self, String> {
+        if self.model != embedder.model_id() {
+            return Self::build(root, None, embedder, job);
+        }
+        let mut files = self.files.clone();
+        for path in delta.removed.iter().chain(&delta.changed) {
+            files.remove(path);
+        }
+        Self::embed_files(root, files, &delta.changed, Some(self), embedder, job)
+    }
+
+    /// Add `paths` to the `kept` files. Only content not found in `previous` is embedded.
+    fn embed_files(
+        root: &str,
+        kept: HashMap<String, String>,
+        paths: &[String],
+        previous: Option<&SemanticIndex>,
+        embedder: &dyn Embedder,
+        job: &IndexJob,
+    ) -> Result<Self, String> {
+        let root_path = Path::new(root);
+        let previous = previous.filter(|p| p.model == embedder.model_id());
+        let reuse = |hash: &str| previous.and_then(|p| p.embeddings.get(hash)).cloned();
+
+        let mut embeddings: HashMap<String, Vec<EmbeddedChunk>> = HashMap::new();
+        for hash in kept.values() {
+            if let Some(old) = reuse(hash) {
+                embeddings.insert(hash.clone(), old);
+            }
+        }
+
+        let mut files = kept;
+        let mut pending: Vec<(String, Vec<Chunk>)> = Vec::new();
+        for rel_path in paths {
+            job.check()?;
+            let Some(text) = chunker::read_text(root_path, rel_path) else { continue };
+            let hash = content_hash(&text);
+            files.insert(rel_path.clone(), hash.clone());
+            if embeddings.contains_key(&hash) || pending.iter().any(|(h, _)| *h == hash) {
+                continue;
+            }
+            match reuse(&hash) {
+                Some(old) => {
+                    embeddings.insert(hash, old);
+                }
+                None => pending.push((hash, chunker::chunk_text(rel_path, &text))),
+            }
+        }
+
+        let new_chunks: usize = pending.iter().map(|(_, chunks)| chunks.len()).sum();
+        println!("[RAG] Semantic index: {} files, embedding {} new chunks", files.len(), new_chunks);
+
+        job.start_phase(IndexPhase::Embed, pending.len());
+        for (hash, chunks) in pending {
+            job.check()?;
+            let mut embedded = Vec::with_capacity(chunks.len());
+            for batch in chunks.chunks(EMBED_BATCH_SIZE) {
+                let texts = batch.iter().map(|c| format!("{}\n{}", c.file_path, c.content)).collect();
+                let vectors = embedder.embed(texts)?;
+                for (chunk, vector) in batch.iter().zip(vectors) {
+                    embedded.push(EmbeddedChunk {
+                        line_start: chunk.line_start,
+                        line_end: chunk.line_end,
+                        content: chunk.content.clone(),
+                        symbol: chunk.symbol.clone(),
+                        kind: chunk.kind.clone(),
+                        scope: chunk.scope.clone(),
+                        language: chunk.language.clone(),
+                        vector: normalize(vector),
+                    });
+                }
+            }
+            if let Some(chunk) = chunks.first() {
+                job.advance(&chunk.file_path);
+            }
+            embeddings.insert(hash, embedded);
+        }
+
+        Ok(Self { version: INDEX_VERSION, model: embedder.model_id().to_string(), files, embeddings })
+    }
+
+    /// Cosine top-k over every chunk, highest first
+    pub fn search(&self, query_vector: &[f32], top_k: usize) -> Vec<(Chunk, f32)> {
+        let query = normalize(query_vector.to_vec());
+        let mut scored: Vec<(Chunk, f32)> = self.files.iter()
+            .filter_map(|(path, hash)| self.embeddings.get(hash).map(|chunks| (path, chunks)))
+            .flat_map(|(path, chunks)| chunks.iter().map(move |c| (path, c)))
+            .map(|(path, c)| {
+                let score = c.vector.iter().zip(&query).map(|(a, b)| a * b).sum();
+                let chunk = Chunk {
+                    file_path: path.clone(),
+                    line_start: c.line_start,
+                    line_end: c.line_end,
+                    content: c.content.clone(),
+                    symbol: c.symbol.clone(),
+                    kind: c.kind.clone(),
+                    scope: c.scope.clone(),
+                    language: c.language.clone(),
+                };
+                (chunk, score)
+            })
+            .collect();
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1)
+            .then_with(|| a.0.file_path.cmp(&b.0.file_path))
+            .then(a.0.line_start.cmp(&b.0.line_start)));
+        scored.truncate(top_k);
+        scored
+    }
+
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    pub fn load(path: &Path) -> Option<Self> {
+        let bytes = fs::read(path).ok()?;
+        let (index, _): (Self, usize) = bincode::serde::decode_from_slice(&bytes, bincode::config::standard()).ok()?;
+        (index.version == INDEX_VERSION).then_some(index)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        let bytes = bincode::serde::encode_to_vec(self, bincode::config::standard()).map_err(|e| e.to_string())?;
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, bytes).map_err(|e| e.to_string())?;
+        fs::rename(&tmp, path).map_err(|e| e.to_string())
+    }
+}
+
+pub fn content_hash(text: &str) -> String {
+    blake3::hash(text.as_bytes()).to_hex().to_string()
+}
+
+fn normalize(mut vector: Vec<f32>) -> Vec<f32> {
+    let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|x| *x /= norm);
+    }
+    vector
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Bag-of-words vectors over a fixed vocabulary; counts how many texts it embedded
+    pub(crate) struct KeywordEmbedder {
+        pub calls: AtomicUsize,
+    }
+
+    const VOCAB: [&str; 4] = ["config", "render", "socket", "parse"];
+
+    impl Embedder for KeywordEmbedder {
+        fn model_id(&self) -> &str {
+            "keyword-test"
+        }
+
+        fn embed(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>, String> {
+            self.calls.fetch_add(texts.len(), Ordering::SeqCst);
+            Ok(texts.iter()
+                .map(|t| VOCAB.iter().map(|w| t.to_lowercase().matches(w).count() as f32 + 0.01).collect())
+                .collect())
+        }
+    }
+
+    #[test]
+    fn test_unchanged_content_is_not_reembedded() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+        fs::write(root.join("config.rs"), "fn parse_config() { load config }\n").unwrap();
+        fs::write(root.join("view.ts"), "function render() { render view }\n").unwrap();
+        let root_str = root.to_string_lossy().to_string();
+        let embedder = KeywordEmbedder { calls: AtomicUsize::new(0) };
+        let job = IndexJob::new(&root_str, None);
+
+        let index = SemanticIndex::build(&root_str, None, &embedder, &job).unwrap();
+        assert_eq!(embedder.calls.load(Ordering::SeqCst), 2);
+        let query = embedder.embed(vec!["config".to_string()]).unwrap().remove(0);
+        assert_eq!(index.search(&query, 1)[0].0.file_path, "config.rs");
+
+        let path = root.join(".ifai/index/vectors.bin");
+        index.save(&path).unwrap();
+        let loaded = SemanticIndex::load(&path).unwrap();
+
+        // A rename keeps its vectors; only the edited file is embedded again
+        fs::rename(root.join("config.rs"), root.join("settings.rs")).unwrap();
+        fs::write(root.join("view.ts"), "function render() { open socket }\n").unwrap();
+        embedder.calls.store(0, Ordering::SeqCst);
+        let rebuilt = SemanticIndex::build(&root_str, Some(&loaded), &embedder, &job).unwrap();
+        assert_eq!(embedder.calls.load(Ordering::SeqCst), 1);
+        assert_eq!(rebuilt.search(&query, 1)[0].0.file_path, "settings.rs");
+    }
+}
@@ -0,0 +1,300 @@
+//! Project-wide symbol index for workspace symbol search without a language server:
+//! where each function, type and impl is defined, and which lines mention a name.
+//!
+//! Built from the same file scan as the RAG index, persisted next to it and held in the
+//! [`IndexStore`](super::IndexStore), so the project watcher keeps it current. Only files
+//! whose size or mtime changed are re-parsed.
+
+use super::chunker::{self, FileStamp};
+use super::syntax;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+pub const INDEX_VERSION: u32 = 1;
+/// Longest source line shown with a reference
+const MAX_PREVIEW_CHARS: usize = 200;
+
+pub(super) fn symbols_path(project_root: &str) -> PathBuf {
+    super::index_dir(project_root).join("symbols.bin")
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SymbolDefinition {
+    pub name: String,
+    /// With its enclosing impl, class or module, e.g. `Supervisor::wait_for_approval`
+    pub qualified_name: String,
+    /// `function`, `method`, `struct`, `impl`, `class`, ...
+    pub kind: String,
+    /// Relative to the project root, with `/` separators
+    pub file_path: String,
+    /// 1-based, inclusive
+    pub line_start: usize,
+    pub line_end: usize,
+    pub language: String,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct SymbolReference {
+    /// Relative to the project root, with `/` separators
+    pub file_path: String,
+    /// 1-based
+    pub line: usize,
+    /// The trimmed source line
+    pub content: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct FileSymbols {
+    stamp: FileStamp,
+    definitions: Vec<SymbolDefinition>,
+    /// Lines mentioning each identifier, other than the lines defining it
+    references: HashMap<String, Vec<usize>>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SymbolIndex {
+    version: u32,
+    files: HashMap<String, FileSymbols>,
+}
+
+impl SymbolIndex {
+    pub fn load(path: &Path) -> Option<Self> {
+        let bytes = fs::read(path).ok()?;
+        let (index, _): (Self, usize) = bincode::serde::decode_from_slice(&bytes, bincode::config::standard()).ok()?;
+        (index.version == INDEX_VERSION).then_some(index)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        let bytes = bincode::serde::encode_to_vec(self, bincode::config::standard()).map_err(|e| e.to_string())?;
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, bytes).map_err(|e| e.to_string())?;
+        fs::rename(&tmp, path).map_err(|e| e.to_string())
+    }
+
+    /// Whether `files` (source files only) are exactly the indexed ones, with the same stamps
+    fn is_current(&self, files: &HashMap<&str, FileStamp>) -> bool {
+        self.files.len() == files.len()
+            && files.iter().all(|(path, stamp)| self.files.get(*path).is_some_and(|f| f.stamp == *stamp))
+    }
+
+    /// Index `files`, reusing entries from `previous` whose stamps are unchanged.
+    /// Returns the index and the number of files parsed.
+    fn update(root: &Path, previous: Option<&SymbolIndex>, files: &HashMap<&str, FileStamp>) -> (Self, usize) {
+        let mut parsed = 0;
+        let files = files.iter()
+            .map(|(path, stamp)| {
+                let reused = previous.and_then(|p| p.files.get(*path)).filter(|f| f.stamp == *stamp);
+                let symbols = match reused {
+                    Some(symbols) => symbols.clone(),
+                    None => {
+                        parsed += 1;
+                        parse_file(root, path, *stamp)
+                    }
+                };
+                (path.to_string(), symbols)
+            })
+            .collect();
+        (Self { version: INDEX_VERSION, files }, parsed)
+    }
+
+    pub fn definition_count(&self) -> usize {
+        self.files.values().map(|f| f.definitions.len()).sum()
+    }
+
+    /// Definitions matching `query`, best first. A qualified query (`Supervisor::wait_for_approval`,
+    /// `Store.get`) matches qualified names; a plain one matches names exactly, then by prefix and substring,
+    /// ignoring case.
+    pub fn find_definitions(&self, query: &str, limit: usize) -> Vec<SymbolDefinition> {
+        let query = query.trim();
+        if query.is_empty() {
+            return vec![];
+        }
+        let qualified = query.contains("::") || query.contains('.');
+        let query_normalized = normalize_qualified(query);
+        let query_lower = query_normalized.to_lowercase();
+
+        let mut matches: Vec<(u8, &SymbolDefinition)> = self.files.values()
+            .flat_map(|f| &f.definitions)
+            .filter_map(|definition| {
+                let rank = if qualified {
+                    let name = normalize_qualified(&definition.qualified_name);
+                    let name_lower = name.to_lowercase();
+                    if name == query_normalized {
+                        0
+                    } else if name.ends_with(&format!(".{}", query_normalized)) {
+                        1
+                    } else if name_lower == query_lower || name_lower.ends_with(&format!(".{}", query_lower)) {
+                        2
+                    } else {
+                        return None;
+                    }
+                } else {
+                    let name_lower = definition.name.to_lowercase();
+                    if definition.name == query {
+                        0
+                    } else if name_lower == query_lower {
+                        1
+                    } else if name_lower.starts_with(&query_lower) {
+                        2
+                    } else if name_lower.contains(&query_lower) {
+                        3
+                    } else {
+                        return None;
+                    }
+                };
+                Some((rank, definition))
+            })
+            .collect();
+
+        // Impl blocks come after the type they implement
+        matches.sort_by(|(rank_a, a), (rank_b, b)| {
+            (rank_a, a.kind == "impl", a.name.len(), &a.file_path, a.line_start)
+                .cmp(&(rank_b, b.kind == "impl", b.name.len(), &b.file_path, b.line_start))
+        });
+        matches.into_iter().take(limit).map(|(_, d)| d.clone()).collect()
+    }
+
+    /// (file, line) of lines mentioning `name` (the last segment when qualified), in path and line order
+    pub fn find_references(&self, name: &str, limit: usize) -> Vec<(String, usize)> {
+        let name = name.trim().rsplit([':', '.']).next().unwrap_or_default();
+        let mut hits: Vec<(String, usize)> = self.files.iter()
+            .filter_map(|(path, f)| f.references.get(name).map(|lines| (path, lines)))
+            .flat_map(|(path, lines)| lines.iter().map(|line| (path.clone(), *line)))
+            .collect();
+        hits.sort();
+        hits.truncate(limit);
+        hits
+    }
+}
+
+/// `::` and `.` compare equal, so `Store::get` finds `Store.get`
+fn normalize_qualified(name: &str) -> String {
+    name.replace("::", ".")
+}
+
+fn parse_file(root: &Path, rel_path: &str, stamp: FileStamp) -> FileSymbols {
+    let mut symbols = FileSymbols { stamp, definitions: Vec::new(), references: HashMap::new() };
+    let Some(outline) = chunker::read_text(root, rel_path).and_then(|text| syntax::outline(rel_path, &text)) else {
+        return symbols;
+    };
+    let language = syntax::language_name(rel_path).unwrap_or_default();
+
+    for (identifier, line) in outline.identifiers {
+        let is_definition = outline.symbols.iter().any(|s| s.name_line == line && s.name == identifier);
+        if is_definition {
+            continue;
+        }
+        let lines = symbols.references.entry(identifier).or_default();
+        if lines.last() != Some(&line) {
+            lines.push(line);
+        }
+    }
+    symbols.definitions = outline.symbols.into_iter()
+        .map(|s| SymbolDefinition {
+            qualified_name: match &s.scope {
+                Some(scope) => format!("{}{}{}", scope, syntax::scope_separator(Some(language)), s.name),
+                None => s.name.clone(),
+            },
+            name: s.name,
+            kind: s.kind.to_string(),
+            file_path: rel_path.to_string(),
+            line_start: s.line_start,
+            line_end: s.line_end,
+            language: language.to_string(),
+        })
+        .collect();
+    symbols
+}
+
+/// Bring `previous` up to date with `files` (as scanned by the chunker) and persist it.
+/// A failed write is logged; the in-memory index is still used.
+pub fn refresh(root: &str, previous: Option<Arc<SymbolIndex>>, files: &HashMap<String, FileStamp>) -> Arc<SymbolIndex> {
+    let files: HashMap<&str, FileStamp> = files.iter()
+        .filter(|(path, _)| syntax::language_name(path).is_some())
+        .map(|(path, stamp)| (path.as_str(), *stamp))
+        .collect();
+
+    match previous {
+        Some(previous) if previous.is_current(&files) => previous,
+        previous => {
+            let (index, parsed) = SymbolIndex::update(Path::new(root), previous.as_deref(), &files);
+            println!("[Symbols] Indexed {} source files ({} parsed), {} definitions", files.len(), parsed, index.definition_count());
+            if let Err(e) = index.save(&symbols_path(root)) {
+                eprintln!("[Symbols] Failed to save index: {}", e);
+            }
+            Arc::new(index)
+        }
+    }
+}
+
+/// Reference hits from [`SymbolIndex::find_references`] with their source lines. Reads files, so call it off the async runtime.
+pub fn with_source_lines(root: &str, hits: Vec<(String, usize)>) -> Vec<SymbolReference> {
+    let mut files: HashMap<String, Vec<String>> = HashMap::new();
+    hits.into_iter()
+        .map(|(file_path, line)| {
+            let lines = files.entry(file_path.clone()).or_insert_with(|| {
+                chunker::read_text(Path::new(root), &file_path)
+                    .map(|text| text.lines().map(str::to_string).collect())
+                    .unwrap_or_default()
+            });
+            let content = lines.get(line - 1).map(|l| l.trim().chars().take(MAX_PREVIEW_CHARS).collect()).unwrap_or_default();
+            SymbolReference { file_path, line, content }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scan(root: &Path) -> HashMap<String, FileStamp> {
+        chunker::scan_files(root).into_iter().collect()
+    }
+
+    #[test]
+    fn test_finds_definitions_and_references_and_follows_edits() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(
+            root.join("src/supervisor.rs"),
+            "pub struct Supervisor;\n\nimpl Supervisor {\n    /// Blocks until approved\n    pub async fn wait_for_approval(&self) -> bool {\n        true\n    }\n}\n",
+        ).unwrap();
+        fs::write(root.join("src/runner.rs"), "fn run(s: &Supervisor) {\n    s.wait_for_approval();\n}\n").unwrap();
+        fs::write(root.join("web.ts"), "export class Store {\n  get() { return waitForApproval(); }\n}\n").unwrap();
+        let root_str = root.to_string_lossy().to_string();
+
+        let index = refresh(&root_str, None, &scan(root));
+        let found = index.find_definitions("Supervisor::wait_for_approval", 10);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].file_path, "src/supervisor.rs");
+        assert_eq!((found[0].line_start, found[0].line_end), (5, 7));
+        assert_eq!(found[0].kind, "method");
+
+        // The struct ranks before its impl; a case-insensitive prefix finds the TS class method
+        let found = index.find_definitions("Supervisor", 10);
+        assert_eq!(found.iter().map(|d| d.kind.as_str()).collect::<Vec<_>>(), vec!["struct", "impl"]);
+        assert_eq!(index.find_definitions("store.GET", 10)[0].qualified_name, "Store.get");
+
+        // The definition's own name isn't a reference
+        let references = with_source_lines(&root_str, index.find_references("Supervisor::wait_for_approval", 10));
+        assert_eq!(references, vec![SymbolReference { file_path: "src/runner.rs".to_string(), line: 2, content: "s.wait_for_approval();".to_string() }]);
+        assert!(symbols_path(&root_str).exists());
+
+        // A refresh after an edit re-parses only that file
+        fs::write(root.join("src/runner.rs"), "fn run(s: &Supervisor) {}\n\nfn retry(s: &Supervisor) {\n    s.wait_for_approval();\n}\n").unwrap();
+        let scanned = scan(root);
+        let files: HashMap<&str, FileStamp> = scanned.iter().map(|(path, stamp)| (path.as_str(), *stamp)).collect();
+        assert!(!index.is_current(&files));
+        assert_eq!(SymbolIndex::update(root, Some(&index), &files).1, 1);
+        let index = refresh(&root_str, SymbolIndex::load(&symbols_path(&root_str)).map(Arc::new), &scanned);
+        assert_eq!(index.find_references("wait_for_approval", 10), vec![("src/runner.rs".to_string(), 4)]);
+        assert_eq!(index.find_definitions("retry", 10)[0].line_start, 3);
+    }
+}
@@ -0,0 +1,472 @@
+//! Syntax-aware chunking with tree-sitter for Rust, TypeScript/JavaScript, Python and Go.
+//!
+//! Files are split on function, impl and class boundaries, with leading doc comments and
+//! attributes kept on their definition. A container that is too big for one chunk (a long
+//! `impl` or class) is split into its members; a single definition that is still too big
+//! falls back to the text splitter, with every piece keeping the definition's symbol.
+
+use super::chunker::{split_text, Chunk};
+use tree_sitter::{Language, Node, Parser};
+
+/// Definitions up to this size stay in one chunk
+const MAX_SYMBOL_BYTES: usize = 3000;
+/// Code between definitions (imports, constants, fields) is grouped into chunks up to this size
+const MAX_GAP_BYTES: usize = 1500;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Lang {
+    Rust,
+    TypeScript,
+    Tsx,
+    JavaScript,
+    Python,
+    Go,
+}
+
+impl Lang {
+    fn from_path(path: &str) -> Option<Self> {
+        let ext = path.rsplit_once('.')?.1.to_ascii_lowercase();
+        match ext.as_str() {
+            "rs" => Some(Self::Rust),
+            "ts" | "mts" | "cts" => Some(Self::TypeScript),
+            "tsx" => Some(Self::Tsx),
+            "js" | "jsx" | "mjs" | "cjs" => Some(Self::JavaScript),
+            "py" | "pyi" => Some(Self::Python),
+            "go" => Some(Self::Go),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Rust => "rust",
+            Self::TypeScript | Self::Tsx => "typescript",
+            Self::JavaScript => "javascript",
+            Self::Python => "python",
+            Self::Go => "go",
+        }
+    }
+
+    fn grammar(self) -> Language {
+        match self {
+            Self::Rust => tree_sitter_rust::LANGUAGE.into(),
+            Self::TypeScript => tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(),
+            Self::Tsx => tree_sitter_typescript::LANGUAGE_TSX.into(),
+            Self::JavaScript => tree_sitter_javascript::LANGUAGE.into(),
+            Self::Python => tree_sitter_python::LANGUAGE.into(),
+            Self::Go => tree_sitter_go::LANGUAGE.into(),
+        }
+    }
+
+    /// Comments and attributes that belong to the definition right after them
+    fn is_leading(self, kind: &str) -> bool {
+        match self {
+            Self::Rust => matches!(kind, "line_comment" | "block_comment" | "attribute_item"),
+            Self::TypeScript | Self::Tsx | Self::JavaScript => matches!(kind, "comment" | "decorator"),
+            Self::Python | Self::Go => kind == "comment",
+        }
+    }
+}
+
+/// Language name for a path, for the languages the syntax chunker understands
+pub fn language_name(path: &str) -> Option<&'static str> {
+    Lang::from_path(path).map(Lang::name)
+}
+
+/// Separator between a scope and a member name in qualified symbols
+pub fn scope_separator(language: Option<&str>) -> &'static str {
+    if language == Some("rust") { "::" } else { "." }
+}
+
+/// Chunks on definition boundaries, or None when the language isn't supported or parsing fails
+pub fn chunk_source(rel_path: &str, text: &str) -> Option<Vec<Chunk>> {
+    let lang = Lang::from_path(rel_path)?;
+    let mut parser = Parser::new();
+    parser.set_language(&lang.grammar()).ok()?;
+    let tree = parser.parse(text, None)?;
+
+    let mut chunker = SyntaxChunker { lang, rel_path, source: text, lines: text.lines().collect(), chunks: Vec::new() };
+    chunker.walk(tree.root_node(), None);
+    for chunk in &mut chunker.chunks {
+        chunk.language = Some(lang.name().to_string());
+    }
+    Some(chunker.chunks)
+}
+
+/// Every definition in a file, nested ones included, and every identifier it mentions
+#[derive(Debug, Default)]
+pub struct Outline {
+    pub symbols: Vec<OutlineSymbol>,
+    /// (identifier, 1-based line)
+    pub identifiers: Vec<(String, usize)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutlineSymbol {
+    pub name: String,
+    pub kind: &'static str,
+    pub scope: Option<String>,
+    /// Line of the name itself, where the identifier is a definition rather than a reference
+    pub name_line: usize,
+    /// 1-based, inclusive, without leading comments
+    pub line_start: usize,
+    pub line_end: usize,
+}
+
+/// Definitions and identifiers of a file, or None when the language isn't supported or parsing fails
+pub fn outline(rel_path: &str, text: &str) -> Option<Outline> {
+    let lang = Lang::from_path(rel_path)?;
+    let mut parser = Parser::new();
+    parser.set_language(&lang.grammar()).ok()?;
+    let tree = parser.parse(text, None)?;
+
+    let chunker = SyntaxChunker { lang, rel_path, source: text, lines: Vec::new(), chunks: Vec::new() };
+    let mut outline = Outline::default();
+    chunker.collect(tree.root_node(), None, &mut outline);
+    Some(outline)
+}
+
+/// A definition found in the tree
+struct Definition<'t> {
+    name: String,
+    kind: &'static str,
+    /// Members to split into when the whole definition is too big
+    body: Option<Node<'t>>,
+    /// Scope implied by the definition itself (a Go method's receiver)
+    scope: Option<String>,
+}
+
+struct SyntaxChunker<'a> {
+    lang: Lang,
+    rel_path: &'a str,
+    source: &'a str,
+    lines: Vec<&'a str>,
+    chunks: Vec<Chunk>,
+}
+
+/// 1-based inclusive line span of a node
+fn line_span(node: Node) -> (usize, usize) {
+    let start = node.start_position().row + 1;
+    let end = node.end_position();
+    // A node ending at column 0 stops at the end of the previous line
+    let end_line = if end.column == 0 && end.row + 1 > start { end.row } else { end.row + 1 };
+    (start, end_line)
+}
+
+impl<'a> SyntaxChunker<'a> {
+    fn text(&self, node: Node) -> String {
+        node.utf8_text(self.source.as_bytes()).unwrap_or_default().to_string()
+    }
+
+    fn field_text(&self, node: Node, field: &str) -> Option<String> {
+        node.child_by_field_name(field).map(|n| self.text(n))
+    }
+
+    fn line_text(&self, start: usize, end: usize) -> String {
+        self.lines.get(start - 1..end.min(self.lines.len())).unwrap_or_default().join("\n")
+    }
+
+    /// Chunk the named children of `parent`, which sit in `scope`
+    fn walk(&mut self, parent: Node, scope: Option<&str>) {
+        let mut cursor = parent.walk();
+        let children: Vec<Node> = parent.named_children(&mut cursor).collect();
+
+        // Pending code between definitions, and the run of comments/attributes at its end
+        let mut gap: Option<(usize, usize)> = None;
+        let mut leading: Option<usize> = None;
+        for node in children {
+            let (start, end) = line_span(node);
+            match self.classify(node, scope.is_some()) {
+                Some(definition) => {
+                    let attached = leading.filter(|_| gap.is_some_and(|(_, gap_end)| gap_end + 1 >= start));
+                    let span_start = attached.unwrap_or(start);
+                    if let Some((gap_start, _)) = gap.take() {
+                        if span_start > gap_start {
+                            self.push_gap(gap_start, span_start - 1, scope);
+                        }
+                    }
+                    leading = None;
+                    self.push_definition(definition, span_start, end, scope);
+                }
+                None => {
+                    let contiguous = gap.is_some_and(|(_, gap_end)| gap_end + 1 >= start);
+                    if self.lang.is_leading(node.kind()) {
+                        if leading.is_none() || !contiguous {
+                            leading = Some(start);
+                        }
+                    } else {
+                        leading = None;
+                    }
+                    gap = Some(match gap {
+                        Some((gap_start, gap_end)) => (gap_start, gap_end.max(end)),
+                        None => (start, end),
+                    });
+                }
+            }
+        }
+        if let Some((gap_start, gap_end)) = gap {
+            self.push_gap(gap_start, gap_end, scope);
+        }
+    }
+
+    fn push_gap(&mut self, mut start: usize, mut end: usize, scope: Option<&str>) {
+        // Blank lines around the gap aren't part of it
+        while start < end && self.line_text(start, start).trim().is_empty() {
+            start += 1;
+        }
+        while end > start && self.line_text(end, end).trim().is_empty() {
+            end -= 1;
+        }
+        let text = self.line_text(start, end);
+        if text.trim().is_empty() {
+            return;
+        }
+        let mut pieces = if text.len() > MAX_GAP_BYTES { split_text(self.rel_path, &text, start) } else { vec![self.chunk(start, end, text)] };
+        for piece in &mut pieces {
+            piece.scope = scope.map(str::to_string);
+        }
+        self.chunks.extend(pieces);
+    }
+
+    fn push_definition(&mut self, definition: Definition, start: usize, end: usize, scope: Option<&str>) {
+        let scope = definition.scope.clone().or(scope.map(str::to_string));
+        let text = self.line_text(start, end);
+        if text.len() <= MAX_SYMBOL_BYTES {
+            let chunk = self.chunk(start, end, text);
+            self.chunks.push(with_symbol(chunk, &definition, scope));
+            return;
+        }
+
+        match definition.body {
+            Some(body) if body.named_child_count() > 0 => {
+                // The header (doc comment, signature) is its own chunk; members are chunked in the definition's scope
+                // A Python block starts at its first statement, so the header ends the line before
+                let body_line = body.start_position().row + 1;
+                let header_end = if self.lang == Lang::Python { body_line - 1 } else { body_line }.max(start);
+                let header = self.chunk(start, header_end, self.line_text(start, header_end));
+                self.chunks.push(with_symbol(header, &definition, scope.clone()));
+
+                let separator = scope_separator(Some(self.lang.name()));
+                let inner = match &scope {
+                    Some(outer) => format!("{}{}{}", outer, separator, definition.name),
+                    None => definition.name.clone(),
+                };
+                self.walk(body, Some(&inner));
+            }
+            _ => {
+                for piece in split_text(self.rel_path, &text, start) {
+                    self.chunks.push(with_symbol(piece, &definition, scope.clone()));
+                }
+            }
+        }
+    }
+
+    fn chunk(&self, start: usize, end: usize, content: String) -> Chunk {
+        Chunk { file_path: self.rel_path.to_string(), line_start: start, line_end: end, content, ..Default::default() }
+    }
+
+    /// Add the definitions and identifiers under `node`, which sits in `scope`
+    fn collect(&self, node: Node, scope: Option<&str>, outline: &mut Outline) {
+        if matches!(node.kind(), "identifier" | "type_identifier" | "field_identifier" | "property_identifier"
+            | "shorthand_property_identifier" | "shorthand_property_identifier_pattern")
+        {
+            outline.identifiers.push((self.text(node), node.start_position().row + 1));
+            return;
+        }
+
+        let mut inner = scope.map(str::to_string);
+        // Export statements and decorators are classified through the declaration they wrap
+        let definition = match node.kind() {
+            "export_statement" | "decorated_definition" => None,
+            _ => self.classify(node, scope.is_some()),
+        };
+        if let Some(definition) = definition {
+            let name = if definition.kind == "impl" {
+                // `impl<T> Display for path::Wrapper<T>` is listed under `Wrapper`
+                let name = definition.name.split('<').next().unwrap_or_default();
+                name.rsplit("::").next().unwrap_or(name).trim().to_string()
+            } else {
+                definition.name
+            };
+            let symbol_scope = definition.scope.or(scope.map(str::to_string));
+            let name_node = node.child_by_field_name("name").or_else(|| node.child_by_field_name("type")).unwrap_or(node);
+            let (line_start, line_end) = line_span(node);
+
+            // Members of a container are in its scope; locals of a function are not scoped to it
+            inner = definition.body.map(|_| match &symbol_scope {
+                Some(outer) => format!("{}{}{}", outer, scope_separator(Some(self.lang.name())), name),
+                None => name.clone(),
+            });
+            outline.symbols.push(OutlineSymbol {
+                name,
+                kind: definition.kind,
+                scope: symbol_scope,
+                name_line: name_node.start_position().row + 1,
+                line_start,
+                line_end,
+            });
+        }
+
+        let mut cursor = node.walk();
+        let children: Vec<Node> = node.named_children(&mut cursor).collect();
+        for child in children {
+            self.collect(child, inner.as_deref(), outline);
+        }
+    }
+
+    fn classify<'t>(&self, node: Node<'t>, in_scope: bool) -> Option<Definition<'t>> {
+        let function_kind = if in_scope { "method" } else { "function" };
+        let named = |kind: &'static str, body: Option<Node<'t>>| {
+            self.field_text(node, "name").map(|name| Definition { name, kind, body, scope: None })
+        };
+        let body = node.child_by_field_name("body");
+
+        match self.lang {
+            Lang::Rust => match node.kind() {
+                "function_item" | "function_signature_item" => named(function_kind, None),
+                "struct_item" => named("struct", None),
+                "enum_item" => named("enum", None),
+                "union_item" => named("union", None),
+                "macro_definition" => named("macro", None),
+                "trait_item" => named("trait", body),
+                // `mod name;` declarations are just a line
+                "mod_item" if body.is_some() => named("module", body),
+                "impl_item" => self.field_text(node, "type")
+                    .map(|name| Definition { name, kind: "impl", body, scope: None }),
+                _ => None,
+            },
+            Lang::TypeScript | Lang::Tsx | Lang::JavaScript => match node.kind() {
+                "export_statement" => node.child_by_field_name("declaration")
+                    .and_then(|declaration| self.classify(declaration, in_scope)),
+                "function_declaration" | "generator_function_declaration" | "function_signature" => named("function", None),
+                "class_declaration" | "abstract_class_declaration" => named("class", body),
+                "interface_declaration" => named("interface", None),
+                "enum_declaration" => named("enum", None),
+                "internal_module" | "module" => named("namespace", body),
+                "method_definition" | "method_signature" | "abstract_method_signature" => named("method", None),
+                "public_field_definition" | "field_definition" => {
+                    let value = node.child_by_field_name("value")?;
+                    if !matches!(value.kind(), "arrow_function" | "function_expression") {
+                        return None;
+                    }
+                    let name = self.field_text(node, "name").or_else(|| self.field_text(node, "property"))?;
+                    Some(Definition { name, kind: "method", body: None, scope: None })
+                }
+                // `const handler = () => {}` and `const Store = class {}`
+                "lexical_declaration" | "variable_declaration" => {
+                    let mut cursor = node.walk();
+                    let declarators: Vec<Node> = node.named_children(&mut cursor).filter(|n| n.kind() == "variable_declarator").collect();
+                    let [declarator] = declarators.as_slice() else { return None };
+                    let value = declarator.child_by_field_name("value")?;
+                    let (kind, body) = match value.kind() {
+                        "arrow_function" | "function_expression" | "generator_function" => (function_kind, None),
+                        "class" => ("class", value.child_by_field_name("body")),
+                        _ => return None,
+                    };
+                    let name = self.field_text(*declarator, "name")?;
+                    Some(Definition { name, kind, body, scope: None })
+                }
+                _ => None,
+            },
+            Lang::Python => match node.kind() {
+                "function_definition" => named(function_kind, None),
+                "class_definition" => named("class", body),
+                // Decorators stay with the function or class they decorate
+                "decorated_definition" => node.child_by_field_name("definition")
+                    .and_then(|definition| self.classify(definition, in_scope)),
+                _ => None,
+            },
+            Lang::Go => match node.kind() {
+                "function_declaration" => named("function", None),
+                "method_declaration" => {
+                    let receiver = node.child_by_field_name("receiver").and_then(|r| first_of_kind(r, "type_identifier"));
+                    named("method", None).map(|d| Definition { scope: receiver.map(|r| self.text(r)), ..d })
+                }
+                "type_declaration" => {
+                    let spec = first_of_kind(node, "type_spec")?;
+                    self.field_text(spec, "name").map(|name| Definition { name, kind: "type", body: None, scope: None })
+                }
+                _ => None,
+            },
+        }
+    }
+}
+
+fn with_symbol(mut chunk: Chunk, definition: &Definition, scope: Option<String>) -> Chunk {
+    chunk.symbol = Some(definition.name.clone());
+    chunk.kind = Some(definition.kind.to_string());
+    chunk.scope = scope;
+    chunk
+}
+
+/// First descendant of `node` (depth first) with the given kind
+fn first_of_kind<'t>(node: Node<'t>, kind: &str) -> Option<Node<'t>> {
+    if node.kind() == kind {
+        return Some(node);
+    }
+    let mut cursor = node.walk();
+    let children: Vec<Node<'t>> = node.named_children(&mut cursor).collect();
+    children.into_iter().find_map(|child| first_of_kind(child, kind))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// (scope, symbol, kind, line_start, line_end)
+    type Span<'a> = (Option<&'a str>, Option<&'a str>, Option<&'a str>, usize, usize);
+
+    fn symbols(chunks: &[Chunk]) -> Vec<Span<'_>> {
+        chunks.iter()
+            .map(|c| (c.scope.as_deref(), c.symbol.as_deref(), c.kind.as_deref(), c.line_start, c.line_end))
+            .collect()
+    }
+
+    #[test]
+    fn test_rust_splits_on_items_and_keeps_doc_comments() {
+        let methods: String = (0..100).map(|i| format!("    fn step_{i}(&self) -> usize {{\n        {i}\n    }}\n")).collect();
+        let text = format!(
+            "use std::fs;\n\n/// Parsed settings\n#[derive(Debug)]\npub struct Config {{\n    path: String,\n}}\n\nimpl Config {{\n{}}}\n\nfn main() {{}}\n",
+            methods
+        );
+        let chunks = chunk_source("src/config.rs", &text).unwrap();
+        let spans = symbols(&chunks);
+
+        assert_eq!(spans[0], (None, None, None, 1, 1));
+        // The doc comment and attribute belong to the struct
+        assert_eq!(spans[1], (None, Some("Config"), Some("struct"), 3, 7));
+        assert!(chunks[1].content.starts_with("/// Parsed settings\n#[derive(Debug)]"));
+        // The impl is too big for one chunk, so its header and each method are separate
+        assert_eq!(spans[2], (None, Some("Config"), Some("impl"), 9, 9));
+        assert_eq!(spans[3], (Some("Config"), Some("step_0"), Some("method"), 10, 12));
+        assert_eq!(spans[103], (None, Some("main"), Some("function"), 312, 312));
+        assert_eq!(chunks[3].qualified_symbol().as_deref(), Some("Config::step_0"));
+        assert!(chunks.iter().all(|c| c.language.as_deref() == Some("rust")));
+    }
+
+    #[test]
+    fn test_typescript_python_and_go_definitions() {
+        let ts = "import { x } from './x';\n\n// Renders the list\nexport function render() {\n  return x;\n}\n\nexport const load = async () => {};\n\nclass Store {\n  get() {}\n}\n";
+        let chunks = chunk_source("src/view.tsx", ts).unwrap();
+        let spans = symbols(&chunks);
+        assert_eq!(spans, vec![
+            (None, None, None, 1, 1),
+            (None, Some("render"), Some("function"), 3, 6),
+            (None, Some("load"), Some("function"), 8, 8),
+            (None, Some("Store"), Some("class"), 10, 12),
+        ]);
+
+        let py = "import os\n\n@cache\ndef load(path):\n    return path\n\nclass Store:\n    def get(self):\n        pass\n";
+        let chunks = chunk_source("app/store.py", py).unwrap();
+        let spans = symbols(&chunks);
+        assert_eq!(spans[1], (None, Some("load"), Some("function"), 3, 5));
+        assert_eq!(spans[2], (None, Some("Store"), Some("class"), 7, 9));
+
+        let go = "package main\n\ntype Server struct{}\n\n// Start listens\nfunc (s *Server) Start() error {\n\treturn nil\n}\n";
+        let chunks = chunk_source("cmd/server.go", go).unwrap();
+        assert_eq!(symbols(&chunks)[2], (Some("Server"), Some("Start"), Some("method"), 5, 8));
+        assert_eq!(chunks[2].qualified_symbol().as_deref(), Some("Server.Start"));
+
+        assert!(chunk_source("README.md", "# Title").is_none());
+    }
+}
@@ -0,0 +1,243 @@
+//! Watches an indexed project and re-indexes changed files in the background.
+
+use super::{chunker, IndexStore};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+/// Quiet period before re-indexing, so a save-all or a branch switch becomes one update
+const DEBOUNCE: Duration = Duration::from_millis(750);
+
+type SharedWatcher = Arc<Mutex<RecommendedWatcher>>;
+
+pub struct ProjectWatcher {
+    // Dropping the watcher stops the notifications
+    _watcher: SharedWatcher,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl ProjectWatcher {
+    pub fn start(store: Arc<IndexStore>, root: &str) -> Result<Self, String> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let mut watcher = notify::recommended_watcher(move |result: notify::Result<Event>| {
+            match result {
+                // Reads and other access events never change the index
+                Ok(event) if !matches!(event.kind, EventKind::Access(_)) => {
+                    let _ = tx.send(event.paths);
+                }
+                Ok(_) => {}
+                Err(e) => eprintln!("[RAG] Watcher error: {}", e),
+            }
+        })
+        .map_err(|e| e.to_string())?;
+        watch_dirs(&mut watcher, Path::new(root))?;
+
+        let watcher = Arc::new(Mutex::new(watcher));
+        let task = tokio::spawn(run(store, root.to_string(), watcher.clone(), rx));
+        Ok(Self { _watcher: watcher, task })
+    }
+}
+
+impl Drop for ProjectWatcher {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Watch `dir` and the directories below it one at a time, skipping ignored ones, so trees
+/// like node_modules or target don't use up the system's watch limit
+fn watch_dirs(watcher: &mut RecommendedWatcher, dir: &Path) -> Result<(), String> {
+    for dir in chunker::scan_dirs(dir) {
+        watcher.watch(&dir, RecursiveMode::NonRecursive)
+            .map_err(|e| format!("{}: {}", dir.display(), e))?;
+    }
+    Ok(())
+}
+
+async fn watch_new_dirs(watcher: &SharedWatcher, dirs: Vec<PathBuf>) -> Result<(), String> {
+    let watcher = watcher.clone();
+    tokio::task::spawn_blocking(move || {
+        let mut watcher = watcher.lock().unwrap();
+        dirs.iter().try_for_each(|dir| watch_dirs(&mut watcher, dir))
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+async fn run(store: Arc<IndexStore>, root: String, watcher: SharedWatcher, mut rx: mpsc::UnboundedReceiver<Vec<PathBuf>>) {
+    let mut filter = IgnoreFilter::new(Path::new(&root));
+    while let Some(paths) = rx.recv().await {
+        let mut pending: HashSet<PathBuf> = paths.into_iter().filter(|p| filter.is_relevant(p)).collect();
+        if pending.is_empty() {
+            continue;
+        }
+        store.mark_stale(&root, &pending);
+
+        // Only relevant events extend the quiet period, so a busy ignored directory
+        // (a running build) can't hold off re-indexing
+        let mut deadline = Instant::now() + DEBOUNCE;
+        loop {
+            match tokio::time::timeout_at(deadline, rx.recv()).await {
+                Ok(Some(paths)) => {
+                    let mut relevant = paths.into_iter().filter(|p| filter.is_relevant(p)).peekable();
+                    if relevant.peek().is_some() {
+                        deadline = Instant::now() + DEBOUNCE;
+                    }
+                    pending.extend(relevant);
+                }
+                Ok(None) => return,
+                Err(_) => break,
+            }
+        }
+        store.mark_stale(&root, &pending);
+
+        let gitignore_changed = pending.iter().any(|p| p.file_name().is_some_and(|n| n == ".gitignore"));
+        if gitignore_changed {
+            filter = IgnoreFilter::new(Path::new(&root));
+        }
+        // New directories need watches of their own, and a changed .gitignore may have un-ignored some.
+        // Files written before a watch is in place are still found by the re-index scan below.
+        let new_dirs: Vec<PathBuf> = if gitignore_changed {
+            vec![PathBuf::from(&root)]
+        } else {
+            pending.iter().filter(|p| p.is_dir()).cloned().collect()
+        };
+        if let Err(e) = watch_new_dirs(&watcher, new_dirs).await {
+            eprintln!("[RAG] Failed to watch {}", e);
+        }
+
+        // The stamp comparison in `build_index` decides what actually changed, which also
+        // covers renames and deleted directories whose contents were never reported
+        if let Err(e) = store.refresh(&root, true).await {
+            eprintln!("[RAG] Incremental re-index failed: {}", e);
+        }
+    }
+}
+
+/// Cheap pre-filter so build output and other ignored paths don't trigger re-indexing.
+/// Only the root ignore files are read; the re-index scan applies nested ones.
+struct IgnoreFilter {
+    root: PathBuf,
+    gitignore: Gitignore,
+}
+
+impl IgnoreFilter {
+    fn new(root: &Path) -> Self {
+        let mut builder = GitignoreBuilder::new(root);
+        // Missing files are fine; there is just nothing to add
+        let _ = builder.add(root.join(".gitignore"));
+        let _ = builder.add(root.join(".ignore"));
+        let gitignore = builder.build().unwrap_or_else(|_| Gitignore::empty());
+        Self { root: root.to_path_buf(), gitignore }
+    }
+
+    fn is_relevant(&self, path: &Path) -> bool {
+        let Ok(rel) = path.strip_prefix(&self.root) else {
+            return false;
+        };
+        if rel == Path::new(".gitignore") {
+            return true;
+        }
+        // Hidden paths (.git, .ifai and its index files) are never indexed
+        if rel.components().any(|c| c.as_os_str().to_string_lossy().starts_with('.')) {
+            return false;
+        }
+        !self.gitignore.matched_path_or_any_parents(rel, path.is_dir()).is_ignore()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::community::rag::semantic::tests::KeywordEmbedder;
+    use crate::community::rag::CommunityRagService;
+    use crate::core_traits::rag::RagService;
+    use crate::events::{IndexState, IndexStatusEvent};
+    use std::fs;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_ignored_and_hidden_paths_are_filtered() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+        fs::write(root.join(".gitignore"), "target/\n*.log\n").unwrap();
+        let filter = IgnoreFilter::new(root);
+
+        assert!(filter.is_relevant(&root.join("src/main.rs")));
+        assert!(filter.is_relevant(&root.join(".gitignore")));
+        assert!(!filter.is_relevant(&root.join("target/debug/app")));
+        assert!(!filter.is_relevant(&root.join("build.log")));
+        assert!(!filter.is_relevant(&root.join(".ifai/index/bm25.bin")));
+        assert!(!filter.is_relevant(Path::new("/elsewhere/file.rs")));
+    }
+
+    #[test]
+    fn test_ignored_directories_are_not_watched() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::create_dir_all(root.join("src/net")).unwrap();
+        fs::create_dir_all(root.join("target/debug/deps")).unwrap();
+        fs::create_dir_all(root.join("node_modules/react")).unwrap();
+        fs::write(root.join(".gitignore"), "target/\nnode_modules/\n").unwrap();
+
+        let mut dirs = chunker::scan_dirs(root);
+        dirs.sort();
+        assert_eq!(dirs, vec![root.to_path_buf(), root.join("src"), root.join("src/net")]);
+    }
+
+    /// Poll until the files returned for `query` satisfy `check`
+    async fn wait_for_search(service: &CommunityRagService, query: &str, check: impl Fn(&[&str]) -> bool) {
+        let mut files = Vec::new();
+        for _ in 0..100 {
+            let hits = service.search(query, 5).await.unwrap();
+            files = hits.iter().map(|h| h.split(':').next().unwrap().to_string()).collect();
+            if check(&files.iter().map(String::as_str).collect::<Vec<_>>()) {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        panic!("unexpected results for {:?}: {:?}", query, files);
+    }
+
+    #[tokio::test]
+    async fn test_changes_are_indexed_incrementally() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("src/view.ts"), "function render() {}\n").unwrap();
+        let root_str = root.to_string_lossy().to_string();
+
+        let statuses = Arc::new(Mutex::new(Vec::new()));
+        let sink = statuses.clone();
+        let service = CommunityRagService::new()
+            .with_status_listener(move |event: IndexStatusEvent| sink.lock().unwrap().push(event.state))
+            .with_embedder(Arc::new(KeywordEmbedder { calls: AtomicUsize::new(0) }));
+        service.index_project(&root_str).await.unwrap();
+
+        fs::write(root.join("src/net.rs"), "fn open_socket() {}\n").unwrap();
+        wait_for_search(&service, "socket", |files| files.first() == Some(&"src/net.rs")).await;
+        assert!(statuses.lock().unwrap().contains(&IndexState::Stale));
+        // The same refresh updated the symbol index
+        assert_eq!(service.find_symbol(&root_str, "open_socket", 5).await.unwrap()[0].file_path, "src/net.rs");
+
+        fs::rename(root.join("src/net.rs"), root.join("src/transport.rs")).unwrap();
+        wait_for_search(&service, "socket", |files| files.first() == Some(&"src/transport.rs") && !files.contains(&"src/net.rs")).await;
+
+        fs::remove_file(root.join("src/transport.rs")).unwrap();
+        wait_for_search(&service, "socket", |files| files == ["src/view.ts"]).await;
+
+        // A directory created later gets its own watch
+        fs::create_dir(root.join("lib")).unwrap();
+        tokio::time::sleep(DEBOUNCE * 3).await;
+        fs::write(root.join("lib/queue.rs"), "fn drain_queue() {}\n").unwrap();
+        wait_for_search(&service, "queue", |files| files.first() == Some(&"lib/queue.rs")).await;
+        assert!(statuses.lock().unwrap().contains(&IndexState::Ready));
+    }
+}
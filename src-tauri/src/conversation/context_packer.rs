@@ -0,0 +1,261 @@
+//! Fits the system prompt, summary, RAG references and history into the model's context window.
+//!
+//! The system prompt, summary and latest user turn are always sent. References are added
+//! whole, best-ranked first, and older history fills what is left, newest first.
+//! Nothing is cut mid-text, and everything left out is listed in a [`ContextReport`].
+
+use super::token_counter::{count_messages_tokens, count_text_tokens};
+use crate::core_traits::ai::{AIProviderConfig, Content, Message};
+use crate::core_traits::rag::RagReference;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use ts_rs::TS;
+
+const DEFAULT_CONTEXT_WINDOW: usize = 32_000;
+/// Room left for the reply, capped at a quarter of the window
+const MAX_OUTPUT_RESERVE: usize = 8_192;
+/// References get at least this share of what remains after the fixed parts, and more when history doesn't need it
+const MIN_REFERENCE_SHARE: f32 = 0.5;
+
+/// Context window of the provider's primary model: the configured size, else a guess from the model name
+pub fn context_window(config: &AIProviderConfig) -> usize {
+    config.context_window_tokens
+        .map(|tokens| tokens as usize)
+        .unwrap_or_else(|| context_window_for_model(config.models.first().map(String::as_str).unwrap_or_default()))
+}
+
+pub fn context_window_for_model(model: &str) -> usize {
+    let model = model.to_lowercase();
+    let has = |s: &str| model.contains(s);
+    if has("gemini") || has("gpt-4.1") {
+        1_000_000
+    } else if has("claude") {
+        200_000
+    } else if has("gpt-4o") || has("gpt-4-turbo") || has("o1") || has("o3") || has("o4") || has("gpt-5")
+        || has("glm-4") || has("llama3.1") || has("llama-3.1") || has("llama3.2") || has("llama3.3") {
+        128_000
+    } else if has("deepseek") || has("kimi") || has("moonshot") {
+        64_000
+    } else if has("gpt-3.5") {
+        16_000
+    } else {
+        DEFAULT_CONTEXT_WINDOW
+    }
+}
+
+/// Input tokens available for a request to this provider
+pub fn input_budget(config: &AIProviderConfig) -> usize {
+    let window = context_window(config);
+    window - MAX_OUTPUT_RESERVE.min(window / 4)
+}
+
+pub struct ContextParts {
+    pub system_prompt: String,
+    pub summary: Option<Message>,
+    /// Best first
+    pub references: Vec<RagReference>,
+    /// Conversation without system messages, oldest first
+    pub history: Vec<Message>,
+}
+
+pub struct PackedContext {
+    pub messages: Vec<Message>,
+    /// The references that made it into the system prompt
+    pub references: Vec<RagReference>,
+    pub report: ContextReport,
+}
+
+/// What was sent and what was left out, sent on `{event_id}_context`
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct ContextReport {
+    #[ts(type = "number")]
+    pub budget_tokens: usize,
+    #[ts(type = "number")]
+    pub used_tokens: usize,
+    #[ts(type = "number")]
+    pub included_references: usize,
+    pub omitted_references: Vec<OmittedReference>,
+    /// Older messages dropped from the start of the history
+    #[ts(type = "number")]
+    pub omitted_messages: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct OmittedReference {
+    pub file_path: String,
+    #[ts(type = "number")]
+    pub line_start: usize,
+    #[ts(type = "number")]
+    pub tokens: usize,
+}
+
+/// A reference as it appears in the system prompt
+fn format_reference(reference: &RagReference, root: &str) -> String {
+    if reference.file_path.is_empty() {
+        return reference.content.trim_end().to_string();
+    }
+    let path = Path::new(&reference.file_path)
+        .strip_prefix(root)
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_else(|_| reference.file_path.clone());
+    let lines = if reference.line_end > reference.line_start {
+        format!("lines {}-{}", reference.line_start, reference.line_end)
+    } else {
+        format!("line {}", reference.line_start)
+    };
+    let symbol = reference.symbol.as_ref().map(|s| format!(", {}", s)).unwrap_or_default();
+    format!("File: {} ({}{})\n```\n{}\n```", path, lines, symbol, reference.content.trim_end())
+}
+
+fn system_message(text: String) -> Message {
+    Message { role: "system".to_string(), content: Content::Text(text), tool_calls: None, tool_call_id: None, thinking_blocks: None }
+}
+
+pub fn pack(parts: ContextParts, root: &str, budget_tokens: usize) -> PackedContext {
+    const CONTEXT_HEADER: &str = "\n\nProject Context:\n";
+    const SEPARATOR: &str = "\n\n";
+
+    // The latest user turn (and any tool exchange after it) is always sent
+    let mut history = parts.history;
+    let tail_start = history.iter().rposition(|m| m.role == "user").unwrap_or(history.len());
+    let tail = history.split_off(tail_start);
+    let older = history;
+
+    let mut fixed = count_messages_tokens(&[system_message(parts.system_prompt.clone())]) + count_messages_tokens(&tail);
+    if let Some(summary) = &parts.summary {
+        fixed += count_messages_tokens(std::slice::from_ref(summary));
+    }
+    let remaining = budget_tokens.saturating_sub(fixed);
+
+    let older_tokens: Vec<usize> = older.iter().map(|m| count_messages_tokens(std::slice::from_ref(m))).collect();
+    let reference_cap = ((remaining as f32 * MIN_REFERENCE_SHARE) as usize)
+        .max(remaining.saturating_sub(older_tokens.iter().sum()));
+
+    // Whole references in rank order; the first one that doesn't fit ends the list
+    let header_tokens = count_text_tokens(CONTEXT_HEADER);
+    let mut blocks: Vec<String> = Vec::new();
+    let mut reference_tokens = 0;
+    let mut included = Vec::new();
+    let mut omitted_references = Vec::new();
+    for reference in parts.references {
+        let block = format_reference(&reference, root);
+        let tokens = count_text_tokens(&block) + count_text_tokens(SEPARATOR);
+        let with_header = if blocks.is_empty() { header_tokens } else { 0 };
+        if omitted_references.is_empty() && reference_tokens + tokens + with_header <= reference_cap {
+            reference_tokens += tokens + with_header;
+            blocks.push(block);
+            included.push(reference);
+        } else {
+            omitted_references.push(OmittedReference { file_path: reference.file_path, line_start: reference.line_start, tokens });
+        }
+    }
+
+    // Older history, newest first, until the budget runs out
+    let mut history_budget = remaining.saturating_sub(reference_tokens);
+    let mut keep_from = older.len();
+    for (i, tokens) in older_tokens.iter().enumerate().rev() {
+        if *tokens > history_budget {
+            break;
+        }
+        history_budget -= tokens;
+        keep_from = i;
+    }
+    // Tool results whose assistant turn was dropped would be rejected by the provider
+    while keep_from < older.len() && older[keep_from].role == "tool" {
+        keep_from += 1;
+    }
+    let omitted_messages = keep_from;
+
+    let mut system_prompt = parts.system_prompt;
+    if !blocks.is_empty() {
+        system_prompt.push_str(CONTEXT_HEADER);
+        system_prompt.push_str(&blocks.join(SEPARATOR));
+    }
+    let mut messages = vec![system_message(system_prompt)];
+    messages.extend(parts.summary);
+    messages.extend(older.into_iter().skip(keep_from));
+    messages.extend(tail);
+
+    let report = ContextReport {
+        budget_tokens,
+        used_tokens: count_messages_tokens(&messages),
+        included_references: included.len(),
+        omitted_references,
+        omitted_messages,
+    };
+    PackedContext { messages, references: included, report }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(role: &str, text: &str) -> Message {
+        Message { role: role.to_string(), content: Content::Text(text.to_string()), tool_calls: None, tool_call_id: None, thinking_blocks: None }
+    }
+
+    fn reference(path: &str, content: String) -> RagReference {
+        RagReference { file_path: format!("/proj/{}", path), line_start: 1, content, ..Default::default() }
+    }
+
+    fn text(message: &Message) -> &str {
+        match &message.content {
+            Content::Text(t) => t,
+            _ => "",
+        }
+    }
+
+    #[test]
+    fn test_context_window_from_config_or_model_name() {
+        let mut config = AIProviderConfig { models: vec!["claude-sonnet-4".to_string()], ..Default::default() };
+        assert_eq!(context_window(&config), 200_000);
+        config.context_window_tokens = Some(50_000);
+        assert_eq!(input_budget(&config), 50_000 - 8_192);
+        assert_eq!(context_window_for_model("some-local-model"), DEFAULT_CONTEXT_WINDOW);
+    }
+
+    #[test]
+    fn test_drops_whole_references_by_rank_and_oldest_history() {
+        // Multi-byte comments used to panic the old byte-offset truncation
+        let chinese = "// 读取配置文件并解析为结构体\n".repeat(40);
+        let references = vec![
+            reference("src/config.rs", chinese.clone()),
+            reference("src/big.rs", chinese.repeat(20)),
+            reference("src/small.rs", "fn small() {}".to_string()),
+        ];
+        let history = vec![
+            message("user", &"old question ".repeat(800)),
+            message("tool", "orphaned tool result"),
+            message("assistant", "recent answer"),
+            message("user", "where is the config parsed?"),
+        ];
+        let parts = ContextParts {
+            system_prompt: "You are a coding assistant.".to_string(),
+            summary: Some(message("system", "## CONVERSATION SUMMARY\n\nEarlier work")),
+            references,
+            history,
+        };
+
+        let budget = 2_000;
+        let packed = pack(parts, "/proj", budget);
+
+        // The first reference fits whole; the oversized one ends the list, taking the small one with it
+        assert_eq!(packed.report.included_references, 1);
+        let omitted: Vec<&str> = packed.report.omitted_references.iter().map(|r| r.file_path.as_str()).collect();
+        assert_eq!(omitted, vec!["/proj/src/big.rs", "/proj/src/small.rs"]);
+        let system = text(&packed.messages[0]);
+        assert!(system.contains("File: src/config.rs (line 1)"));
+        assert!(system.ends_with(&format!("{}```", chinese)));
+
+        // The long old question doesn't fit, and the tool result after it is dropped with it
+        assert_eq!(packed.report.omitted_messages, 2);
+        let roles: Vec<&str> = packed.messages.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles, vec!["system", "system", "assistant", "user"]);
+        assert_eq!(text(packed.messages.last().unwrap()), "where is the config parsed?");
+        assert!(packed.report.used_tokens <= budget);
+    }
+}
@@ -1,5 +1,6 @@
 pub mod token_counter;
 pub mod summarizer;
+pub mod context_packer;
 
 use crate::core_traits::ai::{Message, Content, AIProviderConfig};
 
@@ -16,10 +17,10 @@ pub async fn should_summarize(messages: &[Message]) -> bool {
     token_count > 150_000 || messages.len() > 100
 }
 
-use tauri::{AppHandle, Emitter};
+use tauri::{AppHandle, Emitter, Runtime};
 
-pub async fn auto_summarize(
-    app: &AppHandle,
+pub async fn auto_summarize<R: Runtime>(
+    app: &AppHandle<R>,
     event_id: &str,
     project_root: &str,
     provider_config: &AIProviderConfig,
@@ -54,6 +55,7 @@ pub async fn auto_summarize(
         content: Content::Text(format!("## CONVERSATION SUMMARY\n\n{}\n\n=== End of Summary ===", summary)),
         tool_calls: None,
         tool_call_id: None,
+        thinking_blocks: None,
     });
 
     // Keep the last 10 messages for context
@@ -72,3 +74,52 @@ pub async fn auto_summarize(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cassette::{Cassette, Interaction, MockServer};
+    use std::sync::{Arc, Mutex};
+    use tauri::Listener;
+
+    fn message(role: &str, text: String) -> Message {
+        Message { role: role.to_string(), content: Content::Text(text), tool_calls: None, tool_call_id: None, thinking_blocks: None }
+    }
+
+    #[tokio::test]
+    async fn test_auto_summarize_compacts_long_history() {
+        let server = MockServer::replay(Cassette::default().with(Interaction::text("- The user is refactoring the parser")))
+            .await
+            .unwrap();
+        let provider = AIProviderConfig {
+            id: "cassette".to_string(),
+            base_url: server.chat_url(),
+            models: vec!["gpt-4o-mini".to_string()],
+            ..Default::default()
+        };
+
+        let app = tauri::test::mock_app();
+        let compacted = Arc::new(Mutex::new(None));
+        let sink = compacted.clone();
+        app.listen("chat-1_compacted", move |event| {
+            let history: Vec<Message> = serde_json::from_str(event.payload()).unwrap();
+            *sink.lock().unwrap() = Some(history.len());
+        });
+
+        let mut messages: Vec<Message> = (0..120)
+            .map(|i| message(if i % 2 == 0 { "user" } else { "assistant" }, format!("message {}", i)))
+            .collect();
+        let project_root = tempfile::tempdir().unwrap();
+
+        auto_summarize(app.handle(), "chat-1", &project_root.path().to_string_lossy(), &provider, &mut messages)
+            .await
+            .unwrap();
+
+        // Summary plus the last 10 messages
+        assert_eq!(messages.len(), 11);
+        assert!(matches!(&messages[0].content, Content::Text(t) if t.contains("refactoring the parser")));
+        assert!(matches!(&messages[10].content, Content::Text(t) if t == "message 119"));
+        assert_eq!(*compacted.lock().unwrap(), Some(11));
+        assert_eq!(server.requests().len(), 1);
+    }
+}
@@ -25,10 +25,11 @@ pub async fn generate_summary(
         content: Content::Text(summary_instruction),
         tool_calls: None,
         tool_call_id: None,
+        thinking_blocks: None,
     });
 
     // 3. Call AI
-    println!("[Summarizer] Sending request to AI (Model: {})...", provider_config.models[0]);
+    println!("[Summarizer] Sending request to AI (Model: {})...", provider_config.models.first().map(|m| m.as_str()).unwrap_or("<none>"));
     match ai_utils::fetch_ai_completion(provider_config, messages, None).await {
         Ok(res_msg) => {
             if let Content::Text(summary_text) = res_msg.content {
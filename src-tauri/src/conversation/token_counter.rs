@@ -1,12 +1,10 @@
-use tiktoken_rs::cl100k_base;
+use tiktoken_rs::cl100k_base_singleton;
 use crate::core_traits::ai::{Message, Content, ContentPart};
+use crate::attachments;
 
 pub fn count_messages_tokens(messages: &[Message]) -> usize {
-    let bpe = match cl100k_base() {
-        Ok(b) => b,
-        Err(_) => return 0,
-    };
-    
+    let bpe = cl100k_base_singleton();
+
     let mut total_tokens = 0;
     
     for msg in messages {
@@ -20,9 +18,8 @@ pub fn count_messages_tokens(messages: &[Message]) -> usize {
                         ContentPart::Text { text, .. } => {
                              total_tokens += bpe.encode_with_special_tokens(text).len();
                         }
-                        _ => {
-                            // Image or other part
-                            total_tokens += 2; 
+                        ContentPart::ImageUrl { image_url } => {
+                            total_tokens += attachments::image_url_tokens(&image_url.url);
                         }
                     }
                 }
@@ -43,3 +40,7 @@ pub fn count_messages_tokens(messages: &[Message]) -> usize {
     
     total_tokens
 }
+
+pub fn count_text_tokens(text: &str) -> usize {
+    cl100k_base_singleton().encode_with_special_tokens(text).len()
+}
@@ -3,96 +3,140 @@ use serde::{Deserialize, Serialize};
 pub mod ai {
     use super::*;
 
-    #[cfg(feature = "commercial")]
-    pub use ifainew_core::ai::{Message, Content, ContentPart, ToolCall, FunctionCall, AIProviderConfig, ImageUrl, AIProtocol};
-
-    #[cfg(not(feature = "commercial"))]
-    mod community_types {
-        use super::*;
-        
-        #[derive(Debug, Clone, Serialize, Deserialize, Default)]
-        #[serde(rename_all = "lowercase")]
-        pub enum AIProtocol { #[default] Openai, Anthropic, Gemini }
-
-        #[derive(Debug, Clone, Serialize, Deserialize)]
-        pub struct ImageUrl { pub url: String }
-
-        #[derive(Debug, Clone, Serialize, Deserialize)]
-        #[serde(tag = "type", rename_all = "snake_case")]
-        pub enum ContentPart {
-            Text { 
-                text: String,
-                #[serde(default)]
-                part_type: String,
-            },
-            ImageUrl { image_url: ImageUrl },
-        }
+    // Both editions own the message and provider types: shared code reads fields the
+    // commercial core doesn't have (routing and HTTP settings, thinking blocks), so
+    // `commercial::impls` converts them at the core boundary.
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct ImageUrl { pub url: String }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    #[serde(tag = "type", rename_all = "snake_case")]
+    pub enum ContentPart {
+        Text { 
+            text: String,
+            #[serde(default)]
+            part_type: String,
+        },
+        ImageUrl { image_url: ImageUrl },
+    }
 
-        impl Default for ContentPart {
-            fn default() -> Self { Self::Text { text: String::new(), part_type: "text".to_string() } }
-        }
+    impl Default for ContentPart {
+        fn default() -> Self { Self::Text { text: String::new(), part_type: "text".to_string() } }
+    }
 
-        #[derive(Debug, Clone, Serialize, Deserialize)]
-        #[serde(untagged)]
-        pub enum Content {
-            Text(String),
-            Parts(Vec<ContentPart>),
-        }
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    #[serde(untagged)]
+    pub enum Content {
+        Text(String),
+        Parts(Vec<ContentPart>),
+    }
 
-        impl Default for Content {
-            fn default() -> Self { Self::Text(String::new()) }
-        }
+    impl Default for Content {
+        fn default() -> Self { Self::Text(String::new()) }
+    }
 
-        #[derive(Debug, Clone, Serialize, Deserialize, Default)]
-        pub struct FunctionCall { 
-            #[serde(default)] pub name: String, 
-            #[serde(default)] pub arguments: String 
-        }
+    #[derive(Debug, Clone, Serialize, Deserialize, Default)]
+    pub struct FunctionCall { 
+        #[serde(default)] pub name: String, 
+        #[serde(default)] pub arguments: String 
+    }
 
-        #[derive(Debug, Clone, Serialize, Deserialize, Default)]
-        pub struct ToolCall {
-            #[serde(default)] pub id: String,
-            #[serde(default, rename = "type")] pub r#type: String,
-            #[serde(default)] pub function: FunctionCall,
-        }
+    #[derive(Debug, Clone, Serialize, Deserialize, Default)]
+    pub struct ToolCall {
+        #[serde(default)] pub id: String,
+        #[serde(default, rename = "type")] pub r#type: String,
+        #[serde(default)] pub function: FunctionCall,
+    }
 
-        #[derive(Debug, Clone, Serialize, Deserialize, Default)]
-        pub struct Message {
-            #[serde(default)] pub role: String,
-            pub content: Content,
-            #[serde(default)] pub tool_calls: Option<Vec<ToolCall>>,
-            #[serde(default)] pub tool_call_id: Option<String>,
-        }
+    #[derive(Debug, Clone, Serialize, Deserialize, Default)]
+    pub struct Message {
+        #[serde(default)] pub role: String,
+        pub content: Content,
+        #[serde(default)] pub tool_calls: Option<Vec<ToolCall>>,
+        #[serde(default)] pub tool_call_id: Option<String>,
+        /// Signed Anthropic thinking blocks of a tool-calling turn. They must be sent back
+        /// with the turn's tool results while thinking is enabled, so they round-trip
+        /// through the frontend with the rest of the message.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub thinking_blocks: Option<Vec<serde_json::Value>>,
+    }
 
-        #[derive(Debug, Clone, Serialize, Deserialize, Default)]
-        pub struct AIProviderConfig {
-            #[serde(default)] pub id: String,
-            #[serde(default)] pub name: String,
-            #[serde(default)] pub api_key: String,
-            #[serde(default)] pub base_url: String,
-            #[serde(default)] pub models: Vec<String>,
-            #[serde(default)] pub protocol: AIProtocol,
-        }
+    #[derive(Debug, Clone, Serialize, Deserialize, Default)]
+    #[serde(rename_all = "lowercase")]
+    pub enum AIProtocol { #[default] Openai, Anthropic, Gemini }
+
+    #[derive(Debug, Clone, Serialize, Deserialize, Default)]
+    pub struct AIProviderConfig {
+        #[serde(default)] pub id: String,
+        #[serde(default)] pub name: String,
+        #[serde(default)] pub api_key: String,
+        #[serde(default)] pub base_url: String,
+        #[serde(default)] pub models: Vec<String>,
+        #[serde(default)] pub protocol: AIProtocol,
+        /// Tried in order after `models[0]` when it errors, is rate limited or runs out of context
+        #[serde(default)] pub fallback_models: Vec<String>,
+        /// Other providers to fall back to once this provider's models are exhausted
+        #[serde(default)] pub fallback_providers: Vec<AIProviderConfig>,
+        /// HTTP(S) proxy for this provider, e.g. `http://proxy.corp:8080`
+        #[serde(default)] pub proxy_url: Option<String>,
+        /// Extra headers sent with every request (org id, Azure `api-key`, gateway tokens)
+        #[serde(default)] pub extra_headers: std::collections::HashMap<String, String>,
+        /// Extra query parameters appended to every request URL (e.g. Azure `api-version`)
+        #[serde(default)] pub query_params: std::collections::HashMap<String, String>,
+        #[serde(default)] pub connect_timeout_secs: Option<u64>,
+        /// Overall request timeout. Defaults to 10 minutes for streams and 2 minutes otherwise.
+        #[serde(default)] pub request_timeout_secs: Option<u64>,
+        /// PEM file with additional root certificates, for proxies that inspect TLS
+        #[serde(default)] pub ca_cert_path: Option<String>,
+        /// Thinking budget for Anthropic and Gemini models. OpenAI-compatible reasoning
+        /// models (DeepSeek-R1, GLM, Qwen) stream their reasoning without it.
+        #[serde(default)] pub reasoning_budget_tokens: Option<u32>,
+        /// Context window of the primary model, for models whose size can't be guessed from the name
+        #[serde(default)] pub context_window_tokens: Option<u32>,
     }
 
-    #[cfg(not(feature = "commercial"))]
-    pub use community_types::*;
+    /// Which provider/model produced a response, after any fallbacks
+    #[derive(Debug, Clone, Serialize, Deserialize, Default)]
+    #[serde(rename_all = "camelCase")]
+    pub struct ModelRoute {
+        pub provider_id: String,
+        pub model: String,
+        /// Targets that failed before this one answered, as "provider/model: error"
+        #[serde(default)] pub failed_attempts: Vec<String>,
+    }
 
     #[async_trait::async_trait]
     pub trait AIService: Send + Sync {
         async fn chat(&self, config: &AIProviderConfig, messages: Vec<Message>) -> Result<Message, String>;
+        /// Like `chat`, but also reports the model that answered. Services without fallback
+        /// support always answer with the primary model.
+        async fn chat_routed(&self, config: &AIProviderConfig, messages: Vec<Message>) -> Result<(Message, ModelRoute), String> {
+            let route = ModelRoute {
+                provider_id: config.id.clone(),
+                model: config.models.first().cloned().unwrap_or_default(),
+                failed_attempts: vec![],
+            };
+            Ok((self.chat(config, messages).await?, route))
+        }
         async fn stream_chat(&self, config: &AIProviderConfig, messages: Vec<Message>, event_id: &str, callback: Box<dyn Fn(String) + Send>) -> Result<(), String>;
     }
 }
 
 pub mod rag {
     use super::*;
+    use crate::community::rag::symbols::{SymbolDefinition, SymbolReference};
+    use crate::events::IndexStatus;
 
     #[derive(Debug, Clone, Serialize, Deserialize, Default)]
     pub struct RagReference { 
         #[serde(default)] pub file_path: String, 
         #[serde(default)] pub line_start: usize, 
-        #[serde(default)] pub content: String 
+        /// Last line of the span, inclusive; 0 when unknown
+        #[serde(default)] pub line_end: usize,
+        #[serde(default)] pub content: String,
+        /// Qualified name of the definition the span covers, e.g. `IndexStore::refresh`
+        #[serde(default)] pub symbol: Option<String>,
+        #[serde(default)] pub language: Option<String>,
     }
 
     #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -106,6 +150,40 @@ pub mod rag {
         async fn index_project(&self, root: &str) -> Result<(), String>;
         async fn search(&self, query: &str, top_k: usize) -> Result<Vec<String>, String>;
         async fn retrieve_context(&self, query: &str, root: &str) -> Result<RagResult, String>;
+
+        /// Best chunks for `query` with their locations, for hybrid search.
+        /// The default wraps `search`, whose results carry no location.
+        async fn search_chunks(&self, query: &str, _root: &str, top_k: usize) -> Result<Vec<RagReference>, String> {
+            Ok(self.search(query, top_k).await?
+                .into_iter()
+                .map(|content| RagReference { content, ..Default::default() })
+                .collect())
+        }
+
+        /// Build state, progress and size of the project's index, so callers can check readiness instead of waiting
+        async fn index_status(&self, _root: &str) -> Result<IndexStatus, String> {
+            Err("Index status is not available for this RAG service".to_string())
+        }
+
+        /// Stop the project's running index build; false when none is running
+        fn cancel_index(&self, _root: &str) -> bool {
+            false
+        }
+
+        /// Stop updating the project's index as its files change, e.g. once it is closed; false when it wasn't watched
+        fn stop_watching(&self, _root: &str) -> bool {
+            false
+        }
+
+        /// Definitions matching `query` from the project's symbol index; needs no language server
+        async fn find_symbol(&self, _root: &str, _query: &str, _limit: usize) -> Result<Vec<SymbolDefinition>, String> {
+            Err("Symbol search is not available for this RAG service".to_string())
+        }
+
+        /// Lines mentioning `name`, with their source text
+        async fn find_references(&self, _root: &str, _name: &str, _limit: usize) -> Result<Vec<SymbolReference>, String> {
+            Err("Symbol search is not available for this RAG service".to_string())
+        }
     }
 }
 
@@ -0,0 +1,464 @@
+//! Typed event protocol shared with the frontend.
+//!
+//! Chat streams send [`ChatEvent`]s (as JSON strings) on the request's `event_id`.
+//! Agents send [`AgentEvent`]s on `agent_{id}`, plus [`AgentStatusEvent`] on `agent:status`
+//! and [`AgentResultEvent`] on `agent:result` for global listeners.
+//! Community RAG index changes are sent as [`IndexStatusEvent`] on [`INDEX_STATUS_EVENT`],
+//! and the progress of a running build as [`IndexProgressEvent`] on [`INDEX_PROGRESS_EVENT`].
+//!
+//! Every payload carries `v` = [`PROTOCOL_VERSION`]. TypeScript definitions are generated
+//! into `src/types/bindings` by `cargo test`; bump the version when a change would break
+//! an older frontend.
+
+use crate::ai_utils::retry::RetryNotice;
+use crate::ai_utils::routing::FallbackNotice;
+use crate::usage_ledger::TokenUsage;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use tauri::{AppHandle, Emitter, Runtime};
+use ts_rs::TS;
+
+pub const PROTOCOL_VERSION: u32 = 1;
+
+pub const INDEX_STATUS_EVENT: &str = "rag:index_status";
+pub const INDEX_PROGRESS_EVENT: &str = "rag:index_progress";
+
+/// A chat stream payload: `{"v":1,"type":"content","content":"..."}`
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ChatEvent {
+    pub v: u32,
+    #[serde(flatten)]
+    pub kind: ChatEventKind,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[serde(tag = "type", rename_all = "snake_case")]
+#[ts(export)]
+pub enum ChatEventKind {
+    Content { content: String },
+    /// Reasoning/thinking text from reasoning models; not part of the answer
+    Reasoning { content: String },
+    /// A fragment of a tool call; the frontend merges fragments by id
+    ToolCall { tool_call: ToolCallDelta },
+    /// Signed thinking of a tool-calling turn, sent once the turn is complete. The frontend
+    /// keeps it on the message and sends it back as `thinking_blocks` with the tool results.
+    ThinkingBlocks { thinking_blocks: Vec<Value> },
+    Warning { message: String },
+    Retry { retry: RetryNotice, message: String },
+    Fallback { fallback: FallbackNotice, message: String },
+    Done {
+        usage: Option<TokenUsage>,
+        provider: String,
+        model: String,
+    },
+    Cancelled,
+}
+
+/// OpenAI-shaped tool call fragment, as sent on chat streams
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ToolCallDelta {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub function: FunctionDelta,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct FunctionDelta {
+    pub name: String,
+    pub arguments: String,
+}
+
+impl ChatEvent {
+    pub fn new(kind: ChatEventKind) -> Self {
+        Self { v: PROTOCOL_VERSION, kind }
+    }
+
+    /// Chat payloads travel as JSON strings
+    pub fn to_payload(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+impl ToolCallDelta {
+    pub fn function(id: String, name: String, arguments: String) -> Self {
+        Self { id, kind: "function".to_string(), function: FunctionDelta { name, arguments } }
+    }
+}
+
+/// An agent payload on `agent_{id}`: `{"v":1,"type":"thinking","content":"..."}`
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct AgentEvent {
+    pub v: u32,
+    #[serde(flatten)]
+    pub kind: AgentEventKind,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[serde(tag = "type", rename_all = "snake_case")]
+#[ts(export)]
+pub enum AgentEventKind {
+    /// Streamed assistant text
+    Thinking { content: String },
+    /// Reasoning/thinking text from reasoning models
+    Reasoning { content: String },
+    #[serde(rename_all = "camelCase")]
+    ToolCall { tool_call: AgentToolCall },
+    Status {
+        status: AgentRunStatus,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional)]
+        progress: Option<f32>,
+    },
+    Log { message: String },
+    Warning { message: String },
+    Retry { retry: RetryNotice, message: String },
+    Fallback { fallback: FallbackNotice, message: String },
+    #[serde(rename_all = "camelCase")]
+    ExploreProgress { explore_progress: ExploreProgress },
+    #[serde(rename_all = "camelCase")]
+    ExploreFindings { explore_findings: ExploreFindings },
+    Result { result: String },
+    Error { error: String },
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct AgentToolCall {
+    pub id: String,
+    pub tool: String,
+    /// Parsed arguments; partial calls carry whatever fields could be recovered so far
+    pub args: Value,
+    pub is_partial: bool,
+    /// Read-only calls run without waiting for the user; always false while partial
+    pub auto_approved: bool,
+}
+
+/// Agent lifecycle as shown in the UI
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, TS)]
+#[serde(rename_all = "lowercase")]
+#[ts(export)]
+pub enum AgentRunStatus {
+    Running,
+    WaitingForTool,
+    Completed,
+    Failed,
+    Stopped,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct ExploreProgress {
+    pub phase: ExploreStage,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub current_path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub current_file: Option<String>,
+    pub progress: ScanProgress,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, TS)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum ExploreStage {
+    Scanning,
+    Analyzing,
+    Completed,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct ScanProgress {
+    #[ts(type = "number")]
+    pub total: usize,
+    #[ts(type = "number")]
+    pub scanned: usize,
+    pub by_directory: HashMap<String, DirectoryScan>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct DirectoryScan {
+    #[ts(type = "number")]
+    pub total: usize,
+    #[ts(type = "number")]
+    pub scanned: usize,
+    pub status: DirectoryScanStatus,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, TS)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum DirectoryScanStatus {
+    Pending,
+    Scanning,
+    Completed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ExploreFindings {
+    pub summary: String,
+    pub directories: Vec<DirectoryFinding>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct DirectoryFinding {
+    pub path: String,
+    #[ts(type = "number")]
+    pub file_count: usize,
+    pub key_files: Vec<String>,
+}
+
+/// Payload of the global `agent:status` event
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct AgentStatusEvent {
+    pub v: u32,
+    pub id: String,
+    pub status: AgentRunStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub progress: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub error: Option<String>,
+}
+
+/// Payload of the global `agent:result` event
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct AgentResultEvent {
+    pub v: u32,
+    pub id: String,
+    pub output: String,
+}
+
+/// Payload of the global `rag:index_status` event
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct IndexStatusEvent {
+    pub v: u32,
+    pub root: String,
+    pub state: IndexState,
+    /// Changed files not yet reflected in the index
+    #[ts(type = "number")]
+    pub pending_files: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, TS)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum IndexState {
+    /// No index has been built for the project yet
+    NotIndexed,
+    /// Files changed since the last update; results may be out of date
+    Stale,
+    Indexing,
+    Ready,
+    Failed,
+    /// The last build was cancelled; the index from before it, if any, is still used
+    Cancelled,
+}
+
+/// Payload of the global `rag:index_progress` event, sent as a build works through files.
+/// Per-file updates are throttled; the start of each phase and its last file are always sent.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct IndexProgressEvent {
+    pub v: u32,
+    pub root: String,
+    pub phase: IndexPhase,
+    #[ts(type = "number")]
+    pub processed: usize,
+    #[ts(type = "number")]
+    pub total: usize,
+    /// The file just processed, relative to the root
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub file: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, TS)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum IndexPhase {
+    /// Walking the project for indexable files
+    Scan,
+    /// Splitting new and changed files into chunks
+    Chunk,
+    /// Embedding content that has no vectors yet
+    Embed,
+    /// Writing the indexes to `.ifai/index`
+    Persist,
+}
+
+/// Response of `rag_index_status`
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct IndexStatus {
+    pub root: String,
+    pub state: IndexState,
+    /// Where the running build is; None when no build is running
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub progress: Option<IndexProgressEvent>,
+    #[ts(type = "number")]
+    pub indexed_files: usize,
+    #[ts(type = "number")]
+    pub chunks: usize,
+    /// Files added, changed or removed since the last build
+    #[ts(type = "number")]
+    pub stale_files: usize,
+    /// Unix time in milliseconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional, type = "number")]
+    pub last_build_ms: Option<u64>,
+    /// Size of `.ifai/index`
+    #[ts(type = "number")]
+    pub on_disk_bytes: u64,
+    /// Whether there is a semantic index, not just the keyword one
+    pub semantic: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub error: Option<String>,
+}
+
+impl IndexStatus {
+    /// Whether searches can be answered now, from an index built earlier
+    pub fn is_searchable(&self) -> bool {
+        self.last_build_ms.is_some()
+    }
+}
+
+fn retry_message(retry: &RetryNotice) -> String {
+    format!("{}, retrying in {:.1}s ({}/{})",
+        retry.reason, retry.delay_ms as f64 / 1000.0, retry.attempt, retry.max_retries)
+}
+
+fn fallback_message(fallback: &FallbackNotice) -> String {
+    format!("{} unavailable, switching to {}", fallback.from_model, fallback.to_model)
+}
+
+impl ChatEventKind {
+    pub fn retry(retry: RetryNotice) -> Self {
+        Self::Retry { message: retry_message(&retry), retry }
+    }
+
+    pub fn fallback(fallback: FallbackNotice) -> Self {
+        Self::Fallback { message: fallback_message(&fallback), fallback }
+    }
+}
+
+impl AgentEventKind {
+    pub fn retry(retry: RetryNotice) -> Self {
+        Self::Retry { message: retry_message(&retry), retry }
+    }
+
+    pub fn fallback(fallback: FallbackNotice) -> Self {
+        Self::Fallback { message: fallback_message(&fallback), fallback }
+    }
+}
+
+/// Send an event on an agent's `agent_{id}` channel
+pub fn emit_agent<R: Runtime>(app: &AppHandle<R>, event_name: &str, kind: AgentEventKind) {
+    let event = AgentEvent { v: PROTOCOL_VERSION, kind };
+    if let Err(e) = app.emit(event_name, event) {
+        eprintln!("[Events] Failed to emit on {}: {}", event_name, e);
+    }
+}
+
+/// Send a status update on both the agent's channel and the global `agent:status` channel
+pub fn emit_agent_status<R: Runtime>(
+    app: &AppHandle<R>,
+    event_name: &str,
+    id: &str,
+    status: AgentRunStatus,
+    progress: Option<f32>,
+    error: Option<String>,
+) {
+    let _ = app.emit("agent:status", AgentStatusEvent {
+        v: PROTOCOL_VERSION,
+        id: id.to_string(),
+        status,
+        progress,
+        error,
+    });
+    // Failures are reported to the agent channel as an `error` event instead
+    if status != AgentRunStatus::Failed {
+        emit_agent(app, event_name, AgentEventKind::Status { status, progress });
+    }
+}
+
+/// Send the final output on the agent's channel and on the global `agent:result` channel
+pub fn emit_agent_result<R: Runtime>(app: &AppHandle<R>, event_name: &str, id: &str, output: String) {
+    emit_agent(app, event_name, AgentEventKind::Result { result: output.clone() });
+    let _ = app.emit("agent:result", AgentResultEvent { v: PROTOCOL_VERSION, id: id.to_string(), output });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_wire_format_matches_frontend() {
+        let event = AgentEvent {
+            v: PROTOCOL_VERSION,
+            kind: AgentEventKind::ToolCall {
+                tool_call: AgentToolCall {
+                    id: "call_1".to_string(),
+                    tool: "agent_read_file".to_string(),
+                    args: json!({ "rel_path": "a.rs" }),
+                    is_partial: true,
+                    auto_approved: false,
+                },
+            },
+        };
+        assert_eq!(serde_json::to_value(&event).unwrap(), json!({
+            "v": 1,
+            "type": "tool_call",
+            "toolCall": { "id": "call_1", "tool": "agent_read_file", "args": { "rel_path": "a.rs" }, "isPartial": true, "autoApproved": false }
+        }));
+
+        let status = AgentEvent { v: 1, kind: AgentEventKind::Status { status: AgentRunStatus::WaitingForTool, progress: None } };
+        assert_eq!(serde_json::to_value(&status).unwrap(), json!({ "v": 1, "type": "status", "status": "waitingfortool" }));
+
+        let chat = ChatEvent::new(ChatEventKind::ToolCall {
+            tool_call: ToolCallDelta::function("call_2".to_string(), "search".to_string(), "{\"q\":".to_string()),
+        });
+        let parsed: Value = serde_json::from_str(&chat.to_payload()).unwrap();
+        assert_eq!(parsed, json!({
+            "v": 1,
+            "type": "tool_call",
+            "tool_call": { "id": "call_2", "type": "function", "function": { "name": "search", "arguments": "{\"q\":" } }
+        }));
+    }
+
+    #[test]
+    fn test_events_round_trip() {
+        let payload = ChatEvent::new(ChatEventKind::Cancelled).to_payload();
+        assert_eq!(payload, r#"{"v":1,"type":"cancelled"}"#);
+        let parsed: ChatEvent = serde_json::from_str(&payload).unwrap();
+        assert!(matches!(parsed.kind, ChatEventKind::Cancelled));
+    }
+}
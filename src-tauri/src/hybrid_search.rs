@@ -0,0 +1,314 @@
+//! Hybrid code search: text matches from ripgrep fused with semantic hits
+//! using reciprocal rank fusion (RRF).
+
+use crate::core_traits::rag::{RagReference, RagService};
+use crate::search::{self, MatchResult};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::Path;
+
+/// RRF damping constant; 60 is the value from the original paper
+const RRF_K: f32 = 60.0;
+/// How many results from each source take part in the fusion
+const CANDIDATES_PER_SOURCE: usize = 50;
+const MAX_KEYWORDS: usize = 3;
+
+/// Natural-language words too common to be worth grepping for
+const STOP_WORDS: [&str; 20] = [
+    "where", "what", "which", "does", "there", "this", "that", "with", "from", "into",
+    "when", "code", "file", "files", "function", "find", "show", "have", "about", "used",
+];
+
+#[derive(Serialize, Clone, Debug)]
+pub struct HybridHit {
+    pub file_path: String,
+    /// 1-based, inclusive
+    pub line_start: usize,
+    pub line_end: usize,
+    pub content: String,
+    pub score: f32,
+    /// Why this hit matched, strongest first
+    pub reasons: Vec<MatchReason>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum MatchReason {
+    /// The line defines the identifier (`fn name`, `class Name`, ...)
+    Definition { identifier: String },
+    /// The line contains the term as a whole word
+    Text { term: String },
+    /// The chunk is among the nearest semantic neighbours of the query
+    Semantic { rank: usize },
+}
+
+/// Grep and semantic search side by side, fused with RRF.
+/// Semantic chunks that contain a text match are folded into that match, so each place
+/// in the code shows up once; an exact identifier query lists its definition first.
+pub async fn hybrid_search(rag: &dyn RagService, root: &str, query: &str, limit: usize) -> Result<Vec<HybridHit>, String> {
+    let terms = query_terms(query);
+
+    let grep_root = root.to_string();
+    let grep_terms = terms.clone();
+    let text_matches = tokio::task::spawn_blocking(move || grep_terms_in(&grep_root, &grep_terms))
+        .await
+        .map_err(|e| format!("Task join error: {}", e))?;
+
+    let semantic = match rag.search_chunks(query, root, CANDIDATES_PER_SOURCE).await {
+        Ok(chunks) => chunks,
+        Err(e) => {
+            eprintln!("[HybridSearch] Semantic search failed, using text matches only: {}", e);
+            vec![]
+        }
+    };
+
+    let exact_identifier = (terms.len() == 1 && is_single_identifier(query)).then(|| terms[0].clone());
+    Ok(fuse(&terms, text_matches, semantic, exact_identifier.as_deref(), limit))
+}
+
+/// Identifiers in the query, or its longer words when it has none.
+/// A query that is a single word is always searched as-is.
+fn query_terms(query: &str) -> Vec<String> {
+    let words: Vec<&str> = query
+        .split(|c: char| !(c.is_alphanumeric() || c == '_' || c == ':' || c == '.'))
+        .map(|w| w.trim_matches([':', '.']))
+        .filter(|w| !w.is_empty())
+        .collect();
+
+    // Qualified names (`git::convert_git2_status`, `self.render`) are searched by their last segment
+    let last_segment = |w: &str| w.rsplit([':', '.']).next().unwrap_or(w).to_string();
+
+    if words.len() == 1 {
+        return vec![last_segment(words[0])];
+    }
+
+    let mut terms: Vec<String> = words.iter()
+        .filter(|w| looks_like_identifier(w))
+        .map(|w| last_segment(w))
+        .collect();
+    if terms.is_empty() {
+        terms = words.iter()
+            .filter(|w| w.chars().count() >= 4 && !STOP_WORDS.contains(&w.to_lowercase().as_str()))
+            .take(MAX_KEYWORDS)
+            .map(|w| w.to_string())
+            .collect();
+    }
+    terms.dedup();
+    terms
+}
+
+fn looks_like_identifier(word: &str) -> bool {
+    let inner_upper = word.chars().skip(1).any(|c| c.is_uppercase()) && word.chars().any(|c| c.is_lowercase());
+    word.contains('_') || word.contains("::") || inner_upper
+        || (word.chars().any(|c| c.is_ascii_digit()) && word.chars().any(|c| c.is_alphabetic()))
+}
+
+fn is_single_identifier(query: &str) -> bool {
+    let trimmed = query.trim();
+    !trimmed.is_empty() && !trimmed.contains(char::is_whitespace) && trimmed.chars().any(|c| c.is_alphabetic())
+}
+
+/// Whole-word matches for any of the terms
+fn grep_terms_in(root: &str, terms: &[String]) -> Vec<MatchResult> {
+    if terms.is_empty() {
+        return vec![];
+    }
+    let alternation = terms.iter().map(|t| regex::escape(t)).collect::<Vec<_>>().join("|");
+    match search::grep_search(root, &format!(r"\b(?:{})\b", alternation)) {
+        Ok(matches) => matches,
+        Err(e) => {
+            eprintln!("[HybridSearch] Text search failed: {}", e);
+            vec![]
+        }
+    }
+}
+
+/// Patterns for lines that define each term: `fn name`, `class Name`, `func (r *T) Name`, `name = function`, ...
+pub(crate) fn definition_patterns(terms: &[String]) -> Vec<(&String, regex::Regex)> {
+    terms.iter()
+        .filter_map(|term| {
+            let id = regex::escape(term);
+            let pattern = format!(
+                r"(?:\b(?:fn|struct|enum|trait|type|impl|mod|class|interface|def|func|function|const|static|let|var|val)\s+(?:mut\s+)?{id}\b)|(?:\bfunc\s*\([^)]*\)\s*{id}\b)|(?:^\s*(?:export\s+)?(?:async\s+)?{id}\s*[:=]\s*(?:async\s*)?(?:function\b|\())",
+            );
+            regex::Regex::new(&pattern).ok().map(|re| (term, re))
+        })
+        .collect()
+}
+
+struct Candidate {
+    hit: HybridHit,
+    is_definition_of_query: bool,
+}
+
+fn rrf(rank: usize) -> f32 {
+    1.0 / (RRF_K + rank as f32 + 1.0)
+}
+
+fn fuse(
+    terms: &[String],
+    text_matches: Vec<MatchResult>,
+    semantic: Vec<RagReference>,
+    exact_identifier: Option<&str>,
+    limit: usize,
+) -> Vec<HybridHit> {
+    // Text matches come in walk order, so rank them: definitions first, then lines matching more terms
+    let definitions = definition_patterns(terms);
+    let mut text: Vec<(MatchResult, Option<&String>, usize)> = text_matches.into_iter()
+        .map(|m| {
+            let definition = definitions.iter().find(|(_, re)| re.is_match(&m.content)).map(|(term, _)| *term);
+            let matched = terms.iter().filter(|t| m.content.contains(t.as_str())).count();
+            (m, definition, matched)
+        })
+        .collect();
+    text.sort_by(|a, b| b.1.is_some().cmp(&a.1.is_some()).then(b.2.cmp(&a.2)));
+    text.truncate(CANDIDATES_PER_SOURCE);
+
+    let mut candidates: Vec<Candidate> = Vec::new();
+    let mut seen_lines: HashSet<(String, usize)> = HashSet::new();
+    for (rank, (m, definition, _)) in text.into_iter().enumerate() {
+        let line = m.line_number as usize;
+        if !seen_lines.insert((m.path.clone(), line)) {
+            continue;
+        }
+        let mut reasons: Vec<MatchReason> = Vec::new();
+        if let Some(identifier) = definition {
+            reasons.push(MatchReason::Definition { identifier: identifier.clone() });
+        }
+        reasons.extend(terms.iter()
+            .filter(|t| m.content.contains(t.as_str()))
+            .map(|t| MatchReason::Text { term: t.clone() }));
+
+        candidates.push(Candidate {
+            is_definition_of_query: definition.is_some_and(|d| Some(d.as_str()) == exact_identifier),
+            hit: HybridHit {
+                file_path: m.path,
+                line_start: line,
+                line_end: line,
+                content: m.content.trim_end().to_string(),
+                score: rrf(rank),
+                reasons,
+            },
+        });
+    }
+
+    for (rank, chunk) in semantic.into_iter().enumerate() {
+        let reason = MatchReason::Semantic { rank: rank + 1 };
+        let line_start = chunk.line_start;
+        let line_end = if chunk.line_end >= line_start {
+            chunk.line_end
+        } else {
+            line_start + chunk.content.trim_end().lines().count().saturating_sub(1)
+        };
+
+        // Fold the chunk into the best text match inside it, if there is one
+        let inside = candidates.iter_mut()
+            .filter(|c| same_file(&c.hit.file_path, &chunk.file_path)
+                && c.hit.line_start >= line_start && c.hit.line_end <= line_end)
+            .max_by(|a, b| a.hit.score.total_cmp(&b.hit.score));
+        match inside {
+            Some(candidate) => {
+                if !candidate.hit.reasons.iter().any(|r| matches!(r, MatchReason::Semantic { .. })) {
+                    candidate.hit.score += rrf(rank);
+                    candidate.hit.reasons.push(reason);
+                }
+            }
+            None => {
+                // Identical chunks (same file and range) from the vector index are kept once
+                let duplicate = candidates.iter().any(|c| same_file(&c.hit.file_path, &chunk.file_path)
+                    && c.hit.line_start == line_start && c.hit.line_end == line_end);
+                if !duplicate {
+                    candidates.push(Candidate {
+                        is_definition_of_query: false,
+                        hit: HybridHit {
+                            file_path: chunk.file_path,
+                            line_start,
+                            line_end,
+                            content: chunk.content,
+                            score: rrf(rank),
+                            reasons: vec![reason],
+                        },
+                    });
+                }
+            }
+        }
+    }
+
+    candidates.sort_by(|a, b| b.is_definition_of_query.cmp(&a.is_definition_of_query)
+        .then(b.hit.score.total_cmp(&a.hit.score))
+        .then_with(|| a.hit.file_path.cmp(&b.hit.file_path))
+        .then(a.hit.line_start.cmp(&b.hit.line_start)));
+    candidates.into_iter().take(limit).map(|c| c.hit).collect()
+}
+
+/// Grep reports absolute paths; vector hits may be absolute or relative to the root
+fn same_file(a: &str, b: &str) -> bool {
+    a == b || Path::new(a).ends_with(b) || Path::new(b).ends_with(a)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core_traits::rag::RagResult;
+    use std::fs;
+
+    /// Returns fixed chunks, like a vector index that ranks the caller first
+    struct FixedRag(Vec<RagReference>);
+
+    #[async_trait::async_trait]
+    impl RagService for FixedRag {
+        async fn index_project(&self, _root: &str) -> Result<(), String> {
+            Ok(())
+        }
+        async fn search(&self, _query: &str, _top_k: usize) -> Result<Vec<String>, String> {
+            Ok(vec![])
+        }
+        async fn retrieve_context(&self, _query: &str, _root: &str) -> Result<RagResult, String> {
+            Ok(RagResult::default())
+        }
+        async fn search_chunks(&self, _query: &str, _root: &str, _top_k: usize) -> Result<Vec<RagReference>, String> {
+            Ok(self.0.clone())
+        }
+    }
+
+    #[test]
+    fn test_query_terms() {
+        assert_eq!(query_terms("convert_git2_status"), vec!["convert_git2_status"]);
+        assert_eq!(query_terms("git::convert_git2_status"), vec!["convert_git2_status"]);
+        assert_eq!(query_terms("where is parseConfig called"), vec!["parseConfig"]);
+        assert_eq!(query_terms("where does the terminal resize"), vec!["terminal", "resize"]);
+    }
+
+    #[tokio::test]
+    async fn test_definition_ranks_first_and_chunks_are_merged() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+        fs::create_dir_all(root.join("src")).unwrap();
+        let caller = "use crate::git::convert_git2_status;\n\nfn statuses() {\n    let s = convert_git2_status(raw);\n    let t = convert_git2_status(other);\n}\n";
+        fs::write(root.join("src/a_caller.rs"), caller).unwrap();
+        fs::write(root.join("src/git.rs"), "// helpers\npub fn convert_git2_status(status: Status) -> GitStatus {\n    GitStatus::Clean\n}\n").unwrap();
+        let root_str = root.to_string_lossy().to_string();
+
+        let rag = FixedRag(vec![
+            RagReference { file_path: root.join("src/a_caller.rs").to_string_lossy().to_string(), line_start: 1, content: caller.to_string(), ..Default::default() },
+            RagReference { file_path: root.join("src/git.rs").to_string_lossy().to_string(), line_start: 1, content: "// helpers\npub fn convert_git2_status(status: Status) -> GitStatus {".to_string(), ..Default::default() },
+            RagReference { file_path: root.join("src/notes.md").to_string_lossy().to_string(), line_start: 3, content: "status icons".to_string(), ..Default::default() },
+        ]);
+        let hits = hybrid_search(&rag, &root_str, "convert_git2_status", 10).await.unwrap();
+
+        assert!(hits[0].file_path.ends_with("src/git.rs"));
+        assert_eq!((hits[0].line_start, hits[0].line_end), (2, 2));
+        assert_eq!(hits[0].reasons, vec![
+            MatchReason::Definition { identifier: "convert_git2_status".to_string() },
+            MatchReason::Text { term: "convert_git2_status".to_string() },
+            MatchReason::Semantic { rank: 2 },
+        ]);
+
+        // The caller's chunk is folded into one of its three text matches rather than listed again
+        let caller_hits: Vec<&HybridHit> = hits.iter().filter(|h| h.file_path.ends_with("a_caller.rs")).collect();
+        assert_eq!(caller_hits.len(), 3);
+        assert_eq!(caller_hits.iter().filter(|h| h.reasons.contains(&MatchReason::Semantic { rank: 1 })).count(), 1);
+        assert!(hits.iter().any(|h| h.file_path.ends_with("notes.md") && h.reasons == vec![MatchReason::Semantic { rank: 3 }]));
+        assert_eq!(hits.len(), 5);
+    }
+}
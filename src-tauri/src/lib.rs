@@ -5,6 +5,8 @@ use ifainew_core;
 mod file_walker;
 mod terminal;
 mod search;
+mod hybrid_search;
+mod mentions;
 mod git;
 mod lsp;
 mod prompt_manager;
@@ -16,6 +18,11 @@ mod performance;
 mod core_traits;
 mod project_config;
 mod community;
+mod usage_ledger;
+mod attachments;
+#[cfg(any(test, debug_assertions))]
+mod cassette;
+mod events;
 #[cfg(feature = "commercial")]
 mod commercial;
 
@@ -59,10 +66,56 @@ async fn ai_chat(
     app: tauri::AppHandle,
     state: tauri::State<'_, AppState>,
     provider_config: core_traits::ai::AIProviderConfig,
-    mut messages: Vec<core_traits::ai::Message>,
+    messages: Vec<core_traits::ai::Message>,
     event_id: String,
     enable_tools: Option<bool>,
     project_root: Option<String>,
+    model: Option<String>,
+    providers: Option<Vec<core_traits::ai::AIProviderConfig>>,
+    conversation_id: Option<String>,
+) -> Result<(), String> {
+    // IFAI.md provider/model overrides and per-request model choice; the provider's fallback chain still applies after them
+    let (provider_config, settings) = project_config::resolve_settings(
+        project_root.as_deref(),
+        provider_config,
+        &providers.unwrap_or_default(),
+        model.as_deref(),
+    );
+    println!("[AI Chat] Effective settings: {:?}", settings);
+    let _ = app.emit(&format!("{}_settings", event_id), &settings);
+
+    // Registered for the whole pipeline so summarization, RAG and streaming all stop on cancel
+    let registration = ai_utils::cancel::register(&event_id);
+    // Token usage of every call in the pipeline is billed to the conversation (chat thread);
+    // the event id only names this one response
+    let usage_ctx = usage_ledger::UsageContext {
+        project_root: project_root.clone().unwrap_or_default(),
+        conversation_id,
+        agent_id: None,
+    };
+    let chat = usage_ledger::scope(
+        usage_ctx,
+        run_ai_chat(app.clone(), state, provider_config, messages, event_id.clone(), project_root, settings.rag_enabled),
+    );
+
+    match ai_utils::cancel::with_cancellation(&registration.token(), chat).await {
+        Ok(result) => result,
+        Err(_) => {
+            println!("[AI Chat] Request {} cancelled", event_id);
+            let _ = app.emit(&event_id, events::ChatEvent::new(events::ChatEventKind::Cancelled).to_payload());
+            Ok(())
+        }
+    }
+}
+
+async fn run_ai_chat<R: tauri::Runtime>(
+    app: tauri::AppHandle<R>,
+    state: tauri::State<'_, AppState>,
+    provider_config: core_traits::ai::AIProviderConfig,
+    mut messages: Vec<core_traits::ai::Message>,
+    event_id: String,
+    project_root: Option<String>,
+    rag_enabled: bool,
 ) -> Result<(), String> {
     println!("[AI Chat] Entry - project_root: {:?}, event_id: {}", project_root, event_id);
     println!("[AI Chat] Received {} messages", messages.len());
@@ -72,10 +125,28 @@ async fn ai_chat(
     ai_utils::sanitize_messages(&mut messages);
     println!("[AI Chat] After sanitize: {} messages", messages.len());
 
+    // Local image paths (dropped files, .ifai/attachments) become data URLs
+    let mut messages = attachments::inline_local_images_async(messages, project_root.clone()).await?;
+
     if let Some(root) = project_root {
         let root_clone = root.clone();
 
-        // 1. Detect @codebase query or smart RAG trigger
+        // 1. Resolve @file, @folder, @diff, @terminal and @symbol mentions in the latest user message
+        let mut resolved_mentions = mentions::ResolvedMentions::default();
+        if let Some(last_msg) = messages.iter().rev().find(|m| m.role == "user") {
+            let mentions = mentions::parse_mentions(&mentions::message_text(&last_msg.content));
+            if !mentions.is_empty() {
+                let terminal_output = mentions::terminal_output(&mentions, app.try_state::<TerminalManager>().as_deref());
+                let root_for_mentions = root.clone();
+                resolved_mentions = tokio::task::spawn_blocking(move || mentions::resolve_mentions(&mentions, &root_for_mentions, &terminal_output))
+                    .await
+                    .map_err(|e| format!("Task join error: {}", e))?;
+                println!("[AI Chat] Resolved {} mentions into {} references", resolved_mentions.blocks.len(), resolved_mentions.references.len());
+            }
+        }
+        let has_mentions = !resolved_mentions.blocks.is_empty();
+
+        // 2. Detect @codebase query or smart RAG trigger
         let mut codebase_query = None;
         if let Some(last_msg) = messages.iter().filter(|m| m.role == "user").last() {
              match &last_msg.content {
@@ -89,9 +160,9 @@ async fn ai_chat(
                             codebase_query = Some(if final_query.is_empty() { "overview of the project structure and main logic".to_string() } else { final_query });
                         }
                     }
-                    // Priority 2: Smart RAG detection (if enabled in settings)
-                    // Note: For now we enable by default, can be controlled via provider_config in future
-                    else if should_use_rag(&lower_text) {
+                    // Priority 2: Smart RAG detection, unless IFAI.md sets `enable_rag: false`
+                    // or the user already mentioned what to look at
+                    else if rag_enabled && !has_mentions && should_use_rag(&lower_text) {
                         println!("[AI Chat] Smart RAG triggered for query: {}", text);
                         codebase_query = Some(text.to_string());
                     }
@@ -114,7 +185,7 @@ async fn ai_chat(
                         }
                     }
                     // Priority 2: Smart RAG detection
-                    else if should_use_rag(&lower_text) {
+                    else if rag_enabled && !has_mentions && should_use_rag(&lower_text) {
                         println!("[AI Chat] Smart RAG triggered for query: {}", combined_text);
                         codebase_query = Some(combined_text);
                     }
@@ -122,11 +193,16 @@ async fn ai_chat(
             };
         }
 
-        // 2. RAG Context Building (Parallel)
-        let app_handle = app.clone();
+        // Mentions are what the user asked for, so they go with the message rather than competing for the RAG budget
+        if let Some(last_msg) = messages.iter_mut().rev().find(|m| m.role == "user") {
+            mentions::append_blocks(&mut last_msg.content, &resolved_mentions.blocks);
+        }
+
+        // 3. RAG Context Building (Parallel)
         let rag_service = state.rag_service.clone();
-        let event_id_for_rag = event_id.clone();
         let root_for_rag = root.clone();
+        let app_for_rag = app.clone();
+        let event_id_for_rag = event_id.clone();
         
         // Clone messages for summarization to avoid move
         let mut messages_for_summarize = messages.clone();
@@ -136,27 +212,33 @@ async fn ai_chat(
             if let Some(query) = codebase_query {
                  println!("[AI Chat] Parallel RAG: Starting context build for query: {}", query);
 
-                 // Note: initialization check is implicit in retrieve_context logic in Commercial impl
-                 // or skipped in Community impl.
-
-                 // Add timeout to prevent blocking indefinitely
-                 let retrieve_future = rag_service.retrieve_context(&query, &root_for_rag);
-                 let timeout_duration = std::time::Duration::from_secs(30);
+                 // Answer without project context rather than wait for a first build; services
+                 // that can't report status build or load the index inside retrieve_context
+                 if let Ok(status) = rag_service.index_status(&root_for_rag).await {
+                     if !status.is_searchable() {
+                         println!("[AI Chat] RAG skipped: project index is {:?}", status.state);
+                         if status.state == events::IndexState::NotIndexed {
+                             let rag_service = rag_service.clone();
+                             let root = root_for_rag.clone();
+                             tauri::async_runtime::spawn(async move {
+                                 if let Err(e) = rag_service.index_project(&root).await {
+                                     eprintln!("[AI Chat] Background indexing failed: {}", e);
+                                 }
+                             });
+                         }
+                         let _ = app_for_rag.emit(&format!("{}_index_status", event_id_for_rag), &status);
+                         return None;
+                     }
+                 }
 
-                 match tokio::time::timeout(timeout_duration, retrieve_future).await {
-                    Ok(Ok(rag_result)) => {
+                 match rag_service.retrieve_context(&query, &root_for_rag).await {
+                    Ok(rag_result) => {
                         println!("[AI Chat] RAG context built successfully with {} references", rag_result.references.len());
-                        let _ = app_handle.emit(&format!("{}_references", event_id_for_rag), &rag_result.references);
-                        let _ = app_handle.emit("codebase-references", rag_result.references);
-                        Some(rag_result.context)
+                        Some(rag_result)
                     },
-                    Ok(Err(e)) => {
+                    Err(e) => {
                          eprintln!("[AI Chat] RAG failed: {}", e);
                          None
-                    },
-                    Err(_) => {
-                         eprintln!("[AI Chat] RAG timeout after 30s - index may not be initialized. Try running /index command first.");
-                         None
                     }
                  }
             } else {
@@ -178,62 +260,54 @@ async fn ai_chat(
         };
 
         // Execute tasks in parallel
-        let (rag_context, updated_messages): (Option<String>, Vec<_>) = tokio::join!(rag_task, summarize_task);
+        let (rag_result, updated_messages): (Option<core_traits::rag::RagResult>, Vec<_>) = tokio::join!(rag_task, summarize_task);
         
         // Update messages with summarized version
         messages = updated_messages;
 
-        // 3. Assemble Final System Prompt
-        let mut final_system_prompt = prompt_manager::get_main_system_prompt(&root);
-        if let Some(context) = rag_context {
-             if !context.is_empty() {
-                let truncated_context = if context.len() > 12000 {
-                    format!("{}... [Context Truncated]", &context[..12000])
-                } else {
-                    context
-                };
-                final_system_prompt.push_str("\n\nProject Context:\n");
-                final_system_prompt.push_str(&truncated_context);
-             }
-        }
-
-        // Extract existing summary if present (from auto_summarize)
-        let mut summary_message = None;
-        for msg in &messages {
-            if msg.role == "system" {
-                match &msg.content {
-                    core_traits::ai::Content::Text(text) => {
-                        if text.contains("## CONVERSATION SUMMARY") {
-                            summary_message = Some(msg.clone());
-                            break;
-                        }
-                    },
-                    _ => {}
-                }
+        // Services that only return text get it packed as a single reference
+        let references = match rag_result {
+            Some(result) if result.references.is_empty() && !result.context.is_empty() => {
+                vec![core_traits::rag::RagReference { content: result.context, ..Default::default() }]
             }
-        }
+            Some(result) => result.references,
+            None => Vec::new(),
+        };
 
-        println!("[AI Chat] Before retain: {} messages", messages.len());
+        // Extract existing summary if present (from auto_summarize)
+        let summary_message = messages.iter()
+            .find(|m| m.role == "system" && matches!(&m.content, core_traits::ai::Content::Text(text) if text.contains("## CONVERSATION SUMMARY")))
+            .cloned();
         messages.retain(|m| m.role != "system");
-        println!("[AI Chat] After retain: {} messages", messages.len());
-        
-        // Insert Main System Prompt
-        messages.insert(0, core_traits::ai::Message {
-            role: "system".to_string(),
-            content: core_traits::ai::Content::Text(final_system_prompt),
-            tool_calls: None,
-            tool_call_id: None,
-        });
 
-        // Re-insert Summary if found
-        if let Some(summary) = summary_message {
-            // Insert after the main system prompt
-            if messages.len() > 0 {
-                messages.insert(1, summary);
-            } else {
-                messages.push(summary);
-            }
+        // 4. Fit system prompt, summary, references and history into the model's context window.
+        // Counting tokens for every reference and message is CPU-bound, so it runs off the async workers.
+        let budget_tokens = conversation::context_packer::input_budget(&provider_config);
+        let packed = tokio::task::spawn_blocking(move || conversation::context_packer::pack(
+            conversation::context_packer::ContextParts {
+                system_prompt: prompt_manager::get_main_system_prompt(&root),
+                summary: summary_message,
+                references,
+                history: messages,
+            },
+            &root,
+            budget_tokens,
+        ))
+        .await
+        .map_err(|e| format!("Task join error: {}", e))?;
+        let report = &packed.report;
+        println!(
+            "[AI Chat] Context packed: {}/{} tokens, {} references included, {} omitted, {} messages omitted",
+            report.used_tokens, report.budget_tokens, report.included_references, report.omitted_references.len(), report.omitted_messages
+        );
+        let mut references = resolved_mentions.references;
+        references.extend(packed.references);
+        if !references.is_empty() {
+            let _ = app.emit(&format!("{}_references", event_id), &references);
+            let _ = app.emit("codebase-references", &references);
         }
+        let _ = app.emit(&format!("{}_context", event_id), &packed.report);
+        messages = packed.messages;
     }
 
     ai_utils::sanitize_messages(&mut messages);
@@ -270,18 +344,104 @@ async fn ai_chat(
 
 #[tauri::command]
 async fn ai_completion(
+    app: tauri::AppHandle,
     state: tauri::State<'_, AppState>,
     provider_config: core_traits::ai::AIProviderConfig,
     messages: Vec<core_traits::ai::Message>,
+    event_id: Option<String>,
+    model: Option<String>,
+    project_root: Option<String>,
+    conversation_id: Option<String>,
 ) -> Result<String, String> {
     println!("[AI Completion] Entry - provider: {}", provider_config.id);
-    let response = state.ai_service.chat(&provider_config, messages).await?;
+    let provider_config = match model {
+        Some(m) => ai_utils::routing::select_model(&provider_config, &m),
+        None => provider_config,
+    };
+    let messages = attachments::inline_local_images_async(messages, project_root.clone()).await?;
+
+    let usage_ctx = usage_ledger::UsageContext {
+        project_root: project_root.unwrap_or_default(),
+        conversation_id,
+        agent_id: None,
+    };
+    let request = usage_ledger::scope(usage_ctx, state.ai_service.chat_routed(&provider_config, messages));
+    let (response, route) = match &event_id {
+        Some(id) => {
+            let registration = ai_utils::cancel::register(id);
+            ai_utils::cancel::with_cancellation(&registration.token(), request).await
+                .map_err(|_| ai_utils::cancel::CANCELLED_MESSAGE.to_string())??
+        }
+        None => request.await?,
+    };
+
+    println!("[AI Completion] Answered by {}/{}", route.provider_id, route.model);
+    if let Some(id) = &event_id {
+        let _ = app.emit(&format!("{}_model", id), &route);
+    }
+
     match response.content {
         core_traits::ai::Content::Text(t) => Ok(t),
         _ => Err("Received non-text content for completion".to_string()),
     }
 }
 
+/// Completion constrained to a JSON Schema. Returns the parsed value, or an error starting
+/// with "Schema validation failed" when the model's output never matched.
+#[tauri::command]
+async fn ai_completion_json(
+    app: tauri::AppHandle,
+    provider_config: core_traits::ai::AIProviderConfig,
+    messages: Vec<core_traits::ai::Message>,
+    schema: serde_json::Value,
+    schema_name: Option<String>,
+    event_id: Option<String>,
+    model: Option<String>,
+    project_root: Option<String>,
+    conversation_id: Option<String>,
+) -> Result<serde_json::Value, String> {
+    println!("[AI Completion] Structured entry - provider: {}", provider_config.id);
+    let provider_config = match model {
+        Some(m) => ai_utils::routing::select_model(&provider_config, &m),
+        None => provider_config,
+    };
+    let messages = attachments::inline_local_images_async(messages, project_root.clone()).await?;
+
+    let output = ai_utils::structured::JsonOutput::new(schema_name, schema);
+    let usage_ctx = usage_ledger::UsageContext {
+        project_root: project_root.unwrap_or_default(),
+        conversation_id,
+        agent_id: None,
+    };
+    let request = usage_ledger::scope(
+        usage_ctx,
+        ai_utils::structured::fetch_structured_completion(&provider_config, messages, &output),
+    );
+    let (value, route) = match &event_id {
+        Some(id) => {
+            let registration = ai_utils::cancel::register(id);
+            ai_utils::cancel::with_cancellation(&registration.token(), request).await
+                .map_err(|_| ai_utils::cancel::CANCELLED_MESSAGE.to_string())??
+        }
+        None => request.await?,
+    };
+
+    println!("[AI Completion] Structured output answered by {}/{}", route.provider_id, route.model);
+    if let Some(id) = &event_id {
+        let _ = app.emit(&format!("{}_model", id), &route);
+    }
+
+    Ok(value)
+}
+
+/// Cancel an in-flight `ai_chat`/`ai_completion` (by event id) or agent run (by agent id)
+#[tauri::command]
+fn cancel_ai_request(id: String) -> bool {
+    let cancelled = ai_utils::cancel::cancel(&id);
+    println!("[AI] Cancel requested for {}: {}", id, if cancelled { "cancelled" } else { "not running" });
+    cancelled
+}
+
 #[tauri::command]
 async fn create_window(app: tauri::AppHandle, label: String, title: String, url: String) -> Result<(), String> {
     let window_builder = tauri::WebviewWindowBuilder::new(&app, label, tauri::WebviewUrl::App(url.into()))
@@ -312,7 +472,15 @@ pub fn run() {
         #[cfg(not(feature = "commercial"))]
         let (ai, rag, agent) = {
              let ai = Arc::new(community::BasicAIService);
-             let rag = Arc::new(community::CommunityRagService);
+             let status_handle = app_handle.clone();
+             let progress_handle = app_handle.clone();
+             let rag = Arc::new(community::CommunityRagService::new()
+                 .with_status_listener(move |status| {
+                     let _ = status_handle.emit(events::INDEX_STATUS_EVENT, status);
+                 })
+                 .with_progress_listener(move |progress| {
+                     let _ = progress_handle.emit(events::INDEX_PROGRESS_EVENT, progress);
+                 }));
              let agent = Arc::new(community::CommunityAgentService);
              (
                  ai as Arc<dyn core_traits::ai::AIService>, 
@@ -321,6 +489,18 @@ pub fn run() {
              )
         };
         
+        // IFAI_CASSETTE replays (or records) provider traffic instead of calling the provider directly.
+        // Debug builds only, so a stray variable can't send a release build's prompts through a recording proxy.
+        #[cfg(debug_assertions)]
+        let ai: Arc<dyn core_traits::ai::AIService> = match tauri::async_runtime::block_on(cassette::CassetteAIService::from_env()) {
+            Some(Ok(service)) => Arc::new(service),
+            Some(Err(e)) => {
+                eprintln!("[Cassette] Failed to start, using the configured provider: {}", e);
+                ai
+            }
+            None => ai,
+        };
+
         app.manage(AppState {
             ai_service: ai,
             rag_service: rag,
@@ -349,6 +529,22 @@ pub fn run() {
                 }
                 tauri::WindowEvent::DragDrop(tauri::DragDropEvent::Drop { paths, .. }) => {
                     let _ = window.emit("tauri://file-drop", paths.clone());
+
+                    // Dropped images are also prepared as attachments for vision models
+                    let images: Vec<String> = paths.iter()
+                        .filter(|p| attachments::is_image_path(p))
+                        .map(|p| p.to_string_lossy().to_string())
+                        .collect();
+                    if !images.is_empty() {
+                        let window = window.clone();
+                        tauri::async_runtime::spawn_blocking(move || {
+                            let prepared: Vec<_> = attachments::prepare_images_blocking(&images, None)
+                                .into_iter()
+                                .filter_map(|r| r.map_err(|e| eprintln!("[Attachments] {}", e)).ok())
+                                .collect();
+                            let _ = window.emit("file-drop-images", prepared);
+                        });
+                    }
                 }
                 _ => {}
             }
@@ -362,6 +558,8 @@ pub fn run() {
             greet,
             ai_chat,
             ai_completion,
+            ai_completion_json,
+            cancel_ai_request,
             create_window,
             file_walker::get_all_file_paths,
             file_walker::get_all_file_paths_parallel,
@@ -378,8 +576,13 @@ pub fn run() {
             lsp::send_lsp_message,
             lsp::kill_lsp,
             commands::core_wrappers::init_rag_index,
+            commands::core_wrappers::rag_index_status,
+            commands::core_wrappers::cancel_rag_index,
+            commands::core_wrappers::stop_rag_watch,
             commands::core_wrappers::search_semantic,
             commands::core_wrappers::search_hybrid,
+            commands::core_wrappers::find_symbol,
+            commands::core_wrappers::find_references,
             commands::core_wrappers::build_context,
             commands::core_wrappers::agent_write_file,
             commands::core_wrappers::agent_read_file,
@@ -393,6 +596,8 @@ pub fn run() {
             commands::agent_commands::launch_agent,
             commands::agent_commands::list_running_agents,
             commands::agent_commands::approve_agent_action,
+            commands::provider_commands::list_provider_models,
+            commands::provider_commands::test_provider_connection,
             performance::detect_gpu_info,
             performance::is_on_battery,
             performance::get_display_refresh_rate,
@@ -400,8 +605,69 @@ pub fn run() {
             project_config::save_project_config,
             project_config::parse_project_config,
             project_config::project_config_exists,
-            project_config::delete_project_config
+            project_config::delete_project_config,
+            attachments::prepare_image_attachments,
+            attachments::list_image_attachments,
+            usage_ledger::get_usage_by_day,
+            usage_ledger::get_usage_by_conversation,
+            usage_ledger::get_usage_by_agent
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cassette::{Cassette, CassetteAIService, Interaction};
+    use std::sync::Mutex;
+    use tauri::Listener;
+
+    #[tokio::test]
+    async fn test_ai_chat_streams_recorded_reply() {
+        let service = CassetteAIService::replay(Cassette::default().with(Interaction::stream_text(&["Hello", " there"])))
+            .await
+            .unwrap();
+
+        let app = tauri::test::mock_app();
+        app.manage(AppState {
+            ai_service: Arc::new(service),
+            rag_service: Arc::new(community::CommunityRagService::new()),
+            agent_service: Arc::new(community::CommunityAgentService),
+        });
+
+        let chunks = Arc::new(Mutex::new(Vec::new()));
+        let sink = chunks.clone();
+        app.listen("chat-test", move |event| {
+            // Chat chunks are JSON documents sent as strings
+            let chunk: String = serde_json::from_str(event.payload()).unwrap();
+            sink.lock().unwrap().push(serde_json::from_str::<serde_json::Value>(&chunk).unwrap());
+        });
+
+        let provider = core_traits::ai::AIProviderConfig {
+            id: "cassette".to_string(),
+            models: vec!["gpt-4o-mini".to_string()],
+            ..Default::default()
+        };
+        let messages = vec![Message {
+            role: "user".to_string(),
+            content: Content::Text("Say hello".to_string()),
+            tool_calls: None,
+            tool_call_id: None,
+            thinking_blocks: None,
+        }];
+
+        run_ai_chat(app.handle().clone(), app.state::<AppState>(), provider, messages, "chat-test".to_string(), None, true)
+            .await
+            .unwrap();
+
+        let chunks = chunks.lock().unwrap();
+        let text: String = chunks.iter()
+            .filter(|c| c["type"] == "content")
+            .filter_map(|c| c["content"].as_str())
+            .collect();
+        assert_eq!(text, "Hello there");
+        assert_eq!(chunks.last().unwrap()["type"], "done");
+        assert_eq!(chunks.last().unwrap()["usage"]["completion_tokens"], 5);
+    }
+}
@@ -0,0 +1,483 @@
+//! Expands `@file`, `@folder`, `@diff`, `@terminal` and `@symbol` mentions in a chat message
+//! into tagged context blocks.
+//!
+//! ```text
+//! @file:src/main.rs          whole file (first FILE_MAX_LINES lines)
+//! @file:src/main.rs:L10-40   lines 10 to 40
+//! @folder:src/commands       file list plus contents, up to FOLDER_MAX_BYTES
+//! @diff                      working tree and index against HEAD, untracked files included
+//! @terminal:3                recent output of PTY 3
+//! @symbol:TerminalManager    where the symbol is defined
+//! ```
+//!
+//! Paths are relative to the project root unless absolute. A mention that can't be
+//! resolved becomes an empty block with an `error` attribute, so the model knows why.
+
+use crate::community::rag::syntax;
+use crate::core_traits::ai::{Content, ContentPart};
+use crate::core_traits::rag::RagReference;
+use crate::hybrid_search::definition_patterns;
+use crate::search;
+use crate::terminal::TerminalManager;
+use git2::{DiffFormat, DiffOptions, Repository};
+use ignore::WalkBuilder;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const FILE_MAX_LINES: usize = 500;
+const FILE_MAX_BYTES: u64 = 4 * 1024 * 1024;
+const FOLDER_MAX_ENTRIES: usize = 200;
+const FOLDER_MAX_BYTES: usize = 32 * 1024;
+const DIFF_MAX_BYTES: usize = 48 * 1024;
+const TERMINAL_MAX_LINES: usize = 200;
+const SYMBOL_MAX_DEFINITIONS: usize = 3;
+/// Lines shown from a definition onwards when its extent isn't known
+const SYMBOL_LINES: usize = 40;
+
+static MENTION: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"(?:^|\s)@(?:(file|folder|terminal|symbol):("[^"]+"|\S+)|(diff)\b)"#).unwrap()
+});
+static LINE_RANGE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^(.+?):L(\d+)(?:-L?(\d+))?$").unwrap());
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Mention {
+    /// `lines` is 1-based and inclusive
+    File { path: String, lines: Option<(usize, usize)> },
+    Folder { path: String },
+    Diff,
+    Terminal { pty_id: u32 },
+    Symbol { name: String },
+}
+
+#[derive(Debug, Default)]
+pub struct ResolvedMentions {
+    /// Tagged blocks, one per mention, ready to append to the message
+    pub blocks: Vec<String>,
+    pub references: Vec<RagReference>,
+}
+
+/// Mentions in the order they appear, without duplicates
+pub fn parse_mentions(text: &str) -> Vec<Mention> {
+    let mut mentions = Vec::new();
+    for caps in MENTION.captures_iter(text) {
+        let mention = if caps.get(3).is_some() {
+            Some(Mention::Diff)
+        } else {
+            let raw = &caps[2];
+            let value = if raw.starts_with('"') {
+                raw.trim_matches('"')
+            } else {
+                // Sentence punctuation after a mention isn't part of it
+                let trimmed = raw.trim_end_matches([',', ';', '!', '?', ')', '.']);
+                if trimmed.is_empty() { raw } else { trimmed }
+            };
+            match &caps[1] {
+                "file" => Some(parse_file(value)),
+                "folder" => Some(Mention::Folder { path: value.to_string() }),
+                "terminal" => value.parse().ok().map(|pty_id| Mention::Terminal { pty_id }),
+                _ => Some(Mention::Symbol { name: value.to_string() }),
+            }
+        };
+        if let Some(mention) = mention {
+            if !mentions.contains(&mention) {
+                mentions.push(mention);
+            }
+        }
+    }
+    mentions
+}
+
+fn parse_file(value: &str) -> Mention {
+    match LINE_RANGE.captures(value) {
+        Some(caps) => {
+            let start: usize = caps[2].parse().unwrap_or(1).max(1);
+            let end = caps.get(3).and_then(|m| m.as_str().parse().ok()).unwrap_or(start).max(start);
+            Mention::File { path: caps[1].to_string(), lines: Some((start, end)) }
+        }
+        None => Mention::File { path: value.to_string(), lines: None },
+    }
+}
+
+/// Text of a message, with the text parts of multi-part content joined
+pub fn message_text(content: &Content) -> String {
+    match content {
+        Content::Text(text) => text.clone(),
+        Content::Parts(parts) => parts.iter()
+            .filter_map(|p| match p {
+                ContentPart::Text { text, .. } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join(" "),
+    }
+}
+
+/// Add resolved blocks after the message text
+pub fn append_blocks(content: &mut Content, blocks: &[String]) {
+    if blocks.is_empty() {
+        return;
+    }
+    let expanded = blocks.join("\n\n");
+    match content {
+        Content::Text(text) => {
+            text.push_str("\n\n");
+            text.push_str(&expanded);
+        }
+        Content::Parts(parts) => parts.push(ContentPart::Text { text: expanded, part_type: "text".to_string() }),
+    }
+}
+
+/// Recent output of the mentioned terminals. Read up front, since resolution runs off the async runtime.
+pub fn terminal_output(mentions: &[Mention], manager: Option<&TerminalManager>) -> HashMap<u32, String> {
+    mentions.iter()
+        .filter_map(|m| match m {
+            Mention::Terminal { pty_id } => manager
+                .and_then(|t| t.recent_output(*pty_id, TERMINAL_MAX_LINES))
+                .map(|output| (*pty_id, output)),
+            _ => None,
+        })
+        .collect()
+}
+
+pub fn resolve_mentions(mentions: &[Mention], root: &str, terminal_output: &HashMap<u32, String>) -> ResolvedMentions {
+    let mut resolved = ResolvedMentions::default();
+    for mention in mentions {
+        let result = match mention {
+            Mention::File { path, lines } => resolve_file(root, path, *lines),
+            Mention::Folder { path } => resolve_folder(root, path),
+            Mention::Diff => resolve_diff(root),
+            Mention::Terminal { pty_id } => resolve_terminal(*pty_id, terminal_output),
+            Mention::Symbol { name } => resolve_symbol(root, name),
+        };
+        match result {
+            Ok((block, references)) => {
+                resolved.blocks.push(block);
+                resolved.references.extend(references);
+            }
+            Err(e) => {
+                eprintln!("[Mentions] Could not resolve {:?}: {}", mention, e);
+                resolved.blocks.push(error_block(mention, &e));
+            }
+        }
+    }
+    resolved
+}
+
+type Resolution = Result<(String, Vec<RagReference>), String>;
+
+fn error_block(mention: &Mention, error: &str) -> String {
+    let error = attr(error);
+    match mention {
+        Mention::File { path, .. } => format!("<file path=\"{}\" error=\"{}\"/>", attr(path), error),
+        Mention::Folder { path } => format!("<folder path=\"{}\" error=\"{}\"/>", attr(path), error),
+        Mention::Diff => format!("<diff error=\"{}\"/>", error),
+        Mention::Terminal { pty_id } => format!("<terminal id=\"{}\" error=\"{}\"/>", pty_id, error),
+        Mention::Symbol { name } => format!("<symbol name=\"{}\" error=\"{}\"/>", attr(name), error),
+    }
+}
+
+fn attr(value: &str) -> String {
+    value.replace('&', "&amp;").replace('"', "&quot;").replace('<', "&lt;")
+}
+
+/// `path` resolved against `root` with symlinks followed, or `missing` when it doesn't exist.
+/// Anything outside the project (absolute paths elsewhere, `../`, links out of it) is refused,
+/// so a mention can't pull in files like ~/.ssh keys.
+fn absolute(root: &str, path: &str, missing: &str) -> Result<PathBuf, String> {
+    let root = Path::new(root).canonicalize().map_err(|_| "Project folder not found".to_string())?;
+    let resolved = root.join(path).canonicalize().map_err(|_| missing.to_string())?;
+    if resolved.starts_with(&root) {
+        Ok(resolved)
+    } else {
+        Err("Path is outside the project".to_string())
+    }
+}
+
+fn relative(root: &str, path: &Path) -> String {
+    // Resolved paths are relative to the canonical root, which differs from `root` under symlinked folders
+    let canonical_root = Path::new(root).canonicalize().unwrap_or_else(|_| PathBuf::from(root));
+    path.strip_prefix(&canonical_root)
+        .or_else(|_| path.strip_prefix(root))
+        .unwrap_or(path)
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+fn read_text(path: &Path) -> Result<String, String> {
+    let metadata = fs::metadata(path).map_err(|_| "File not found".to_string())?;
+    if !metadata.is_file() {
+        return Err("Not a file".to_string());
+    }
+    if metadata.len() > FILE_MAX_BYTES {
+        return Err(format!("File is larger than {} MB", FILE_MAX_BYTES / 1024 / 1024));
+    }
+    let bytes = fs::read(path).map_err(|e| e.to_string())?;
+    if bytes.contains(&0) {
+        return Err("Binary file".to_string());
+    }
+    String::from_utf8(bytes).map_err(|_| "Not a UTF-8 text file".to_string())
+}
+
+fn resolve_file(root: &str, path: &str, lines: Option<(usize, usize)>) -> Resolution {
+    let full_path = absolute(root, path, "File not found")?;
+    let text = read_text(&full_path)?;
+    let all: Vec<&str> = text.lines().collect();
+    let (start, end) = match lines {
+        Some((start, _)) if start > all.len() => {
+            return Err(format!("Line {} is past the end of the file ({} lines)", start, all.len()));
+        }
+        Some((start, end)) => (start, end.min(all.len())),
+        None => (1, all.len().min(FILE_MAX_LINES)),
+    };
+    let content = all.get(start.saturating_sub(1)..end).unwrap_or_default().join("\n");
+    let truncated = if lines.is_none() && all.len() > FILE_MAX_LINES {
+        format!(" truncated=\"{} of {} lines\"", FILE_MAX_LINES, all.len())
+    } else {
+        String::new()
+    };
+
+    let block = format!(
+        "<file path=\"{}\" lines=\"{}-{}\"{}>\n{}\n</file>",
+        attr(&relative(root, &full_path)), start, end, truncated, content
+    );
+    let file_path = full_path.to_string_lossy().to_string();
+    let language = syntax::language_name(&file_path).map(str::to_string);
+    let reference = RagReference { file_path, line_start: start, line_end: end, content, language, ..Default::default() };
+    Ok((block, vec![reference]))
+}
+
+fn resolve_folder(root: &str, path: &str) -> Resolution {
+    let dir = absolute(root, path, "Folder not found")?;
+    if !dir.is_dir() {
+        return Err("Folder not found".to_string());
+    }
+    let mut files: Vec<PathBuf> = WalkBuilder::new(&dir)
+        .standard_filters(true)
+        .hidden(true)
+        .build()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_some_and(|ft| ft.is_file()))
+        .map(|e| e.into_path())
+        .collect();
+    files.sort();
+    let total = files.len();
+    files.truncate(FOLDER_MAX_ENTRIES);
+
+    let listing: Vec<String> = files.iter().map(|f| relative(root, f)).collect();
+    let mut body = listing.join("\n");
+    if total > files.len() {
+        body.push_str(&format!("\n... and {} more files", total - files.len()));
+    }
+
+    // Contents of the first files that fit; the rest are only listed
+    let mut references = Vec::new();
+    let mut used = 0;
+    for file in &files {
+        let Ok(text) = read_text(file) else { continue };
+        if used + text.len() > FOLDER_MAX_BYTES {
+            break;
+        }
+        used += text.len();
+        body.push_str(&format!("\n\n<file path=\"{}\">\n{}\n</file>", attr(&relative(root, file)), text.trim_end()));
+        let file_path = file.to_string_lossy().to_string();
+        references.push(RagReference {
+            line_end: text.lines().count(),
+            language: syntax::language_name(&file_path).map(str::to_string),
+            file_path,
+            line_start: 1,
+            content: text,
+            ..Default::default()
+        });
+    }
+
+    let block = format!(
+        "<folder path=\"{}\" files=\"{}\" included=\"{}\">\n{}\n</folder>",
+        attr(&relative(root, &dir)), total, references.len(), body
+    );
+    Ok((block, references))
+}
+
+fn resolve_diff(root: &str) -> Resolution {
+    let repo = Repository::discover(root).map_err(|e| format!("Not a git repository: {}", e.message()))?;
+    let workdir = repo.workdir().ok_or("Bare repository has no working tree")?.to_path_buf();
+    // An unborn branch has no HEAD tree; everything then shows up as added
+    let head_tree = repo.head().ok().and_then(|h| h.peel_to_tree().ok());
+
+    let mut options = DiffOptions::new();
+    options.include_untracked(true).recurse_untracked_dirs(true).show_untracked_content(true);
+    let diff = repo.diff_tree_to_workdir_with_index(head_tree.as_ref(), Some(&mut options)).map_err(|e| e.to_string())?;
+
+    // Patch text per file, in diff order
+    let mut patches: Vec<(PathBuf, usize, String)> = Vec::new();
+    diff.print(DiffFormat::Patch, |delta, hunk, line| {
+        let Some(path) = delta.new_file().path().or(delta.old_file().path()) else { return true };
+        if patches.last().is_none_or(|(p, _, _)| p != path) {
+            patches.push((path.to_path_buf(), 0, String::new()));
+        }
+        let (_, first_line, patch) = patches.last_mut().unwrap();
+        if *first_line == 0 {
+            if let Some(hunk) = hunk {
+                *first_line = hunk.new_start() as usize;
+            }
+        }
+        if matches!(line.origin(), '+' | '-' | ' ') {
+            patch.push(line.origin());
+        }
+        patch.push_str(&String::from_utf8_lossy(line.content()));
+        true
+    })
+    .map_err(|e| e.to_string())?;
+
+    if patches.is_empty() {
+        return Ok(("<diff>\nNo uncommitted changes\n</diff>".to_string(), vec![]));
+    }
+
+    let mut body = String::new();
+    let mut references = Vec::new();
+    let mut omitted = Vec::new();
+    for (path, first_line, patch) in patches {
+        if !body.is_empty() && body.len() + patch.len() > DIFF_MAX_BYTES {
+            omitted.push(path.to_string_lossy().replace('\\', "/"));
+            continue;
+        }
+        body.push_str(&patch);
+        references.push(RagReference {
+            file_path: workdir.join(&path).to_string_lossy().to_string(),
+            line_start: first_line.max(1),
+            content: patch,
+            ..Default::default()
+        });
+    }
+    if !omitted.is_empty() {
+        body.push_str(&format!("\n[{} more changed files not shown: {}]", omitted.len(), omitted.join(", ")));
+    }
+    Ok((format!("<diff files=\"{}\">\n{}\n</diff>", references.len() + omitted.len(), body.trim_end()), references))
+}
+
+fn resolve_terminal(pty_id: u32, terminal_output: &HashMap<u32, String>) -> Resolution {
+    let output = terminal_output.get(&pty_id).ok_or_else(|| format!("Terminal {} is not open", pty_id))?;
+    let block = format!("<terminal id=\"{}\">\n{}\n</terminal>", pty_id, output.trim_end());
+    let reference = RagReference {
+        file_path: format!("terminal:{}", pty_id),
+        line_start: 1,
+        line_end: output.lines().count(),
+        content: output.clone(),
+        ..Default::default()
+    };
+    Ok((block, vec![reference]))
+}
+
+fn resolve_symbol(root: &str, name: &str) -> Resolution {
+    // Qualified names (`terminal::TerminalManager`) are looked up by their last segment
+    let name = name.rsplit([':', '.']).next().unwrap_or(name).to_string();
+    if name.is_empty() {
+        return Err("Empty symbol name".to_string());
+    }
+    let matches = search::grep_search(root, &format!(r"\b{}\b", regex::escape(&name))).map_err(|e| e.to_string())?;
+    let names = [name.clone()];
+    let patterns = definition_patterns(&names);
+
+    let mut blocks = Vec::new();
+    let mut references = Vec::new();
+    for m in matches.iter().filter(|m| patterns.iter().any(|(_, re)| re.is_match(&m.content))).take(SYMBOL_MAX_DEFINITIONS) {
+        let Ok(text) = read_text(Path::new(&m.path)) else { continue };
+        let line = m.line_number as usize;
+        // The syntax chunker knows where the definition ends (and where its doc comment starts)
+        let definition = syntax::chunk_source(&m.path, &text).and_then(|chunks| {
+            chunks.into_iter().find(|c| c.symbol.as_deref() == Some(name.as_str()) && c.line_start <= line && line <= c.line_end)
+        });
+        let (start, end, content, symbol) = match definition {
+            Some(chunk) => {
+                let symbol = chunk.qualified_symbol();
+                (chunk.line_start, chunk.line_end, chunk.content, symbol)
+            }
+            None => {
+                let content = text.lines().skip(line - 1).take(SYMBOL_LINES).collect::<Vec<_>>().join("\n");
+                (line, line + content.lines().count().saturating_sub(1), content, Some(name.clone()))
+            }
+        };
+        blocks.push(format!(
+            "<symbol name=\"{}\" path=\"{}\" lines=\"{}-{}\">\n{}\n</symbol>",
+            attr(symbol.as_deref().unwrap_or(&name)), attr(&relative(root, Path::new(&m.path))), start, end, content
+        ));
+        references.push(RagReference {
+            file_path: m.path.clone(),
+            line_start: start,
+            line_end: end,
+            content,
+            symbol,
+            language: syntax::language_name(&m.path).map(str::to_string),
+        });
+    }
+    if blocks.is_empty() {
+        return Err("No definition found".to_string());
+    }
+    Ok((blocks.join("\n\n"), references))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    #[test]
+    fn test_parse_mentions() {
+        let mentions = parse_mentions(
+            "Why does @file:src/main.rs:L10-40 fail? See @terminal:3, @diff and @symbol:terminal::TerminalManager. \
+             Also @folder:\"src/my dir\" and @file:README.md. Not an email@diff.com or @terminal:abc",
+        );
+        assert_eq!(mentions, vec![
+            Mention::File { path: "src/main.rs".to_string(), lines: Some((10, 40)) },
+            Mention::Terminal { pty_id: 3 },
+            Mention::Diff,
+            Mention::Symbol { name: "terminal::TerminalManager".to_string() },
+            Mention::Folder { path: "src/my dir".to_string() },
+            Mention::File { path: "README.md".to_string(), lines: None },
+        ]);
+    }
+
+    #[test]
+    fn test_resolve_mentions() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+        fs::create_dir_all(root.join("src")).unwrap();
+        let git = |args: &[&str]| {
+            Command::new("git").args(args).current_dir(root).output().unwrap();
+        };
+        git(&["init", "-q"]);
+        let lib = (1..=50).map(|i| format!("// 第{}行", i)).collect::<Vec<_>>().join("\n");
+        fs::write(root.join("src/lib.rs"), format!("pub struct Parser {{}}\n{}\n", lib)).unwrap();
+        git(&["add", "."]);
+        git(&["-c", "user.name=t", "-c", "user.email=t@t", "commit", "-qm", "init"]);
+        fs::write(root.join("src/new.rs"), "fn added() {}\n").unwrap();
+        let root_str = root.to_string_lossy().to_string();
+
+        let mentions = parse_mentions("@file:src/lib.rs:L2-3 @folder:src @diff @symbol:Parser @terminal:7 @file:missing.rs");
+        let terminals = HashMap::from([(7, "$ cargo test\nok".to_string())]);
+        let resolved = resolve_mentions(&mentions, &root_str, &terminals);
+
+        assert_eq!(resolved.blocks[0], "<file path=\"src/lib.rs\" lines=\"2-3\">\n// 第1行\n// 第2行\n</file>");
+        assert!(resolved.blocks[1].starts_with("<folder path=\"src\" files=\"2\" included=\"2\">\nsrc/lib.rs\nsrc/new.rs"));
+        assert!(resolved.blocks[2].contains("+fn added() {}"));
+        assert_eq!(resolved.blocks[3], "<symbol name=\"Parser\" path=\"src/lib.rs\" lines=\"1-1\">\npub struct Parser {}\n</symbol>");
+        assert_eq!(resolved.blocks[4], "<terminal id=\"7\">\n$ cargo test\nok\n</terminal>");
+        assert_eq!(resolved.blocks[5], "<file path=\"missing.rs\" error=\"File not found\"/>");
+
+        // Nothing outside the project is read, however the path is written
+        let outside = temp.path().parent().unwrap().join(format!("{}-secret", root.file_name().unwrap().to_string_lossy()));
+        fs::write(&outside, "private").unwrap();
+        let escapes = format!("@file:../{} @file:\"{}\" @folder:..", outside.file_name().unwrap().to_string_lossy(), outside.display());
+        let refused = resolve_mentions(&parse_mentions(&escapes), &root_str, &HashMap::new());
+        fs::remove_file(&outside).unwrap();
+        assert_eq!(refused.blocks.len(), 3);
+        assert!(refused.blocks.iter().all(|b| b.contains("error=\"Path is outside the project\"")));
+        assert!(refused.references.is_empty());
+
+        let files: Vec<String> = resolved.references.iter().map(|r| relative(&root_str, Path::new(&r.file_path))).collect();
+        assert_eq!(files, ["src/lib.rs", "src/lib.rs", "src/new.rs", "src/new.rs", "src/lib.rs", "terminal:7"]);
+        assert_eq!(resolved.references[0].line_start, 2);
+    }
+}
@@ -2,6 +2,10 @@ use tauri::command;
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 use std::fs;
+use ts_rs::TS;
+use crate::ai_utils::routing;
+use crate::core_traits::ai::AIProviderConfig;
+use crate::prompt_manager;
 
 /// Project-level configuration from `.ifai/IFAI.md`
 ///
@@ -45,6 +49,102 @@ impl Default for ProjectConfig {
     }
 }
 
+/// Where an effective setting came from
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, TS)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum SettingSource {
+    /// Built into the app
+    Default,
+    /// The provider selected in the app settings
+    Global,
+    /// Chosen for this request
+    Request,
+    /// `.ifai/IFAI.md` or the project's prompt files
+    Project,
+}
+
+/// The settings a chat or agent request runs with, sent on `{event_id}_settings`
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct EffectiveSettings {
+    pub provider_id: String,
+    pub provider_source: SettingSource,
+    pub model: String,
+    pub model_source: SettingSource,
+    pub rag_enabled: bool,
+    pub rag_source: SettingSource,
+    pub system_prompt_source: SettingSource,
+    /// Project settings that could not be applied
+    pub warnings: Vec<String>,
+}
+
+/// Apply the project's provider, model and RAG settings on top of the global provider.
+///
+/// `ai_provider_id` is looked up in the global provider, its fallback providers and `providers`.
+/// A per-request model beats the project's `ai_model`, which beats the provider's primary model.
+pub fn resolve_settings(
+    project_root: Option<&str>,
+    global: AIProviderConfig,
+    providers: &[AIProviderConfig],
+    request_model: Option<&str>,
+) -> (AIProviderConfig, EffectiveSettings) {
+    let config = project_root.and_then(load_project_config_sync).unwrap_or_default();
+    let mut warnings = Vec::new();
+
+    let project_provider = config.ai_provider_id.as_deref().map(str::trim).filter(|id| !id.is_empty());
+    let (mut provider, provider_source) = match project_provider {
+        Some(id) => {
+            let found = std::iter::once(&global)
+                .chain(&global.fallback_providers)
+                .chain(providers)
+                .find(|p| p.id == id)
+                .cloned();
+            match found {
+                Some(provider) => (provider, SettingSource::Project),
+                None => {
+                    warnings.push(format!("Project provider '{}' is not configured; using '{}'", id, global.id));
+                    (global, SettingSource::Global)
+                }
+            }
+        }
+        None => (global, SettingSource::Global),
+    };
+
+    let project_model = config.ai_model.as_deref().map(str::trim).filter(|m| !m.is_empty());
+    let model_source = match (request_model, project_model) {
+        (Some(model), _) => {
+            provider = routing::select_model(&provider, model);
+            SettingSource::Request
+        }
+        (None, Some(model)) => {
+            provider = routing::select_model(&provider, model);
+            SettingSource::Project
+        }
+        (None, None) => provider_source,
+    };
+
+    let (rag_enabled, rag_source) = match config.enable_rag {
+        Some(enabled) => (enabled, SettingSource::Project),
+        None => (true, SettingSource::Default),
+    };
+
+    let settings = EffectiveSettings {
+        provider_id: provider.id.clone(),
+        provider_source,
+        model: routing::primary_model(&provider).unwrap_or_default().to_string(),
+        model_source,
+        rag_enabled,
+        rag_source,
+        system_prompt_source: project_root
+            .map(prompt_manager::main_system_prompt_source)
+            .unwrap_or(SettingSource::Default),
+        warnings,
+    };
+    (provider, settings)
+}
+
 /// Get the path to `.ifai/IFAI.md` for a project root
 fn get_config_path(project_root: &str) -> Result<PathBuf, String> {
     let root = Path::new(project_root);
@@ -266,6 +366,43 @@ custom_instructions: |
         assert_eq!(config.custom_instructions, Some("Please respond in English.".to_string()));
     }
 
+    #[test]
+    fn test_resolve_settings_applies_project_overrides() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+        let root_str = root.to_string_lossy().to_string();
+        let provider = |id: &str, model: &str| AIProviderConfig {
+            id: id.to_string(),
+            models: vec![model.to_string()],
+            ..Default::default()
+        };
+        let global = provider("openai", "gpt-4o");
+        let providers = [provider("zhipu", "glm-4.6")];
+
+        // No IFAI.md: everything comes from the global provider and the defaults
+        let (config, settings) = resolve_settings(Some(&root_str), global.clone(), &providers, None);
+        assert_eq!((config.id.as_str(), settings.model.as_str()), ("openai", "gpt-4o"));
+        assert_eq!(settings.provider_source, SettingSource::Global);
+        assert_eq!((settings.rag_enabled, settings.rag_source), (true, SettingSource::Default));
+        assert_eq!(settings.system_prompt_source, SettingSource::Default);
+
+        fs::create_dir_all(root.join(".ifai")).unwrap();
+        fs::write(root.join(".ifai/IFAI.md"), "---\nai_provider_id: zhipu\nai_model: glm-4-plus\nenable_rag: false\ncustom_system_prompt: You review Rust code.\n---\n").unwrap();
+        let (config, settings) = resolve_settings(Some(&root_str), global.clone(), &providers, None);
+        assert_eq!(config.id, "zhipu");
+        assert_eq!(config.models[0], "glm-4-plus");
+        assert_eq!((settings.provider_source, settings.model_source), (SettingSource::Project, SettingSource::Project));
+        assert_eq!((settings.rag_enabled, settings.rag_source), (false, SettingSource::Project));
+        assert_eq!(settings.system_prompt_source, SettingSource::Project);
+        assert!(prompt_manager::get_main_system_prompt(&root_str).starts_with("You review Rust code."));
+
+        // A model picked for the request wins; an unknown project provider is reported
+        let (config, settings) = resolve_settings(Some(&root_str), global.clone(), &[], Some("gpt-4.1"));
+        assert_eq!((config.id.as_str(), settings.model.as_str()), ("openai", "gpt-4.1"));
+        assert_eq!(settings.model_source, SettingSource::Request);
+        assert_eq!(settings.warnings, vec!["Project provider 'zhipu' is not configured; using 'openai'".to_string()]);
+    }
+
     #[test]
     fn test_parse_no_frontmatter() {
         let content = r#"# Just markdown
@@ -22,31 +22,42 @@ pub enum AccessTier {
 
 pub fn get_main_system_prompt(project_root: &str) -> String {
     let variables = variables::collect_system_variables(project_root);
+    let ifai_config = project_config::load_project_config_sync(project_root);
 
-    let template = {
-        let local_root = std::path::Path::new(project_root).join(".ifai/prompts/system");
-        let override_path = local_root.join("main.override.md");
-        let local_path = local_root.join("main.md");
+    // IFAI.md `custom_system_prompt` replaces the main prompt; project prompt files come next
+    let custom_prompt = ifai_config.as_ref()
+        .and_then(|c| c.custom_system_prompt.clone())
+        .filter(|p| !p.trim().is_empty());
 
-        if override_path.exists() {
-            storage::load_prompt(&override_path).ok()
-        } else if local_path.exists() {
-            storage::load_prompt(&local_path).ok()
-        } else if let Some(content_file) = BuiltinPrompts::get("system/main.md") {
-            let content = std::str::from_utf8(content_file.data.as_ref()).unwrap_or("");
-            storage::load_prompt_from_str(content, None).ok()
-        } else {
-            None
-        }
-    };
+    let mut prompt = if let Some(custom) = custom_prompt {
+        println!("[PromptManager] Using custom_system_prompt from IFAI.md: {} chars", custom.len());
+        template::render_template(&custom, &variables).unwrap_or(custom)
+    } else {
+        let template = {
+            let local_root = std::path::Path::new(project_root).join(".ifai/prompts/system");
+            let override_path = local_root.join("main.override.md");
+            let local_path = local_root.join("main.md");
+
+            if override_path.exists() {
+                storage::load_prompt(&override_path).ok()
+            } else if local_path.exists() {
+                storage::load_prompt(&local_path).ok()
+            } else if let Some(content_file) = BuiltinPrompts::get("system/main.md") {
+                let content = std::str::from_utf8(content_file.data.as_ref()).unwrap_or("");
+                storage::load_prompt_from_str(content, None).ok()
+            } else {
+                None
+            }
+        };
 
-    let mut prompt = match template {
-        Some(t) => template::render_template(&t.content, &variables).unwrap_or_else(|_| t.content),
-        None => "You are a helpful AI programming assistant.".to_string(),
+        match template {
+            Some(t) => template::render_template(&t.content, &variables).unwrap_or_else(|_| t.content),
+            None => "You are a helpful AI programming assistant.".to_string(),
+        }
     };
 
     // 追加 IFAI.md 中的 custom_instructions
-    if let Some(ifai_config) = project_config::load_project_config_sync(project_root) {
+    if let Some(ifai_config) = ifai_config {
         println!("[PromptManager] Loaded IFAI.md config: {:?}", ifai_config.default_language);
         if let Some(instructions) = ifai_config.custom_instructions {
             if !instructions.trim().is_empty() {
@@ -62,6 +73,19 @@ pub fn get_main_system_prompt(project_root: &str) -> String {
     prompt
 }
 
+/// Whether the main prompt comes from the project (IFAI.md or `.ifai/prompts/system`) or is the builtin one
+pub fn main_system_prompt_source(project_root: &str) -> project_config::SettingSource {
+    let custom = project_config::load_project_config_sync(project_root)
+        .and_then(|c| c.custom_system_prompt)
+        .is_some_and(|p| !p.trim().is_empty());
+    let local_root = std::path::Path::new(project_root).join(".ifai/prompts/system");
+    if custom || local_root.join("main.override.md").exists() || local_root.join("main.md").exists() {
+        project_config::SettingSource::Project
+    } else {
+        project_config::SettingSource::Default
+    }
+}
+
 pub fn get_agent_prompt(agent_type: &str, project_root: &str, task_description: &str) -> String {
     let mut variables = variables::collect_system_variables(project_root);
     variables.insert("TASK_DESCRIPTION".to_string(), task_description.to_string());
@@ -4,10 +4,16 @@ use std::io::{Read, Write};
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 use std::path::PathBuf;
+use once_cell::sync::Lazy;
+
+/// Scrollback kept per session for `@terminal` mentions
+const SCROLLBACK_BYTES: usize = 64 * 1024;
 
 pub struct TerminalSession {
     pub master: Box<dyn MasterPty + Send>,
     pub writer: Box<dyn Write + Send>,
+    /// Most recent output, at most `SCROLLBACK_BYTES`
+    pub scrollback: Arc<Mutex<String>>,
 }
 
 // Store PTY sessions
@@ -25,6 +31,36 @@ impl TerminalManager {
             pty_system: NativePtySystem::default(),
         }
     }
+
+    /// The last `max_lines` lines a session printed, without escape sequences
+    pub fn recent_output(&self, pty_id: u32, max_lines: usize) -> Option<String> {
+        let sessions = self.pty_sessions.lock().unwrap();
+        let raw = sessions.get(&pty_id)?.scrollback.lock().unwrap().clone();
+        let text = strip_ansi(&raw);
+        let lines: Vec<&str> = text.lines().collect();
+        Some(lines[lines.len().saturating_sub(max_lines)..].join("\n"))
+    }
+}
+
+/// Append to a scrollback buffer, dropping the oldest output beyond `SCROLLBACK_BYTES`
+fn push_scrollback(scrollback: &mut String, output: &str) {
+    scrollback.push_str(output);
+    if scrollback.len() > SCROLLBACK_BYTES {
+        let mut cut = scrollback.len() - SCROLLBACK_BYTES;
+        while !scrollback.is_char_boundary(cut) {
+            cut += 1;
+        }
+        scrollback.drain(..cut);
+    }
+}
+
+/// Colour codes, cursor movement, OSC titles and carriage returns
+static ANSI_ESCAPES: Lazy<regex::Regex> = Lazy::new(|| {
+    regex::Regex::new(r"\x1b\[[0-?]*[ -/]*[@-~]|\x1b\][^\x07\x1b]*(?:\x07|\x1b\\)|\x1b[@-_]|\r").unwrap()
+});
+
+fn strip_ansi(text: &str) -> String {
+    ANSI_ESCAPES.replace_all(text, "").into_owned()
 }
 
 // Global PTY counter for unique IDs
@@ -64,6 +100,8 @@ pub async fn create_pty(app_handle: AppHandle, manager: tauri::State<'_, Termina
     let writer = pty_pair.master.take_writer().map_err(|e| e.to_string())?;
     
     let event_name = format!("pty-output-{}", pty_id);
+    let scrollback = Arc::new(Mutex::new(String::new()));
+    let scrollback_for_reader = scrollback.clone();
 
     // Spawn a thread to read PTY output and emit to frontend
     async_runtime::spawn(async move {
@@ -77,6 +115,7 @@ pub async fn create_pty(app_handle: AppHandle, manager: tauri::State<'_, Termina
                 },
                 Ok(bytes_read) => {
                     let output = String::from_utf8_lossy(&buf[..bytes_read]);
+                    push_scrollback(&mut scrollback_for_reader.lock().unwrap(), &output);
                     let _ = app_handle.emit(&event_name, output.to_string());
                 },
                 Err(e) => {
@@ -93,6 +132,7 @@ pub async fn create_pty(app_handle: AppHandle, manager: tauri::State<'_, Termina
     manager.pty_sessions.lock().unwrap().insert(pty_id, TerminalSession {
         master: pty_pair.master,
         writer,
+        scrollback,
     });
 
     Ok(pty_id)
@@ -130,4 +170,18 @@ pub async fn kill_pty(manager: tauri::State<'_, TerminalManager>, pty_id: u32) -
     } else {
         Err(format!("PTY session {} not found", pty_id))
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scrollback_is_capped_and_stripped() {
+        let mut scrollback = String::new();
+        push_scrollback(&mut scrollback, &"日志".repeat(SCROLLBACK_BYTES / 3));
+        push_scrollback(&mut scrollback, "\x1b[1;32m   Compiling\x1b[0m app\r\n\x1b]0;title\x07$ ");
+        assert!(scrollback.len() <= SCROLLBACK_BYTES);
+        assert!(strip_ansi(&scrollback).ends_with("   Compiling app\n$ "));
+    }
+}
@@ -0,0 +1,416 @@
+use tauri::command;
+use serde::{Deserialize, Serialize};
+use once_cell::sync::Lazy;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, OpenOptions};
+use std::future::Future;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use ts_rs::TS;
+use crate::conversation::token_counter;
+
+/// Token counts normalized across providers.
+///
+/// `prompt_tokens` always includes cached input tokens; `cached_tokens` is the share read from the
+/// cache and `cache_write_tokens` the share written to it.
+/// Likewise `completion_tokens` includes reasoning tokens and `reasoning_tokens` is their share.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, TS)]
+#[ts(export)]
+pub struct TokenUsage {
+    #[serde(default)]
+    #[ts(type = "number")]
+    pub prompt_tokens: u64,
+    #[serde(default)]
+    #[ts(type = "number")]
+    pub completion_tokens: u64,
+    #[serde(default)]
+    #[ts(type = "number")]
+    pub cached_tokens: u64,
+    #[serde(default)]
+    #[ts(type = "number")]
+    pub reasoning_tokens: u64,
+    #[serde(default)]
+    #[ts(type = "number")]
+    pub cache_write_tokens: u64,
+}
+
+impl TokenUsage {
+    /// Parse a provider `usage` block (OpenAI, Anthropic or Gemini `usageMetadata`)
+    pub fn from_provider(usage: &Value) -> Option<Self> {
+        let n = |v: &Value| v.as_u64().unwrap_or(0);
+
+        if usage.get("prompt_tokens").is_some() || usage.get("completion_tokens").is_some() {
+            // OpenAI-compatible; some providers report cache hits at the top level instead
+            let cached = usage["prompt_tokens_details"]["cached_tokens"].as_u64()
+                .or_else(|| usage["prompt_cache_hit_tokens"].as_u64())
+                .unwrap_or(0);
+            return Some(Self {
+                prompt_tokens: n(&usage["prompt_tokens"]),
+                completion_tokens: n(&usage["completion_tokens"]),
+                cached_tokens: cached,
+                reasoning_tokens: n(&usage["completion_tokens_details"]["reasoning_tokens"]),
+                cache_write_tokens: 0,
+            });
+        }
+
+        if usage.get("input_tokens").is_some() || usage.get("output_tokens").is_some() {
+            // Anthropic reports cache reads and writes separately from `input_tokens`,
+            // and doesn't break thinking out of `output_tokens`
+            let cache_read = n(&usage["cache_read_input_tokens"]);
+            let cache_write = n(&usage["cache_creation_input_tokens"]);
+            return Some(Self {
+                prompt_tokens: n(&usage["input_tokens"]) + cache_read + cache_write,
+                completion_tokens: n(&usage["output_tokens"]),
+                cached_tokens: cache_read,
+                reasoning_tokens: 0,
+                cache_write_tokens: cache_write,
+            });
+        }
+
+        if usage.get("promptTokenCount").is_some() || usage.get("candidatesTokenCount").is_some() {
+            // Gemini counts thoughts outside `candidatesTokenCount`, but bills them as output
+            let thoughts = n(&usage["thoughtsTokenCount"]);
+            return Some(Self {
+                prompt_tokens: n(&usage["promptTokenCount"]),
+                completion_tokens: n(&usage["candidatesTokenCount"]) + thoughts,
+                cached_tokens: n(&usage["cachedContentTokenCount"]),
+                reasoning_tokens: thoughts,
+                cache_write_tokens: 0,
+            });
+        }
+
+        None
+    }
+
+    /// Fill in `reasoning_tokens` from the streamed reasoning text when the provider didn't
+    /// report them (Anthropic, most OpenAI-compatible servers)
+    pub fn with_reasoning_estimate(mut self, reasoning: &str) -> Self {
+        if self.reasoning_tokens == 0 && !reasoning.is_empty() {
+            let estimate = token_counter::count_text_tokens(reasoning) as u64;
+            self.reasoning_tokens = estimate.min(self.completion_tokens);
+        }
+        self
+    }
+}
+
+/// USD per million tokens
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ModelPrice {
+    pub input: f64,
+    #[serde(default)]
+    pub cached_input: Option<f64>,
+    /// Defaults to [`CACHE_WRITE_MULTIPLIER`] times `input`
+    #[serde(default)]
+    pub cache_write_input: Option<f64>,
+    pub output: f64,
+}
+
+/// Anthropic bills writes to its (5-minute) prompt cache at 1.25x the input rate
+const CACHE_WRITE_MULTIPLIER: f64 = 1.25;
+
+/// Built-in prices, matched by longest model-name prefix.
+/// Projects can override or extend them in `.ifai/usage/prices.json`.
+const BUILTIN_PRICES: &[(&str, f64, f64, f64)] = &[
+    // (model prefix, input, cached input, output)
+    ("gpt-4o-mini", 0.15, 0.075, 0.60),
+    ("gpt-4o", 2.50, 1.25, 10.00),
+    ("gpt-4.1-nano", 0.10, 0.025, 0.40),
+    ("gpt-4.1-mini", 0.40, 0.10, 1.60),
+    ("gpt-4.1", 2.00, 0.50, 8.00),
+    ("o4-mini", 1.10, 0.275, 4.40),
+    ("o3-mini", 1.10, 0.55, 4.40),
+    ("o3", 2.00, 0.50, 8.00),
+    ("claude-opus-4", 15.00, 1.50, 75.00),
+    ("claude-sonnet-4", 3.00, 0.30, 15.00),
+    ("claude-3-7-sonnet", 3.00, 0.30, 15.00),
+    ("claude-3-5-haiku", 0.80, 0.08, 4.00),
+    ("claude-haiku-4", 1.00, 0.10, 5.00),
+    ("gemini-2.5-pro", 1.25, 0.31, 10.00),
+    ("gemini-2.5-flash", 0.30, 0.075, 2.50),
+    ("deepseek-chat", 0.27, 0.07, 1.10),
+    ("deepseek-reasoner", 0.55, 0.14, 2.19),
+    ("glm-4.6", 0.60, 0.11, 2.20),
+    ("glm-4.5", 0.60, 0.11, 2.20),
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageEntry {
+    /// Unix timestamp (seconds)
+    pub timestamp: i64,
+    pub provider_id: String,
+    pub model: String,
+    #[serde(default)]
+    pub conversation_id: Option<String>,
+    #[serde(default)]
+    pub agent_id: Option<String>,
+    #[serde(flatten)]
+    pub usage: TokenUsage,
+    /// `None` when the model has no known price
+    #[serde(default)]
+    pub cost_usd: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct UsageSummary {
+    pub key: String,
+    pub requests: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub cached_tokens: u64,
+    pub reasoning_tokens: u64,
+    pub cost_usd: f64,
+    /// Requests whose model had no price; their tokens are counted but not their cost
+    pub unpriced_requests: u64,
+}
+
+/// Who a request should be billed to. Set around chat/agent pipelines with [`scope`].
+#[derive(Debug, Clone, Default)]
+pub struct UsageContext {
+    pub project_root: String,
+    pub conversation_id: Option<String>,
+    pub agent_id: Option<String>,
+}
+
+tokio::task_local! {
+    static USAGE_CONTEXT: UsageContext;
+}
+
+static LEDGER_LOCK: Mutex<()> = Mutex::new(());
+
+/// A project's prices and the modification time of the `prices.json` they were read with
+struct CachedPrices {
+    modified: Option<SystemTime>,
+    prices: Arc<HashMap<String, ModelPrice>>,
+}
+
+static PRICES: Lazy<Mutex<HashMap<String, CachedPrices>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Run `fut` with `ctx` as the billing target for every AI call it makes
+pub async fn scope<F: Future>(ctx: UsageContext, fut: F) -> F::Output {
+    USAGE_CONTEXT.scope(ctx, fut).await
+}
+
+/// Record usage for the current scope. Calls outside a project scope are not persisted.
+///
+/// Called from streaming loops, so the entry is priced and written on the blocking pool.
+pub fn record(provider_id: &str, model: &str, usage: &TokenUsage) {
+    let Ok(ctx) = USAGE_CONTEXT.try_with(|c| c.clone()) else {
+        return;
+    };
+    if ctx.project_root.is_empty() {
+        return;
+    }
+
+    let mut entry = UsageEntry {
+        timestamp: chrono::Utc::now().timestamp(),
+        provider_id: provider_id.to_string(),
+        model: model.to_string(),
+        conversation_id: ctx.conversation_id,
+        agent_id: ctx.agent_id,
+        usage: usage.clone(),
+        cost_usd: None,
+    };
+    let project_root = ctx.project_root;
+    tokio::task::spawn_blocking(move || {
+        let prices = cached_prices(&project_root);
+        entry.cost_usd = price_for(&prices, &entry.model).map(|p| cost_of(&p, &entry.usage));
+        if let Err(e) = append_entry(&project_root, &entry) {
+            eprintln!("[UsageLedger] Failed to record usage: {}", e);
+        }
+    });
+}
+
+fn usage_dir(project_root: &str) -> PathBuf {
+    Path::new(project_root).join(".ifai").join("usage")
+}
+
+fn ledger_path(project_root: &str) -> PathBuf {
+    usage_dir(project_root).join("ledger.jsonl")
+}
+
+fn append_entry(project_root: &str, entry: &UsageEntry) -> Result<(), String> {
+    let _guard = LEDGER_LOCK.lock().unwrap();
+    fs::create_dir_all(usage_dir(project_root)).map_err(|e| e.to_string())?;
+
+    let line = serde_json::to_string(entry).map_err(|e| e.to_string())?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(ledger_path(project_root))
+        .map_err(|e| e.to_string())?;
+    writeln!(file, "{}", line).map_err(|e| e.to_string())
+}
+
+fn read_entries(project_root: &str) -> Result<Vec<UsageEntry>, String> {
+    let path = ledger_path(project_root);
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+
+    let file = fs::File::open(&path).map_err(|e| e.to_string())?;
+    Ok(BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        // Skip partially written or hand-edited lines rather than failing the whole query
+        .filter_map(|line| serde_json::from_str(&line).ok())
+        .collect())
+}
+
+fn prices_path(project_root: &str) -> PathBuf {
+    usage_dir(project_root).join("prices.json")
+}
+
+/// The project's prices, re-read only when `prices.json` changed
+fn cached_prices(project_root: &str) -> Arc<HashMap<String, ModelPrice>> {
+    let modified = fs::metadata(prices_path(project_root)).and_then(|m| m.modified()).ok();
+    let mut cache = PRICES.lock().unwrap();
+    match cache.get(project_root) {
+        Some(cached) if cached.modified == modified => cached.prices.clone(),
+        _ => {
+            let prices = Arc::new(load_prices(project_root));
+            cache.insert(project_root.to_string(), CachedPrices { modified, prices: prices.clone() });
+            prices
+        }
+    }
+}
+
+fn load_prices(project_root: &str) -> HashMap<String, ModelPrice> {
+    let mut prices: HashMap<String, ModelPrice> = BUILTIN_PRICES.iter()
+        .map(|(prefix, input, cached, output)| {
+            (prefix.to_string(), ModelPrice { input: *input, cached_input: Some(*cached), cache_write_input: None, output: *output })
+        })
+        .collect();
+
+    let override_path = prices_path(project_root);
+    if let Ok(content) = fs::read_to_string(&override_path) {
+        match serde_json::from_str::<HashMap<String, ModelPrice>>(&content) {
+            Ok(custom) => prices.extend(custom),
+            Err(e) => eprintln!("[UsageLedger] Ignoring invalid {}: {}", override_path.display(), e),
+        }
+    }
+
+    prices
+}
+
+fn price_for(prices: &HashMap<String, ModelPrice>, model: &str) -> Option<ModelPrice> {
+    let model = model.to_lowercase();
+    // Provider-qualified names like "openai/gpt-4o" are priced by their model part
+    let model = model.rsplit('/').next().unwrap_or(&model);
+
+    prices.iter()
+        .filter(|(prefix, _)| model.starts_with(&prefix.to_lowercase()))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(_, price)| *price)
+}
+
+fn cost_of(price: &ModelPrice, usage: &TokenUsage) -> f64 {
+    let cached = usage.cached_tokens.min(usage.prompt_tokens);
+    let written = usage.cache_write_tokens.min(usage.prompt_tokens - cached) as f64;
+    let uncached = (usage.prompt_tokens - cached) as f64 - written;
+    let cached_rate = price.cached_input.unwrap_or(price.input);
+    let write_rate = price.cache_write_input.unwrap_or(price.input * CACHE_WRITE_MULTIPLIER);
+
+    (uncached * price.input + cached as f64 * cached_rate + written * write_rate
+        + usage.completion_tokens as f64 * price.output) / 1_000_000.0
+}
+
+fn summarize<F>(entries: Vec<UsageEntry>, key_of: F) -> Vec<UsageSummary>
+where
+    F: Fn(&UsageEntry) -> Option<String>,
+{
+    let mut groups: BTreeMap<String, UsageSummary> = BTreeMap::new();
+
+    for entry in entries {
+        let Some(key) = key_of(&entry) else { continue };
+        let summary = groups.entry(key.clone()).or_insert_with(|| UsageSummary { key, ..Default::default() });
+        summary.requests += 1;
+        summary.prompt_tokens += entry.usage.prompt_tokens;
+        summary.completion_tokens += entry.usage.completion_tokens;
+        summary.cached_tokens += entry.usage.cached_tokens;
+        summary.reasoning_tokens += entry.usage.reasoning_tokens;
+        match entry.cost_usd {
+            Some(cost) => summary.cost_usd += cost,
+            None => summary.unpriced_requests += 1,
+        }
+    }
+
+    groups.into_values().collect()
+}
+
+/// Spend per local calendar day, optionally limited to the last `days` days
+#[command]
+pub async fn get_usage_by_day(project_root: String, days: Option<u32>) -> Result<Vec<UsageSummary>, String> {
+    let since = days.map(|d| chrono::Utc::now().timestamp() - d as i64 * 86_400);
+    let entries = read_entries(&project_root)?
+        .into_iter()
+        .filter(|e| since.is_none_or(|s| e.timestamp >= s))
+        .collect();
+
+    Ok(summarize(entries, |e| {
+        chrono::DateTime::from_timestamp(e.timestamp, 0)
+            .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d").to_string())
+    }))
+}
+
+/// Spend per chat conversation (requests made by agents are excluded)
+#[command]
+pub async fn get_usage_by_conversation(project_root: String) -> Result<Vec<UsageSummary>, String> {
+    let entries = read_entries(&project_root)?;
+    Ok(summarize(entries, |e| if e.agent_id.is_none() { e.conversation_id.clone() } else { None }))
+}
+
+/// Spend per agent run
+#[command]
+pub async fn get_usage_by_agent(project_root: String) -> Result<Vec<UsageSummary>, String> {
+    let entries = read_entries(&project_root)?;
+    Ok(summarize(entries, |e| e.agent_id.clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_provider_usage() {
+        let openai = json!({ "prompt_tokens": 1200, "completion_tokens": 300, "prompt_tokens_details": { "cached_tokens": 1000 } });
+        assert_eq!(TokenUsage::from_provider(&openai), Some(TokenUsage { prompt_tokens: 1200, completion_tokens: 300, cached_tokens: 1000, reasoning_tokens: 0, cache_write_tokens: 0 }));
+
+        let reasoner = json!({ "prompt_tokens": 10, "completion_tokens": 300, "completion_tokens_details": { "reasoning_tokens": 250 } });
+        assert_eq!(TokenUsage::from_provider(&reasoner).unwrap().reasoning_tokens, 250);
+
+        let anthropic = json!({ "input_tokens": 20, "cache_read_input_tokens": 1000, "cache_creation_input_tokens": 300, "output_tokens": 50 });
+        assert_eq!(TokenUsage::from_provider(&anthropic), Some(TokenUsage { prompt_tokens: 1320, completion_tokens: 50, cached_tokens: 1000, reasoning_tokens: 0, cache_write_tokens: 300 }));
+
+        let gemini = json!({ "promptTokenCount": 10, "candidatesTokenCount": 5, "thoughtsTokenCount": 20, "totalTokenCount": 35 });
+        assert_eq!(TokenUsage::from_provider(&gemini), Some(TokenUsage { prompt_tokens: 10, completion_tokens: 25, cached_tokens: 0, reasoning_tokens: 20, cache_write_tokens: 0 }));
+
+        assert_eq!(TokenUsage::from_provider(&json!({})), None);
+    }
+
+    #[test]
+    fn test_price_lookup_prefers_longest_prefix() {
+        let prices = load_prices("/nonexistent");
+        let mini = price_for(&prices, "gpt-4o-mini-2024-07-18").unwrap();
+        assert_eq!(mini.input, 0.15);
+        assert_eq!(price_for(&prices, "o3-mini-2025-01-31").unwrap().input, 1.10);
+        assert_eq!(price_for(&prices, "o3-2025-04-16").unwrap().input, 2.00);
+        assert!(price_for(&prices, "my-local-llama").is_none());
+    }
+
+    #[test]
+    fn test_cost_discounts_cached_tokens() {
+        let price = ModelPrice { input: 2.0, cached_input: Some(0.5), cache_write_input: None, output: 8.0 };
+        let usage = TokenUsage { prompt_tokens: 1_000_000, completion_tokens: 100_000, cached_tokens: 500_000, reasoning_tokens: 0, cache_write_tokens: 0 };
+        let cost = cost_of(&price, &usage);
+        assert!((cost - (1.0 + 0.25 + 0.8)).abs() < 1e-9);
+
+        // Cache writes cost more than plain input
+        let usage = TokenUsage { prompt_tokens: 1_000_000, completion_tokens: 0, cached_tokens: 0, reasoning_tokens: 0, cache_write_tokens: 400_000 };
+        let cost = cost_of(&price, &usage);
+        assert!((cost - (1.2 + 0.4 * 2.5)).abs() < 1e-9);
+    }
+}
@@ -1,9 +1,9 @@
 import { create } from 'zustand';
 import { listen, UnlistenFn } from '@tauri-apps/api/event';
 import { invoke } from '@tauri-apps/api/core';
-import { Agent, AgentEventPayload } from '../types/agent';
+import { Agent, AgentEvent } from '../types/agent';
 import { useFileStore } from './fileStore';
-import { useSettingsStore } from './settingsStore';
+import { useSettingsStore, toBackendProviders } from './settingsStore';
 import { useChatStore as coreUseChatStore } from 'ifainew-core';
 import { useThreadStore } from './threadStore';
 import { v4 as uuidv4 } from 'uuid';
@@ -69,7 +69,7 @@ export const useAgentStore = create<AgentState>((set, get) => ({
     let thinkingBuffer = "";
     let lastFlush = 0;
 
-    const unlisten = await listen<AgentEventPayload>(eventId, (event) => {
+    const unlisten = await listen<AgentEvent>(eventId, (event) => {
         console.log(`[AgentStore] 🎯 Listener triggered! eventId: ${eventId}, agentId: ${id}`);
         const payload = event.payload;
         if (!payload || typeof payload !== 'object') return;
@@ -88,17 +88,17 @@ export const useAgentStore = create<AgentState>((set, get) => ({
         }
         
         // --- Status Update ---
-        if (payload.type === 'status' && (payload as any).status) {
-            const { status, progress } = (payload as any);
+        if (payload.type === 'status') {
+            const { status, progress } = payload;
             set(state => ({
                 runningAgents: state.runningAgents.map(a => 
-                    a.id === id ? { ...a, status: status as any, progress } : a
+                    a.id === id ? { ...a, status, progress: progress ?? a.progress } : a
                 )
             }));
         }
         // --- Log Update ---
-        else if (payload.type === 'log' && (payload as any).message) {
-            const message = (payload as any).message;
+        else if (payload.type === 'log' && payload.message) {
+            const message = payload.message;
             set(state => ({
                 runningAgents: state.runningAgents.map(a => {
                     if (a.id !== id) return a;
@@ -111,8 +111,8 @@ export const useAgentStore = create<AgentState>((set, get) => ({
             }));
         }
         // --- Content Streaming ---
-        else if (payload.type === 'thinking' || (payload as any).type === 'content') {
-            const chunk = (payload.content || (payload as any).content) || "";
+        else if (payload.type === 'thinking') {
+            const chunk = payload.content || "";
             thinkingBuffer += chunk;
 
             const now = Date.now();
@@ -145,12 +145,32 @@ export const useAgentStore = create<AgentState>((set, get) => ({
                 lastFlush = now;
             }
         } 
+        // --- Reasoning (not part of the answer) ---
+        else if (payload.type === 'reasoning') {
+            const chunk = payload.content;
+            set(state => ({
+                runningAgents: state.runningAgents.map(a =>
+                    a.id === id ? { ...a, reasoning: (a.reasoning || "") + chunk } : a
+                )
+            }));
+        }
+        // --- Warnings, retries and model fallbacks ---
+        else if (payload.type === 'warning' || payload.type === 'retry' || payload.type === 'fallback') {
+            const icon = payload.type === 'warning' ? '⚠️' : payload.type === 'retry' ? '🔁' : '🔀';
+            const line = `${icon} ${payload.message}`;
+            set(state => ({
+                runningAgents: state.runningAgents.map(a =>
+                    a.id === id ? { ...a, logs: [...a.logs, line].slice(-100) } : a
+                )
+            }));
+        }
         // --- Tool Calls ---
         else if (payload.type === 'tool_call') {
             const toolCall = payload.toolCall;
+            const toolArgs = toolCall.args as { content?: string } | null;
             // Debug log for tool call events
-            console.log(`[AgentStore] Received tool_call: tool=${toolCall?.tool}, partial=${toolCall?.isPartial}, content_len=${toolCall?.args?.content?.length || 0}`);
-            if (toolCall && msgId) {
+            console.log(`[AgentStore] Received tool_call: tool=${toolCall.tool}, partial=${toolCall.isPartial}, content_len=${toolArgs?.content?.length || 0}`);
+            if (msgId) {
                 const liveToolCall = {
                     id: toolCall.id,
                     type: 'function' as const,
@@ -160,7 +180,8 @@ export const useAgentStore = create<AgentState>((set, get) => ({
                         name: toolCall.tool,
                         arguments: JSON.stringify(toolCall.args)
                     },
-                    status: 'pending' as const,
+                    // Read-only calls are already running; only the rest wait for approval
+                    status: toolCall.autoApproved ? 'approved' as const : 'pending' as const,
                     isPartial: toolCall.isPartial,
                     agentId: id
                 };
@@ -214,7 +235,7 @@ export const useAgentStore = create<AgentState>((set, get) => ({
 
                     if (isNewlyCompleted && !wasAlreadyHandled) {
                         const settings = useSettingsStore.getState();
-                        if (settings.agentAutoApprove) {
+                        if (settings.agentAutoApprove && !toolCall.autoApproved) {
                             setTimeout(async () => {
                                 const approveToolCall = coreUseChatStore.getState().approveToolCall;
                                 if (approveToolCall) {
@@ -287,10 +308,8 @@ export const useAgentStore = create<AgentState>((set, get) => ({
                     phase: progress.phase,
                     currentFile: progress.currentFile,
                     currentPath: progress.currentPath,
-                    scanned: progress.progress?.scanned,
-                    total: progress.progress?.total,
-                    hasScannedFiles: !!progress.scannedFiles,
-                    scannedFilesCount: progress.scannedFiles?.length || 0
+                    scanned: progress.progress.scanned,
+                    total: progress.progress.total
                 });
 
                 // Update agent with explore progress data
@@ -513,6 +532,24 @@ export const useAgentStore = create<AgentState>((set, get) => ({
                 }
             }
         }
+        // --- Cancelled: the agent is stopped, not failed ---
+        else if (payload.type === 'cancelled') {
+            if (msgId) {
+                const { messages } = coreUseChatStore.getState();
+                coreUseChatStore.setState({
+                    messages: messages.map(m => m.id === msgId ? {
+                        ...m,
+                        content: m.content || '⏹️ Agent cancelled',
+                        agentId: undefined,
+                        isAgentLive: false
+                    } : m),
+                    isLoading: false
+                });
+            }
+            set(state => ({
+                runningAgents: state.runningAgents.map(a => a.id === id ? { ...a, status: 'stopped', expiresAt: Date.now() + 10000 } : a)
+            }));
+        }
         // --- Error ---
         else if (payload.type === 'error') {
             if (msgId) {
@@ -592,7 +629,8 @@ export const useAgentStore = create<AgentState>((set, get) => ({
             agentType,
             task,
             projectRoot,
-            providerConfig: backendProviderConfig
+            providerConfig: backendProviderConfig,
+            providers: toBackendProviders(settingsStore.providers)
         });
     } catch (error) {
         console.error("Failed to launch agent:", error);
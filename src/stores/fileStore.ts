@@ -37,6 +37,16 @@ const updateGitStatusRecursive = (node: FileNode, statuses: Map<string, GitStatu
     return node;
 };
 
+// The backend re-indexes open projects as files change; stop that once a project is closed or replaced
+const stopRagWatch = async (rootPath: string) => {
+    try {
+        const { invoke } = await import('@tauri-apps/api/core');
+        await invoke('stop_rag_watch', { rootPath });
+    } catch (e) {
+        console.warn('[RAG] Failed to stop watching the closed project:', e);
+    }
+};
+
 export const useFileStore = create<FileState>()(
   persist(
     (set, get) => ({
@@ -51,6 +61,10 @@ export const useFileStore = create<FileState>()(
       setFileTree: (tree) => {
         const treeWithStatus = tree ? updateGitStatusRecursive(tree, get().gitStatuses) : null;
         const newRootPath = tree ? tree.path : null;
+        const previousRootPath = get().rootPath;
+        if (previousRootPath && previousRootPath !== newRootPath) {
+          stopRagWatch(previousRootPath);
+        }
 
         set((state) => ({
           fileTree: treeWithStatus,
@@ -68,7 +82,11 @@ export const useFileStore = create<FileState>()(
       },
       
       setRootPath: async (path) => {
+        const previousRootPath = get().rootPath;
         set({ rootPath: path });
+        if (previousRootPath && previousRootPath !== path) {
+          stopRagWatch(previousRootPath);
+        }
 
         // Auto-initialize RAG index when project is opened
         if (path) {
@@ -13,6 +13,10 @@ export interface AIProviderConfig {
   enabled: boolean;
 }
 
+/** Providers in the shape the backend deserializes, for IFAI.md provider overrides */
+export const toBackendProviders = (providers: AIProviderConfig[]) =>
+  providers.map(p => ({ ...p, api_key: p.apiKey, base_url: p.baseUrl }));
+
 export interface SettingsState {
   // Appearance
   theme: 'vs-dark' | 'light';
@@ -3,12 +3,13 @@
 
 import { useChatStore as coreUseChatStore, registerStores, type Message } from 'ifainew-core';
 import { useFileStore } from './fileStore';
-import { useSettingsStore } from './settingsStore';
+import { useSettingsStore, toBackendProviders } from './settingsStore';
 import { useAgentStore } from './agentStore';
 import { useThreadStore } from './threadStore';
 import { invoke } from '@tauri-apps/api/core';
 import { recognizeIntent, shouldTriggerAgent, formatAgentName } from '../utils/intentRecognizer';
 import { autoSaveThread } from './persistence/threadPersistence';
+import type { ChatEvent } from '../types/agent';
 
 // Content segment interface for tracking stream reception order
 export interface ContentSegment {
@@ -239,6 +240,18 @@ function selectMessagesForContext(
     return selected.map(s => s.message);
 }
 
+/**
+ * Keep a turn's signed thinking blocks on its message; they are sent back with the
+ * turn's tool results and saved with the thread.
+ */
+function storeThinkingBlocks(messageId: string, thinkingBlocks: unknown[]) {
+    const { messages } = coreUseChatStore.getState();
+    coreUseChatStore.setState({
+        // @ts-ignore
+        messages: messages.map(m => m.id === messageId ? { ...m, thinkingBlocks } : m)
+    });
+}
+
 const patchedSendMessage = async (content: string | any[], providerId: string, modelName: string) => {
     const callId = crypto.randomUUID().slice(0, 8);
     console.log(`>>> [${callId}] patchedSendMessage called:`, typeof content === 'string' ? content.slice(0, 50) : 'array');
@@ -493,7 +506,9 @@ const patchedSendMessage = async (content: string | any[], providerId: string, m
             role: m.role,
             content: m.content,
             tool_calls: toolCalls && toolCalls.length > 0 ? toolCalls : undefined,
-            tool_call_id: m.tool_call_id
+            tool_call_id: m.tool_call_id,
+            // @ts-ignore - signed thinking, required back with this turn's tool results
+            thinking_blocks: m.thinkingBlocks
         };
     });
 
@@ -522,14 +537,16 @@ const patchedSendMessage = async (content: string | any[], providerId: string, m
         let toolCallUpdate: any = null;
 
         try {
-            // Parse JSON format: {"type":"content","content":"文本"}
-            const payload = JSON.parse(event.payload);
+            // Parse JSON format: {"v":1,"type":"content","content":"文本"}
+            const payload: ChatEvent = JSON.parse(event.payload);
 
             if (payload.type === 'content' && payload.content) {
                 textChunk = payload.content;
             } else if (payload.type === 'tool_call' && payload.tool_call) {
                 // Note: Rust backend sends snake_case "tool_call", not camelCase "toolCall"
                 toolCallUpdate = payload.tool_call;
+            } else if (payload.type === 'thinking_blocks') {
+                storeThinkingBlocks(assistantMsgId, payload.thinking_blocks);
             }
         } catch (e) {
             // Fallback: treat as plain text
@@ -688,7 +705,8 @@ const patchedSendMessage = async (content: string | any[], providerId: string, m
             role: m.role,
             content: m.content,
             toolCalls: m.tool_calls, // Note: snake_case from Rust
-            tool_call_id: m.tool_call_id
+            tool_call_id: m.tool_call_id,
+            thinkingBlocks: m.thinking_blocks
         }));
 
         // Replace history but keep the currently streaming assistant message
@@ -736,7 +754,9 @@ const patchedSendMessage = async (content: string | any[], providerId: string, m
             messages: msgHistory,
             eventId: assistantMsgId,
             projectRoot: useFileStore.getState().rootPath,
-            enableTools: true
+            enableTools: true,
+            conversationId: useThreadStore.getState().activeThreadId,
+            providers: toBackendProviders(settings.providers)
         });
     } catch (e) {
         console.error('[Chat] Invoke error:', e);
@@ -845,7 +865,9 @@ const patchedGenerateResponse = async (history: any[], providerConfig: any, opti
             role: m.role,
             content: m.content,
             tool_calls: toolCalls && toolCalls.length > 0 ? toolCalls : undefined,
-            tool_call_id: m.tool_call_id
+            tool_call_id: m.tool_call_id,
+            // @ts-ignore - signed thinking, required back with this turn's tool results
+            thinking_blocks: m.thinkingBlocks
         };
     });
 
@@ -868,9 +890,10 @@ const patchedGenerateResponse = async (history: any[], providerConfig: any, opti
         let textChunk = '';
         let toolCallUpdate: any = null;
         try {
-            const payload = JSON.parse(event.payload);
+            const payload: ChatEvent = JSON.parse(event.payload);
             if (payload.type === 'content' && payload.content) textChunk = payload.content;
             else if (payload.type === 'tool_call' && payload.tool_call) toolCallUpdate = payload.tool_call;
+            else if (payload.type === 'thinking_blocks') storeThinkingBlocks(assistantMsgId, payload.thinking_blocks);
         } catch (e) { textChunk = event.payload; }
 
         if (textChunk || toolCallUpdate) {
@@ -964,7 +987,8 @@ const patchedGenerateResponse = async (history: any[], providerConfig: any, opti
             role: m.role,
             content: m.content,
             toolCalls: m.tool_calls,
-            tool_call_id: m.tool_call_id
+            tool_call_id: m.tool_call_id,
+            thinkingBlocks: m.thinking_blocks
         }));
         coreUseChatStore.setState({ messages: [...compactedMessages, assistantMsgPlaceholder] });
     });
@@ -994,7 +1018,9 @@ const patchedGenerateResponse = async (history: any[], providerConfig: any, opti
             messages: msgHistory,
             eventId: assistantMsgId,
             projectRoot: useFileStore.getState().rootPath,
-            enableTools: true
+            enableTools: true,
+            conversationId: useThreadStore.getState().activeThreadId,
+            providers: toBackendProviders(settings.providers)
         });
     } catch (e) {
         const { messages } = coreUseChatStore.getState();
@@ -1,15 +1,15 @@
-export type AgentStatus = 'idle' | 'running' | 'waitingfortool' | 'stopped' | 'failed' | 'completed' | 'initializing';
+import type { AgentEvent } from './bindings/AgentEvent';
+import type { AgentRunStatus } from './bindings/AgentRunStatus';
 
-export type AgentEventType =
-  | 'thinking'     // Analysis, reasoning, explanations
-  | 'tool_call'    // Requesting to use a tool
-  | 'tool_result'  // Result of a tool execution
-  | 'result'       // Final task result
-  | 'status'       // Status updates
-  | 'log'          // Activity logs
-  | 'error'        // Error during execution
-  | 'explore_progress'  // Explore agent scan progress
-  | 'explore_findings'; // Explore agent discoveries
+// Event payloads are generated from the Rust definitions in src-tauri/src/events.rs
+export type { AgentEvent } from './bindings/AgentEvent';
+export type { AgentStatusEvent } from './bindings/AgentStatusEvent';
+export type { AgentResultEvent } from './bindings/AgentResultEvent';
+export type { ChatEvent } from './bindings/ChatEvent';
+
+export type AgentStatus = AgentRunStatus | 'idle' | 'initializing';
+
+export type AgentEventType = AgentEvent['type'];
 
 export interface Agent {
   id: string;
@@ -19,6 +19,7 @@ export interface Agent {
   progress: number; // 0.0 to 1.0
   logs: string[];
   content?: string; // The accumulated "thinking" content
+  reasoning?: string; // Reasoning text from reasoning models, kept apart from the answer
   expiresAt?: number;
   startTime?: number;
   threadId?: string; // Associated thread ID for background tasks
@@ -59,50 +60,6 @@ export interface Agent {
   };
 }
 
-export interface AgentEventPayload {
-  type: AgentEventType;
-  content?: string;
-  toolCall?: {
-    id: string;
-    tool: string;
-    args: any;
-    isPartial?: boolean;
-  };
-  result?: string;
-  error?: string;
-
-  // Explore agent progress events
-  exploreProgress?: {
-    phase: 'scanning' | 'analyzing' | 'completed';
-    currentPath?: string;
-    currentFile?: string;
-    progress: {
-      total: number;
-      scanned: number;
-      byDirectory: Record<string, {
-        total: number;
-        scanned: number;
-        status: 'pending' | 'scanning' | 'completed';
-      }>;
-    };
-    scannedFiles?: string[];
-  };
-
-  // Explore agent findings events
-  exploreFindings?: {
-    summary: string;
-    directories: Array<{
-      path: string;
-      fileCount: number;
-      keyFiles: string[];
-    }>;
-    patterns?: Array<{
-      type: 'import' | 'export' | 'class' | 'function';
-      description: string;
-    }>;
-  };
-}
-
 // Helper types for explore events
 export interface ExploreDirectory {
   path: string;
@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { AgentRunStatus } from "./AgentRunStatus";
+import type { AgentToolCall } from "./AgentToolCall";
+import type { ExploreFindings } from "./ExploreFindings";
+import type { ExploreProgress } from "./ExploreProgress";
+import type { FallbackNotice } from "./FallbackNotice";
+import type { RetryNotice } from "./RetryNotice";
+
+/**
+ * An agent payload on `agent_{id}`: `{"v":1,"type":"thinking","content":"..."}`
+ */
+export type AgentEvent = { v: number, } & ({ "type": "thinking", content: string, } | { "type": "reasoning", content: string, } | { "type": "tool_call", toolCall: AgentToolCall, } | { "type": "status", status: AgentRunStatus, progress?: number, } | { "type": "log", message: string, } | { "type": "warning", message: string, } | { "type": "retry", retry: RetryNotice, message: string, } | { "type": "fallback", fallback: FallbackNotice, message: string, } | { "type": "explore_progress", exploreProgress: ExploreProgress, } | { "type": "explore_findings", exploreFindings: ExploreFindings, } | { "type": "result", result: string, } | { "type": "error", error: string, } | { "type": "cancelled" });
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Agent lifecycle as shown in the UI
+ */
+export type AgentRunStatus = "running" | "waitingfortool" | "completed" | "failed" | "stopped";
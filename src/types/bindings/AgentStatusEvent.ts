@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { AgentRunStatus } from "./AgentRunStatus";
+
+/**
+ * Payload of the global `agent:status` event
+ */
+export type AgentStatusEvent = { v: number, id: string, status: AgentRunStatus, progress?: number, error?: string, };
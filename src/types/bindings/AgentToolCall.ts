@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { JsonValue } from "./serde_json/JsonValue";
+
+export type AgentToolCall = { id: string, tool: string, 
+/**
+ * Parsed arguments; partial calls carry whatever fields could be recovered so far
+ */
+args: JsonValue, isPartial: boolean, 
+/**
+ * Read-only calls run without waiting for the user; always false while partial
+ */
+autoApproved: boolean, };
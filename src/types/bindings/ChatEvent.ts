@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { FallbackNotice } from "./FallbackNotice";
+import type { RetryNotice } from "./RetryNotice";
+import type { TokenUsage } from "./TokenUsage";
+import type { ToolCallDelta } from "./ToolCallDelta";
+import type { JsonValue } from "./serde_json/JsonValue";
+
+/**
+ * A chat stream payload: `{"v":1,"type":"content","content":"..."}`
+ */
+export type ChatEvent = { v: number, } & ({ "type": "content", content: string, } | { "type": "reasoning", content: string, } | { "type": "tool_call", tool_call: ToolCallDelta, } | { "type": "thinking_blocks", thinking_blocks: Array<JsonValue>, } | { "type": "warning", message: string, } | { "type": "retry", retry: RetryNotice, message: string, } | { "type": "fallback", fallback: FallbackNotice, message: string, } | { "type": "done", usage: TokenUsage | null, provider: string, model: string, } | { "type": "cancelled" });
@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { OmittedReference } from "./OmittedReference";
+
+/**
+ * What was sent and what was left out, sent on `{event_id}_context`
+ */
+export type ContextReport = { budgetTokens: number, usedTokens: number, includedReferences: number, omittedReferences: Array<OmittedReference>, 
+/**
+ * Older messages dropped from the start of the history
+ */
+omittedMessages: number, };
@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { SettingSource } from "./SettingSource";
+
+/**
+ * The settings a chat or agent request runs with, sent on `{event_id}_settings`
+ */
+export type EffectiveSettings = { providerId: string, providerSource: SettingSource, model: string, modelSource: SettingSource, ragEnabled: boolean, ragSource: SettingSource, systemPromptSource: SettingSource, 
+/**
+ * Project settings that could not be applied
+ */
+warnings: Array<string>, };
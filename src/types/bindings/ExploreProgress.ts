@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ExploreStage } from "./ExploreStage";
+import type { ScanProgress } from "./ScanProgress";
+
+export type ExploreProgress = { phase: ExploreStage, currentPath?: string, currentFile?: string, progress: ScanProgress, };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { FallbackReason } from "./FallbackReason";
+
+/**
+ * Sent to the frontend when a request moves on to the next model
+ */
+export type FallbackNotice = { fromProvider: string, fromModel: string, toProvider: string, toModel: string, reason: FallbackReason, error: string, };
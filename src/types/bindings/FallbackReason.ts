@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Why the previous target was abandoned
+ */
+export type FallbackReason = "context_limit" | "rate_limited" | "error";
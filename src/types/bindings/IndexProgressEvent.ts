@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { IndexPhase } from "./IndexPhase";
+
+/**
+ * Payload of the global `rag:index_progress` event, sent as a build works through files.
+ * Per-file updates are throttled; the start of each phase and its last file are always sent.
+ */
+export type IndexProgressEvent = { v: number, root: string, phase: IndexPhase, processed: number, total: number, 
+/**
+ * The file just processed, relative to the root
+ */
+file?: string, };
@@ -0,0 +1,28 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { IndexProgressEvent } from "./IndexProgressEvent";
+import type { IndexState } from "./IndexState";
+
+/**
+ * Response of `rag_index_status`
+ */
+export type IndexStatus = { root: string, state: IndexState, 
+/**
+ * Where the running build is; None when no build is running
+ */
+progress?: IndexProgressEvent, indexedFiles: number, chunks: number, 
+/**
+ * Files added, changed or removed since the last build
+ */
+staleFiles: number, 
+/**
+ * Unix time in milliseconds
+ */
+lastBuildMs?: number, 
+/**
+ * Size of `.ifai/index`
+ */
+onDiskBytes: number, 
+/**
+ * Whether there is a semantic index, not just the keyword one
+ */
+semantic: boolean, error?: string, };
@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { IndexState } from "./IndexState";
+
+/**
+ * Payload of the global `rag:index_status` event
+ */
+export type IndexStatusEvent = { v: number, root: string, state: IndexState, 
+/**
+ * Changed files not yet reflected in the index
+ */
+pendingFiles: number, error?: string, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Sent to the frontend before each retry so the UI can explain the pause
+ */
+export type RetryNotice = { attempt: number, maxRetries: number, delayMs: number, reason: string, status: number | null, };
@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DirectoryScan } from "./DirectoryScan";
+
+export type ScanProgress = { total: number, scanned: number, byDirectory: { [key in string]?: DirectoryScan }, };
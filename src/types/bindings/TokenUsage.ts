@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Token counts normalized across providers.
+ *
+ * `prompt_tokens` always includes cached input tokens; `cached_tokens` is the share read from the
+ * cache and `cache_write_tokens` the share written to it.
+ * Likewise `completion_tokens` includes reasoning tokens and `reasoning_tokens` is their share.
+ */
+export type TokenUsage = { prompt_tokens: number, completion_tokens: number, cached_tokens: number, reasoning_tokens: number, cache_write_tokens: number, };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { FunctionDelta } from "./FunctionDelta";
+
+/**
+ * OpenAI-shaped tool call fragment, as sent on chat streams
+ */
+export type ToolCallDelta = { id: string, type: string, function: FunctionDelta, };